@@ -0,0 +1,24 @@
+use std::sync::Arc;
+use axum::{extract::{Request, State}, http::{HeaderMap, StatusCode}, middleware::Next, response::Response};
+
+
+/// header carrying the api key of the admin or of a tenant
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// the admin key from `ADMIN_API_KEY`, `None` leaves the admin routes open for local development
+pub type AdminKey = Option<Arc<str>>;
+
+
+/// the api key sent with a request
+pub fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok())
+}
+
+
+/// middleware for the /admin routes, answers 401 Unauthorized without the right key
+pub async fn require_key(State(admin_key): State<AdminKey>, req: Request, next: Next) -> Result<Response, StatusCode> {
+    match admin_key {
+        Some(key) if api_key(req.headers()) != Some(&*key) => Err(StatusCode::UNAUTHORIZED),
+        _ => Ok(next.run(req).await),
+    }
+}
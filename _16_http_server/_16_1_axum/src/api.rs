@@ -1,57 +1,106 @@
-use axum::{extract::{Path, State}, http::StatusCode, response::Json};
-use uuid::Uuid;
-use crate::{model::Student, handler::save_students, SharedState};
+use axum::{extract::{Path, State}, http::StatusCode, routing::{on, MethodFilter, MethodRouter}};
+use crate::{fields::{Fields, Sparse}, format::{Accept, Body, Encoded}, v1::StudentV1, SharedState};
 
+// the changes every api version makes are in the store crate
+pub use student_store::students::{commit, create, create_many, find_by_email, remove, store_error, update};
+
+
+/// the v1 handler of a method of student.proto, see `bindings::router`
+pub fn wrapper(rpc: &str, filter: MethodFilter) -> Option<MethodRouter<SharedState>> {
+    Some(match rpc {
+        "ListStudents" => on(filter, get_students),
+        "GetStudent" => on(filter, get_student),
+        "CreateStudent" => on(filter, add_student),
+        "UpdateStudent" => on(filter, update_student),
+        "DeleteStudent" => on(filter, delete_student),
+        _ => return None,
+    })
+}
 
 
 /// get all the students from the file - Command "curl -X GET http://127.0.0.1:4500/students"
-pub async fn get_students(State(state): State<SharedState>) -> Json<Vec<Student>> {
-    let students = state.lock().await;
-    Json(students.clone())
+/// other formats than json - curl -X GET http://127.0.0.1:4500/students -H "Accept: text/csv"
+/// only some fields - curl -X GET "http://127.0.0.1:4500/students?fields=id,name"
+pub async fn get_students(Accept(format): Accept, fields: Fields<StudentV1>, State(state): State<SharedState>) -> Encoded<Sparse<Vec<StudentV1>>> {
+    let students = state.students.lock().await;
+    Encoded(format, fields.apply(students.iter().map(StudentV1::from).collect()))
 }
 
 /// get a student by id
 /// curl -X GET http://127.0.0.1:4500/students/{id}
-pub async fn get_student(Path(id) : Path<String>, State(state): State<SharedState>) -> Result<Json<Student>, StatusCode> {
-    let students = state.lock().await;
-    students.iter().find(|s| s.id == id).cloned().map(Json).ok_or(StatusCode::NOT_FOUND)
+pub async fn get_student(Path(id) : Path<String>, Accept(format): Accept, fields: Fields<StudentV1>, State(state): State<SharedState>) -> Result<Encoded<Sparse<StudentV1>>, StatusCode> {
+    let students = state.students.lock().await;
+    students.iter().find(|s| s.id == id).map(|s| Encoded(format, fields.apply(StudentV1::from(s)))).ok_or(StatusCode::NOT_FOUND)
 }
 
 /// add a new student
 /// curl -X POST http://127.0.0.1:4500/students -H "Content-Type: application/json" -d "{ \"name\": \"Aman\", \"email\": \"aman@example.com\", \"mobile\": \"9876543210\" }"
-pub async fn add_student(State(state): State<SharedState>, Json(mut student): Json<Student>) -> StatusCode {
-    student.id = Uuid::new_v4().to_string();
-    let mut students = state.lock().await;
-    students.push(student);
-    save_students(&students);
-    StatusCode::CREATED
+/// curl -X POST http://127.0.0.1:4500/students -H "Content-Type: text/csv" --data-binary $'name,email,mobile\nAman,aman@example.com,9876543210'
+pub async fn add_student(State(state): State<SharedState>, Body(new_student): Body<StudentV1>) -> StatusCode {
+    create(&state, new_student.into_student()).await.map_or_else(|status| status, |_| StatusCode::CREATED)
 }
 
 /// update a student
 /// curl -X PUT http://127.0.0.1:4500/students/{id} -H "Content-Type: application/json" -d "{ \"name\": \"Aman Verasia\", \"email\": \"aman@example.com\", \"mobile\": \"9876543210\" }"
-pub async fn update_student(Path(id): Path<String>, State(state): State<SharedState>, Json(updated_student): Json<Student>) -> StatusCode {
-    let mut students = state.lock().await;
-    if let Some(student) = students.iter_mut().find(|s| s.id == id) {
-        student.name = updated_student.name;
-        student.email = updated_student.email;
-        student.mobile = updated_student.mobile;
-        save_students(&students);
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
-    }
+pub async fn update_student(Path(id): Path<String>, State(state): State<SharedState>, Body(updated_student): Body<StudentV1>) -> StatusCode {
+    update(&state, &id, |student| updated_student.apply_to(student)).await.map_or_else(|status| status, |_| StatusCode::OK)
 }
 
 
 /// delete a student
 /// curl -X DELETE http://127.0.0.1:4500/students/{id}
 pub async fn delete_student(Path(id): Path<String>, State(state): State<SharedState>) -> StatusCode {
-    let mut students = state.lock().await;
-    if students.iter().any(|s| s.id == id) {
-        students.retain(|s| s.id != id);
-        save_students(&students);
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
+    remove(&state, id).await.map_or_else(|status| status, |_| StatusCode::OK)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, crypto::{generate_key, Keys, SharedKeys}, state::AppState};
+    use axum::{body::{to_bytes, Body}, http::Request, Router};
+    use serde_json::{json, Value};
+    use std::sync::{Arc, RwLock};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn emails_stay_unique_and_found_over_a_rotation() {
+        async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+            let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+            let req = Request::builder().method(method).uri(uri).header("content-type", "application/json").body(body).unwrap();
+            let response = app.clone().oneshot(req).await.unwrap();
+            let status = response.status();
+            let data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&data).unwrap_or(Value::Null))
+        }
+        let student = |name: &str, email: &str| Some(json!({ "name": name, "email": email, "mobile": "9876543210" }));
+
+        let dir = tempfile::tempdir().unwrap();
+        let index_key = generate_key();
+        let key_file = |id: &str| {
+            let file = dir.path().join(format!("{}.json", id));
+            std::fs::write(&file, json!({ "keys": [{ "id": id, "key": generate_key() }], "index_key": index_key }).to_string()).unwrap();
+            Keys::load(file).unwrap()
+        };
+        let shared: SharedKeys = Arc::new(RwLock::new(key_file("k1")));
+        let state = Arc::new(AppState::open(dir.path().join("students.json"), Some(shared.clone())).unwrap());
+        let app = app(state.clone());
+        assert_eq!(send(&app, "POST", "/students", student("Aman", "aman@example.com")).await.0, StatusCode::CREATED);
+        assert_eq!(send(&app, "POST", "/students", student("Bela", "bela@example.com")).await.0, StatusCode::CREATED);
+
+        // the first master key is rotated out, the index key stays
+        *shared.write().unwrap() = key_file("k2");
+        assert!(state.reencrypt(&mut *state.students.lock().await).unwrap());
+
+        assert_eq!(send(&app, "POST", "/students", student("Other", " AMAN@example.com")).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, found) = send(&app, "GET", "/v2/students?email=Aman@Example.com", None).await;
+        assert_eq!(found.as_array().unwrap().len(), 1);
+        assert_eq!(found[0]["name"]["display"], "Aman");
+        let bela = send(&app, "GET", "/v2/students?email=bela@example.com", None).await.1[0]["id"].as_str().unwrap().to_string();
+        let taken = send(&app, "PUT", &format!("/students/{}", bela), student("Bela", "aman@example.com")).await.0;
+        assert_eq!(taken, StatusCode::UNPROCESSABLE_ENTITY);
+        // keeping its own email is fine
+        assert_eq!(send(&app, "PUT", &format!("/students/{}", bela), student("Bella", "bela@example.com")).await.0, StatusCode::OK);
+        assert_eq!(state.students.lock().await.len(), 2);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,800 @@
+use std::{collections::{BTreeMap, HashSet}, fmt, fs, io::{self, Read, Write}, path::{Path as FsPath, PathBuf}, sync::{atomic::Ordering, Arc}};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::{
+    admin::{self, AdminKey},
+    api::store_error,
+    crypto::ENCRYPTED_PREFIX,
+    model::{validate_students, Student},
+    photo,
+    tenant::{NewTenant, TenantInfo, Tenants},
+    watcher::diff,
+    webhook::{Webhook, Webhooks},
+    state::{ChangeEvent, ChangeKind},
+    SharedState,
+};
+
+
+/// version of the snapshot layout, raised whenever `Snapshot` changes in a way older releases can not read
+pub const SCHEMA_VERSION: u32 = 1;
+
+
+/// everything the server stores, as written to a backup file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub schema_version: u32,
+    pub created_at: DateTime<Utc>,
+    pub students: Vec<Student>,
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub tenants: Vec<TenantSnapshot>,
+}
+
+impl Snapshot {
+    /// the students of the default store and of every tenant
+    pub fn all_students(&self) -> impl Iterator<Item = &Student> {
+        self.students.iter().chain(self.tenants.iter().flat_map(|t| &t.students))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TenantSnapshot {
+    pub info: TenantInfo,
+    pub students: Vec<Student>,
+}
+
+/// entry of the backup index, `GET /admin/backups` lists these
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BackupInfo {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub schema_version: u32,
+    // students over all stores
+    pub students: usize,
+    // size of the compressed file in bytes
+    pub size: u64,
+    // SHA-256 of the compressed file, hex encoded
+    pub sha256: String,
+}
+
+/// what a restore changes, or would change for a dry run
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RestorePlan {
+    pub backup: String,
+    pub dry_run: bool,
+    pub students: Changes,
+    pub tenants: BTreeMap<String, Changes>,
+    // tenants in the backup which no longer exist and are created again
+    pub recreated_tenants: Vec<String>,
+    pub webhooks: Changes,
+}
+
+/// ids of the records a restore creates, updates and deletes
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Changes {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+
+#[derive(Debug)]
+pub enum BackupError {
+    NotFound,
+    // the file does not match the checksum in the index or can not be decoded
+    Corrupt(String),
+    // written by a newer release
+    UnsupportedSchema(u32),
+    // the content does not pass the validation of the current release
+    Invalid(String),
+    Io(io::Error),
+    Store(StatusCode),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::NotFound => write!(f, "backup not found"),
+            BackupError::Corrupt(e) => write!(f, "backup is corrupt: {}", e),
+            BackupError::UnsupportedSchema(v) => write!(f, "backup has schema version {}, this release reads up to {}", v, SCHEMA_VERSION),
+            BackupError::Invalid(e) => write!(f, "backup content is invalid: {}", e),
+            BackupError::Io(e) => write!(f, "could not access the backups: {}", e),
+            BackupError::Store(status) => write!(f, "could not update the store: {}", status),
+        }
+    }
+}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl IntoResponse for BackupError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            BackupError::NotFound => StatusCode::NOT_FOUND,
+            BackupError::Corrupt(_) | BackupError::UnsupportedSchema(_) | BackupError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            BackupError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackupError::Store(status) => *status,
+        };
+        if status.is_server_error() {
+            eprintln!("{}", self);
+        }
+        (status, self.to_string()).into_response()
+    }
+}
+
+
+/// snapshots of all stores, webhooks and tenants in `dir`, indexed by `dir/index.json`
+pub struct Backups {
+    dir: PathBuf,
+    state: SharedState,
+    webhooks: Arc<Webhooks>,
+    tenants: Arc<Tenants>,
+    // serializes creating and restoring, reading the stores does not wait for it
+    index: tokio::sync::Mutex<()>,
+}
+
+impl Backups {
+    pub fn new(dir: impl Into<PathBuf>, state: SharedState, webhooks: Arc<Webhooks>, tenants: Arc<Tenants>) -> Self {
+        Backups { dir: dir.into(), state, webhooks, tenants, index: tokio::sync::Mutex::new(()) }
+    }
+
+    pub fn list(&self) -> Result<Vec<BackupInfo>, BackupError> {
+        match fs::read_to_string(self.dir.join("index.json")) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| BackupError::Corrupt(format!("index: {}", e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_index(&self, index: &[BackupInfo]) -> Result<(), BackupError> {
+        fs::write(self.dir.join("index.json"), serde_json::to_string_pretty(index).map_err(io::Error::other)?)?;
+        Ok(())
+    }
+
+    fn file(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json.gz", id))
+    }
+
+    /// the photo files of all backups, named by their hash like in the stores so every file is kept once
+    fn photos_dir(&self) -> PathBuf {
+        self.dir.join("photos")
+    }
+
+    /// copy the students of a store and their photo files. The files are copied with the lock held,
+    /// the store removes the files of replaced photos as soon as it can.
+    async fn copy_store(&self, store: &SharedState) -> Result<Vec<Student>, BackupError> {
+        let students = store.students.lock().await;
+        for photo in students.iter().filter_map(|s| s.photo.as_ref()) {
+            photo::copy_files(photo, &store.photos_dir(), &self.photos_dir())?;
+        }
+        Ok(students.clone())
+    }
+
+    /// copy every store while holding its lock only for the copy, so writes go on while the file is written.
+    /// Every store is consistent in itself, changes landing between two stores may be in one and not the other.
+    async fn snapshot(&self) -> Result<Snapshot, BackupError> {
+        let students = self.copy_store(&self.state).await?;
+        let mut tenants = Vec::new();
+        for info in self.tenants.list() {
+            if let Some(store) = self.tenants.store(&info.name) {
+                let students = self.copy_store(&store).await?;
+                tenants.push(TenantSnapshot { info, students });
+            }
+        }
+        Ok(Snapshot { schema_version: SCHEMA_VERSION, created_at: Utc::now(), students, webhooks: self.webhooks.list(), tenants })
+    }
+
+    /// the compressed file content of a snapshot, backups do not undo the encryption of the data files
+    fn encode(&self, mut snapshot: Snapshot) -> Result<Vec<u8>, BackupError> {
+        if let Some(keys) = &self.state.keys {
+            let keys = keys.read().unwrap();
+            let stores = std::iter::once(&mut snapshot.students).chain(snapshot.tenants.iter_mut().map(|t| &mut t.students));
+            for student in stores.flatten() {
+                for_personal_fields(student, |field, value| Ok(keys.encrypt(field, value))).expect("encrypting does not fail");
+            }
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, &snapshot).map_err(io::Error::other)?;
+        Ok(encoder.finish()?)
+    }
+
+    /// write the file of a backup, to a temporary name first so a crash never leaves a half written backup in the index
+    fn write(&self, id: &str, data: &[u8]) -> Result<(), BackupError> {
+        fs::create_dir_all(&self.dir)?;
+        let partial = self.dir.join(format!("{}.partial", id));
+        fs::File::create(&partial)?.write_all(data)?;
+        fs::rename(&partial, self.file(id))?;
+        Ok(())
+    }
+
+    pub async fn create(&self) -> Result<BackupInfo, BackupError> {
+        // held while the photos are copied too, `scrub` would remove files no backup in the index has yet
+        let _guard = self.index.lock().await;
+        let snapshot = self.snapshot().await?;
+        let info = BackupInfo {
+            id: format!("{}-{}", snapshot.created_at.format("%Y%m%dT%H%M%SZ"), &Uuid::new_v4().simple().to_string()[..8]),
+            created_at: snapshot.created_at,
+            schema_version: snapshot.schema_version,
+            students: snapshot.all_students().count(),
+            size: 0,
+            sha256: String::new(),
+        };
+        let data = self.encode(snapshot)?;
+        let info = BackupInfo { size: data.len() as u64, sha256: hex::encode(Sha256::digest(&data)), ..info };
+
+        self.write(&info.id, &data)?;
+        let mut index = self.list()?;
+        index.push(info.clone());
+        self.save_index(&index)?;
+        Ok(info)
+    }
+
+    /// write every backup holding an erased student again with the student pseudonymized, and remove
+    /// the photo files no backup refers to any more. Returns the ids of the backups written again.
+    /// Call `record_erasure` first, `load` pseudonymizes the erased students.
+    pub async fn scrub(&self, student_id: &str) -> Result<Vec<String>, BackupError> {
+        let _guard = self.index.lock().await;
+        let mut index = self.list()?;
+        let mut scrubbed = Vec::new();
+        let mut photos = HashSet::new();
+        for info in &mut index {
+            let snapshot = self.load(&info.id)?;
+            photos.extend(snapshot.all_students().filter_map(|s| s.photo.as_ref()).flat_map(|photo| photo.files().map(str::to_string)));
+            if !snapshot.all_students().any(|s| s.id == student_id) {
+                continue;
+            }
+            let data = self.encode(snapshot)?;
+            self.write(&info.id, &data)?;
+            info.size = data.len() as u64;
+            info.sha256 = hex::encode(Sha256::digest(&data));
+            scrubbed.push(info.id.clone());
+        }
+        self.save_index(&index)?;
+
+        if let Ok(entries) = fs::read_dir(self.photos_dir()) {
+            for entry in entries.flatten() {
+                if !entry.file_name().to_str().is_some_and(|name| photos.contains(name)) {
+                    fs::remove_file(entry.path())?;
+                }
+            }
+        }
+        Ok(scrubbed)
+    }
+
+    /// read, verify and migrate a backup, students erased since it was made come out pseudonymized
+    pub fn load(&self, id: &str) -> Result<Snapshot, BackupError> {
+        let info = self.list()?.into_iter().find(|b| b.id == id).ok_or(BackupError::NotFound)?;
+        let data = fs::read(self.file(&info.id))?;
+        if hex::encode(Sha256::digest(&data)) != info.sha256 {
+            return Err(BackupError::Corrupt("checksum mismatch".to_string()));
+        }
+        let mut json = Vec::new();
+        GzDecoder::new(&data[..]).read_to_end(&mut json).map_err(|e| BackupError::Corrupt(e.to_string()))?;
+        let value: serde_json::Value = serde_json::from_slice(&json).map_err(|e| BackupError::Corrupt(e.to_string()))?;
+        let mut snapshot = migrate(value)?;
+        let keys = self.state.keys.as_ref().map(|keys| keys.read().unwrap());
+        let stores = std::iter::once(&mut snapshot.students).chain(snapshot.tenants.iter_mut().map(|t| &mut t.students));
+        for student in stores.flatten() {
+            for_personal_fields(student, |field, value| match &keys {
+                Some(keys) => keys.decrypt(field, value).map_err(|e| BackupError::Corrupt(e.to_string())),
+                None if value.starts_with(ENCRYPTED_PREFIX) => Err(BackupError::Corrupt("backup is encrypted but no master key is configured".to_string())),
+                None => Ok(value.to_string()),
+            })?;
+        }
+        drop(keys);
+
+        let erased = self.erasures()?;
+        let stores = std::iter::once(&mut snapshot.students).chain(snapshot.tenants.iter_mut().map(|t| &mut t.students));
+        for student in stores.flatten().filter(|s| erased.contains(&s.id)) {
+            student.pseudonymize();
+        }
+
+        validate_students(&snapshot.students).map_err(BackupError::Invalid)?;
+        for tenant in &snapshot.tenants {
+            validate_students(&tenant.students).map_err(|e| BackupError::Invalid(format!("tenant {}: {}", tenant.info.name, e)))?;
+        }
+        Ok(snapshot)
+    }
+
+    /// remember an erased student, restores pseudonymize it again so old backups do not bring it back
+    pub fn record_erasure(&self, student_id: &str) -> Result<(), BackupError> {
+        let mut erased = self.erasures()?;
+        if !erased.iter().any(|id| id == student_id) {
+            erased.push(student_id.to_string());
+            fs::create_dir_all(&self.dir)?;
+            fs::write(self.dir.join("erasures.json"), serde_json::to_string_pretty(&erased).map_err(io::Error::other)?)?;
+        }
+        Ok(())
+    }
+
+    /// ids of the erased students, see `record_erasure`
+    pub fn erasures(&self) -> Result<Vec<String>, BackupError> {
+        match fs::read_to_string(self.dir.join("erasures.json")) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| BackupError::Corrupt(format!("erasures: {}", e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// ids of the backups which hold data of a student
+    pub fn containing(&self, student_id: &str) -> Result<Vec<String>, BackupError> {
+        let mut ids = Vec::new();
+        for info in self.list()? {
+            if self.load(&info.id)?.all_students().any(|s| s.id == student_id) {
+                ids.push(info.id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// bring all stores back to the content of a backup, or only report what would change for a dry run.
+    /// Tenants created after the backup are left alone, tenants deleted since are created again.
+    ///
+    /// Every store is locked and read before the first one is written. When a store can not take
+    /// its students, the stores written before it get their students back. A dry run writes nothing.
+    pub async fn restore(&self, id: &str, dry_run: bool) -> Result<RestorePlan, BackupError> {
+        let snapshot = self.load(id)?;
+        let _guard = self.index.lock().await;
+        let mut plan = RestorePlan { backup: id.to_string(), dry_run, ..Default::default() };
+
+        // the default store first, then the tenants of the backup, `None` for a store which is gone
+        let (mut tenants, mut stores, mut restored) = (vec![None], vec![Some(self.state.clone())], vec![snapshot.students]);
+        for tenant in snapshot.tenants {
+            stores.push(self.tenants.store(&tenant.info.name));
+            restored.push(tenant.students);
+            tenants.push(Some(tenant.info));
+        }
+        let mut locked = Vec::new();
+        for store in &stores {
+            locked.push(match store {
+                Some(store) => Some(store.students.lock().await),
+                None => None,
+            });
+        }
+
+        // stage every store, nothing is written yet
+        for (((tenant, store), students), restored) in tenants.iter().zip(&stores).zip(&locked).zip(&mut restored) {
+            let current = match (store, students) {
+                (Some(store), Some(students)) => {
+                    store.check_storage()?;
+                    store.peek(students).map_err(|e| BackupError::Store(store_error(e)))?
+                }
+                _ => Vec::new(),
+            };
+            keep_photos(store.as_ref(), restored, &self.photos_dir());
+            let changes = changes_of(&diff(&current, restored));
+            match tenant {
+                None => plan.students = changes,
+                Some(info) => {
+                    if store.is_none() {
+                        plan.recreated_tenants.push(info.name.clone());
+                    }
+                    plan.tenants.insert(info.name.clone(), changes);
+                }
+            }
+        }
+        plan.webhooks = changes(&self.webhooks.list(), &snapshot.webhooks, |w| &w.id);
+        if dry_run {
+            return Ok(plan);
+        }
+
+        let (mut created, mut recreated) = (Vec::new(), Vec::new());
+        for (tenant, store) in tenants.iter().zip(&stores) {
+            if let (Some(info), None) = (tenant, store) {
+                match self.recreate(info) {
+                    Ok(store) => {
+                        created.push(store);
+                        recreated.push(info.name.as_str());
+                    }
+                    Err(e) => {
+                        self.remove_tenants(&recreated);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        let mut created_locked = Vec::new();
+        for store in &created {
+            created_locked.push(store.students.lock().await);
+        }
+        let mut fresh = created.iter().zip(created_locked);
+        let mut targets = Vec::new();
+        for ((store, students), restored) in stores.iter().zip(locked).zip(restored) {
+            let (store, students) = match (store, students) {
+                (Some(store), Some(students)) => (store, students),
+                _ => fresh.next().expect("every missing tenant was created"),
+            };
+            targets.push((store, students, restored));
+        }
+
+        // the stores report not ready while they are being restored
+        for (store, _, _) in &targets {
+            store.recovering.store(true, Ordering::Relaxed);
+        }
+        let mut written = Vec::new();
+        let mut failure = None;
+        for (store, students, restored) in &mut targets {
+            match write_store(store, students, std::mem::take(restored), &self.photos_dir()) {
+                Ok(before) => written.push(before),
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        if failure.is_none() {
+            failure = self.webhooks.replace(snapshot.webhooks).err().map(BackupError::Store);
+        }
+
+        match &failure {
+            Some(_) => {
+                for ((store, students, _), before) in targets.iter_mut().zip(written) {
+                    **students = before;
+                    if let Err(e) = store.sync(students) {
+                        eprintln!("could not put back {} after a failed restore: {}", store.data_file.display(), e);
+                    }
+                }
+            }
+            None => {
+                for ((store, students, _), before) in targets.iter().zip(written) {
+                    for event in diff(&before, students) {
+                        store.notify(event);
+                    }
+                }
+            }
+        }
+        let stores: Vec<SharedState> = targets.iter().map(|(store, _, _)| (*store).clone()).collect();
+        drop(targets);
+        for store in &stores {
+            store.recovering.store(false, Ordering::Relaxed);
+        }
+        if let Some(e) = failure {
+            self.remove_tenants(&recreated);
+            return Err(e);
+        }
+        for store in &stores {
+            photo::prune(store).await;
+        }
+        Ok(plan)
+    }
+
+    /// create a tenant of a backup again, with an empty store
+    fn recreate(&self, info: &TenantInfo) -> Result<SharedState, BackupError> {
+        let new = NewTenant { name: info.name.clone(), max_students: info.max_students, api_keys: Some(info.api_keys.clone()) };
+        self.tenants.create(new).map_err(BackupError::Store)?;
+        if info.suspended {
+            self.tenants.set_suspended(&info.name, true).map_err(BackupError::Store)?;
+        }
+        self.tenants.store(&info.name).ok_or(BackupError::Store(StatusCode::INTERNAL_SERVER_ERROR))
+    }
+
+    /// remove the tenants a failed restore created
+    fn remove_tenants(&self, names: &[&str]) {
+        for name in names {
+            if let Err(e) = self.tenants.delete(name) {
+                eprintln!("could not remove tenant {} after a failed restore: {}", name, e);
+            }
+        }
+    }
+}
+
+
+/// read a snapshot of any schema version this release knows, upgrading older layouts
+fn migrate(value: serde_json::Value) -> Result<Snapshot, BackupError> {
+    let version = value
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| BackupError::Corrupt("no schema version".to_string()))? as u32;
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(BackupError::UnsupportedSchema(version));
+    }
+    // version 1 is the current layout, future versions add their upgrade steps here
+    serde_json::from_value(value).map_err(|e| BackupError::Invalid(e.to_string()))
+}
+
+/// replace every encrypted field of a student like the data file does, see `handler::ENCRYPTED_FIELDS`
+fn for_personal_fields(student: &mut Student, mut change: impl FnMut(&str, &str) -> Result<String, BackupError>) -> Result<(), BackupError> {
+    student.email = change("email", &student.email)?;
+    student.mobile = change("mobile", &student.mobile)?;
+    for contact in &mut student.contacts {
+        contact.value = change("contact", &contact.value)?;
+    }
+    Ok(())
+}
+
+/// put the restored students into a locked store, returns the students it held before
+fn write_store(store: &SharedState, students: &mut Vec<Student>, mut restored: Vec<Student>, photos: &FsPath) -> Result<Vec<Student>, BackupError> {
+    store.sync(students).map_err(|e| BackupError::Store(store_error(e)))?;
+    restore_photos(store, &mut restored, photos)?;
+    let before = std::mem::replace(students, restored);
+    if let Err(e) = store.sync(students) {
+        *students = before;
+        return Err(BackupError::Store(store_error(e)));
+    }
+    Ok(before)
+}
+
+/// drop the photos of restored students whose files are neither in the store nor with the backups.
+/// Backups made before photos were kept with them may refer to files which are gone.
+fn keep_photos(store: Option<&SharedState>, restored: &mut [Student], photos: &FsPath) {
+    let dir = store.map(|store| store.photos_dir());
+    for student in restored {
+        let Some(photo) = &student.photo else { continue };
+        let found = photo.files().iter().all(|name| dir.as_ref().is_some_and(|dir| dir.join(name).exists()) || photos.join(name).exists());
+        if !found {
+            student.photo = None;
+        }
+    }
+}
+
+/// copy the photo files of the restored students back into the store, must be called with the
+/// students locked so `photo::prune` does not remove them again
+fn restore_photos(store: &SharedState, restored: &mut [Student], photos: &FsPath) -> Result<(), BackupError> {
+    let dir = store.photos_dir();
+    for student in restored {
+        let Some(photo) = &student.photo else { continue };
+        if !photo::copy_files(photo, photos, &dir)? {
+            student.photo = None;
+        }
+    }
+    Ok(())
+}
+
+fn changes_of(events: &[ChangeEvent]) -> Changes {
+    let mut changes = Changes::default();
+    for event in events {
+        let ids = match event.kind {
+            ChangeKind::Created => &mut changes.created,
+            ChangeKind::Updated => &mut changes.updated,
+            ChangeKind::Deleted => &mut changes.deleted,
+        };
+        ids.push(event.id.clone());
+    }
+    changes
+}
+
+fn changes<T: PartialEq>(current: &[T], restored: &[T], id: impl Fn(&T) -> &String) -> Changes {
+    let mut changes = Changes::default();
+    for item in restored {
+        match current.iter().find(|c| id(c) == id(item)) {
+            None => changes.created.push(id(item).clone()),
+            Some(c) if c != item => changes.updated.push(id(item).clone()),
+            Some(_) => {}
+        }
+    }
+    for item in current {
+        if !restored.iter().any(|r| id(r) == id(item)) {
+            changes.deleted.push(id(item).clone());
+        }
+    }
+    changes
+}
+
+
+/// the /admin/backups routes, protected by the admin key
+pub fn routes(backups: Arc<Backups>, admin_key: AdminKey) -> Router {
+    Router::new()
+        .route("/admin/backups", get(list_backups).post(create_backup))
+        .route("/admin/backups/{id}/restore", post(restore_backup))
+        .route_layer(from_fn_with_state(admin_key, admin::require_key))
+        .with_state(backups)
+}
+
+
+#[derive(Deserialize)]
+struct RestoreQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// curl -X GET http://127.0.0.1:4500/admin/backups -H "X-Api-Key: {admin key}"
+async fn list_backups(State(backups): State<Arc<Backups>>) -> Result<Json<Vec<BackupInfo>>, BackupError> {
+    backups.list().map(Json)
+}
+
+/// curl -X POST http://127.0.0.1:4500/admin/backups -H "X-Api-Key: {admin key}"
+async fn create_backup(State(backups): State<Arc<Backups>>) -> Result<(StatusCode, Json<BackupInfo>), BackupError> {
+    backups.create().await.map(|info| (StatusCode::CREATED, Json(info)))
+}
+
+/// preview: curl -X POST "http://127.0.0.1:4500/admin/backups/{id}/restore?dry_run=true" -H "X-Api-Key: {admin key}"
+/// restore: curl -X POST http://127.0.0.1:4500/admin/backups/{id}/restore -H "X-Api-Key: {admin key}"
+async fn restore_backup(State(backups): State<Arc<Backups>>, Path(id): Path<String>, Query(query): Query<RestoreQuery>) -> Result<Json<RestorePlan>, BackupError> {
+    backups.restore(&id, query.dry_run).await.map(Json)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, state::AppState, webhook::RetryPolicy};
+    use axum::{body::{to_bytes, Body}, http::Request};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    struct Setup {
+        app: Router,
+        backups: Arc<Backups>,
+        dir: tempfile::TempDir,
+    }
+
+    fn setup() -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let webhooks = Arc::new(Webhooks::load(dir.path().join("webhooks.json"), RetryPolicy::default()).unwrap());
+        let tenants = Arc::new(Tenants::load(dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+        let backups = Arc::new(Backups::new(dir.path().join("backups"), state.clone(), webhooks, tenants.clone()));
+        let app = crate::tenant::scoped(app(state).merge(routes(backups.clone(), None)), tenants, None);
+        Setup { app, backups, dir }
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json").body(body).unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        let status = response.status();
+        let data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&data).unwrap_or(Value::Null))
+    }
+
+    fn student(name: &str) -> Option<Value> {
+        Some(json!({ "name": name, "email": format!("{}@example.com", name.to_lowercase()), "mobile": "9876543210" }))
+    }
+
+    #[tokio::test]
+    async fn restore_previews_and_applies_the_diff() {
+        let s = setup();
+        send(&s.app, "POST", "/students", student("Aman")).await;
+        send(&s.app, "POST", "/students", student("Bela")).await;
+        let (status, backup) = send(&s.app, "POST", "/admin/backups", None).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(backup["students"], 2);
+        let id = backup["id"].as_str().unwrap().to_string();
+
+        // changes after the backup
+        let (_, students) = send(&s.app, "GET", "/students", None).await;
+        let aman = students[0]["id"].as_str().unwrap().to_string();
+        let bela = students[1]["id"].as_str().unwrap().to_string();
+        send(&s.app, "DELETE", &format!("/students/{}", aman), None).await;
+        send(&s.app, "PUT", &format!("/students/{}", bela), student("Bella")).await;
+        send(&s.app, "POST", "/students", student("Chris")).await;
+
+        let (status, plan) = send(&s.app, "POST", &format!("/admin/backups/{}/restore?dry_run=true", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(plan["students"]["created"], json!([aman]));
+        assert_eq!(plan["students"]["updated"], json!([bela]));
+        assert_eq!(plan["students"]["deleted"].as_array().unwrap().len(), 1);
+        assert_eq!(send(&s.app, "GET", "/students", None).await.1.as_array().unwrap().len(), 2);
+
+        let (status, plan) = send(&s.app, "POST", &format!("/admin/backups/{}/restore", id), None).await;
+        assert_eq!((status, &plan["dry_run"]), (StatusCode::OK, &json!(false)));
+        let (_, restored) = send(&s.app, "GET", "/students", None).await;
+        let names: Vec<&str> = restored.as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["Aman", "Bela"]);
+
+        let (_, listed) = send(&s.app, "GET", "/admin/backups", None).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deleted_tenants_are_recreated() {
+        let s = setup();
+        let (_, tenant) = send(&s.app, "POST", "/admin/tenants", Some(json!({ "name": "north" }))).await;
+        let key = tenant["api_keys"][0].as_str().unwrap().to_string();
+        let req = Request::post("/t/north/students")
+            .header("content-type", "application/json")
+            .header("x-api-key", &key)
+            .body(Body::from(student("Aman").unwrap().to_string()))
+            .unwrap();
+        assert_eq!(s.app.clone().oneshot(req).await.unwrap().status(), StatusCode::CREATED);
+
+        let info = s.backups.create().await.unwrap();
+        send(&s.app, "DELETE", "/admin/tenants/north", None).await;
+
+        let plan = s.backups.restore(&info.id, false).await.unwrap();
+        assert_eq!(plan.recreated_tenants, vec!["north".to_string()]);
+        let req = Request::get("/t/north/students").header("x-api-key", &key).body(Body::empty()).unwrap();
+        let data = to_bytes(s.app.clone().oneshot(req).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&data).unwrap()[0]["name"], "Aman");
+    }
+
+    #[tokio::test]
+    async fn photos_come_back_with_their_students() {
+        let s = setup();
+        send(&s.app, "POST", "/students", student("Aman")).await;
+        let (_, students) = send(&s.app, "GET", "/students", None).await;
+        let aman = students[0]["id"].as_str().unwrap().to_string();
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(80, 80, image::Rgb([200, 40, 40])));
+        let png = imaging::encode(&image, imaging::Format::Png, 0).unwrap();
+        let req = Request::put(format!("/students/{}/photo", aman)).header("content-type", "image/png").body(Body::from(png)).unwrap();
+        assert_eq!(s.app.clone().oneshot(req).await.unwrap().status(), StatusCode::OK);
+        async fn photo(app: &Router, id: &str) -> (StatusCode, axum::body::Bytes) {
+            let response = app.clone().oneshot(Request::get(format!("/students/{}/photo", id)).body(Body::empty()).unwrap()).await.unwrap();
+            (response.status(), to_bytes(response.into_body(), usize::MAX).await.unwrap())
+        }
+        let (_, uploaded) = photo(&s.app, &aman).await;
+
+        let info = s.backups.create().await.unwrap();
+        send(&s.app, "DELETE", &format!("/students/{}", aman), None).await;
+        assert_eq!(fs::read_dir(s.dir.path().join("students.photos")).unwrap().count(), 0);
+
+        s.backups.restore(&info.id, false).await.unwrap();
+        assert_eq!(photo(&s.app, &aman).await, (StatusCode::OK, uploaded));
+    }
+
+    #[tokio::test]
+    async fn a_dry_run_leaves_the_stores_alone() {
+        let s = setup();
+        send(&s.app, "POST", "/students", student("Aman")).await;
+        let info = s.backups.create().await.unwrap();
+        send(&s.app, "POST", "/students", student("Bela")).await;
+
+        // Aman is renamed by hand, the watcher did not see it yet
+        let file = s.dir.path().join("students.json");
+        let mut students: Vec<Value> = serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+        students[0]["name"] = json!("Aman Verasia");
+        let (aman, bela) = (students[0]["id"].as_str().unwrap().to_string(), students[1]["id"].as_str().unwrap().to_string());
+        fs::write(&file, serde_json::to_vec_pretty(&students).unwrap()).unwrap();
+        let mut events = s.backups.state.events.subscribe();
+
+        let plan = s.backups.restore(&info.id, true).await.unwrap();
+        assert_eq!(plan.students, Changes { updated: vec![aman], deleted: vec![bela], ..Default::default() });
+        let students = s.backups.state.students.lock().await;
+        assert_eq!((students.len(), students[0].name.as_str()), (2, "Aman"));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn a_store_which_can_not_be_restored_leaves_all_stores_as_they_were() {
+        let s = setup();
+        send(&s.app, "POST", "/admin/tenants", Some(json!({ "name": "north" }))).await;
+        send(&s.app, "POST", "/students", student("Aman")).await;
+        let info = s.backups.create().await.unwrap();
+        send(&s.app, "POST", "/students", student("Bela")).await;
+
+        // the data file of the tenant can not be read
+        let tenant_file = s.dir.path().join("tenants").join("north").join("students.json");
+        fs::create_dir(&tenant_file).unwrap();
+        assert!(s.backups.restore(&info.id, false).await.is_err());
+        assert_eq!(s.backups.state.students.lock().await.len(), 2);
+        assert!(!s.backups.state.recovering.load(Ordering::Relaxed));
+
+        fs::remove_dir(&tenant_file).unwrap();
+        s.backups.restore(&info.id, false).await.unwrap();
+        assert_eq!(s.backups.state.students.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn damaged_and_newer_backups_are_refused() {
+        let s = setup();
+        send(&s.app, "POST", "/students", student("Aman")).await;
+        let info = s.backups.create().await.unwrap();
+
+        let file = s.dir.path().join("backups").join(format!("{}.json.gz", info.id));
+        let mut data = fs::read(&file).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(&file, &data).unwrap();
+        let (status, _) = send(&s.app, "POST", &format!("/admin/backups/{}/restore?dry_run=true", info.id), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let newer = json!({ "schema_version": SCHEMA_VERSION + 1, "created_at": Utc::now(), "students": [], "webhooks": [] });
+        assert!(matches!(migrate(newer), Err(BackupError::UnsupportedSchema(_))));
+        assert_eq!(send(&s.app, "POST", "/admin/backups/missing/restore", None).await.0, StatusCode::NOT_FOUND);
+    }
+}
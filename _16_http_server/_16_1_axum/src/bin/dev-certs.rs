@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+use studet_api::tls::dev;
+
+
+/// generate a self-signed CA with server and client certificates for local https testing
+///
+/// cargo run --bin dev-certs -- certs
+/// TLS_CERT=certs/server.pem TLS_KEY=certs/server-key.pem TLS_CLIENT_CA=certs/ca.pem cargo run
+/// curl --cacert certs/ca.pem --cert certs/client.pem --key certs/client-key.pem https://localhost:4500/students
+fn main() {
+    let dir = std::env::args().nth(1).map(PathBuf::from).unwrap_or_else(|| PathBuf::from("certs"));
+    if let Err(e) = dev::generate(&dir) {
+        eprintln!("could not generate certificates: {}", e);
+        std::process::exit(1);
+    }
+    println!("wrote dev certificates to {}", dir.display());
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use axum::{routing::{MethodFilter, MethodRouter}, Router};
+use student_store::bindings::{bindings, POOL};
+use crate::SharedState;
+
+
+/// the handler of one api version for a method of student.proto, like `api::wrapper` for v1.
+/// `None` for methods the version has no route for.
+pub type Wrapper = fn(rpc: &str, filter: MethodFilter) -> Option<MethodRouter<SharedState>>;
+
+
+/// the student routes of an api version, generated from the `google.api.http` options in
+/// student.proto - the same routes the gRPC gateway serves. The paths are the ones of the
+/// bindings without their `/v1` prefix, the version is where the router is nested.
+pub fn router(wrapper: Wrapper) -> Router<SharedState> {
+    let mut paths: HashMap<String, MethodRouter<SharedState>> = HashMap::new();
+    for binding in bindings(&POOL) {
+        let path = binding.path.strip_prefix("/v1").unwrap_or(&binding.path).to_string();
+        let filter = MethodFilter::try_from(binding.method.clone()).expect("google.api.http only has methods axum knows");
+        let Some(handler) = wrapper(binding.name(), filter) else { continue };
+        let route = match paths.remove(&path) {
+            Some(existing) => existing.merge(handler),
+            None => handler,
+        };
+        paths.insert(path, route);
+    }
+    paths.into_iter().fold(Router::new(), |router, (path, route)| router.route(&path, route))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api, v2};
+
+    #[test]
+    fn every_binding_has_a_route_in_every_version() {
+        for binding in bindings(&POOL) {
+            let filter = MethodFilter::try_from(binding.method.clone()).unwrap();
+            assert!(api::wrapper(binding.name(), filter).is_some(), "v1 has no route for {}", binding.rpc);
+            assert!(v2::wrapper(binding.name(), filter).is_some(), "v2 has no route for {}", binding.rpc);
+        }
+        assert!(api::wrapper("WatchStudents", MethodFilter::GET).is_none());
+    }
+}
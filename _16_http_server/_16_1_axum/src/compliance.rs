@@ -0,0 +1,319 @@
+use std::{fs::{self, OpenOptions}, io::{self, Write}, path::PathBuf, sync::{Arc, Mutex}};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{
+    admin::{self, AdminKey},
+    api::update,
+    backup::Backups,
+    model::Student,
+    photo,
+    tenant::Tenants,
+    webhook::{Delivery, Webhooks},
+    SharedState,
+};
+
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceAction {
+    Export,
+    Erase,
+}
+
+/// one line of the compliance log, it never holds personal data besides the student id
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ComplianceEntry {
+    pub at: DateTime<Utc>,
+    pub action: ComplianceAction,
+    pub student_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+/// append-only log of exports and erasures, one json object per line
+pub struct ComplianceLog {
+    file: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl ComplianceLog {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        ComplianceLog { file: file.into(), lock: Mutex::new(()) }
+    }
+
+    pub fn record(&self, action: ComplianceAction, student_id: &str, details: Option<String>) -> io::Result<ComplianceEntry> {
+        let entry = ComplianceEntry { at: Utc::now(), action, student_id: student_id.to_string(), details };
+        let line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        let _guard = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.file)?;
+        writeln!(file, "{}", line)?;
+        Ok(entry)
+    }
+
+    pub fn entries_for(&self, student_id: &str) -> io::Result<Vec<ComplianceEntry>> {
+        let data = match fs::read_to_string(&self.file) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        Ok(data
+            .lines()
+            .filter_map(|line| serde_json::from_str::<ComplianceEntry>(line).ok())
+            .filter(|entry| entry.student_id == student_id)
+            .collect())
+    }
+}
+
+
+/// everything stored about one student, the answer to `GET /students/{id}/export`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Export {
+    pub exported_at: DateTime<Utc>,
+    pub student: Student,
+    // exports and erasures of this student, including this export
+    pub audit: Vec<ComplianceEntry>,
+    // webhook payloads about the student which are still kept
+    pub webhook_deliveries: Vec<Delivery>,
+    // ids of the backups holding the student
+    pub backups: Vec<String>,
+}
+
+/// what `POST /students/{id}/erase` did
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Erasure {
+    pub student: Student,
+    pub webhook_deliveries_removed: usize,
+    // ids of the backups which were written again without the student's data
+    pub backups_scrubbed: Vec<String>,
+    pub erased_at: DateTime<Utc>,
+}
+
+
+/// the stores a student's data can end up in
+pub struct Compliance {
+    // the default store
+    pub state: SharedState,
+    pub tenants: Arc<Tenants>,
+    pub webhooks: Arc<Webhooks>,
+    pub backups: Arc<Backups>,
+    pub log: ComplianceLog,
+}
+
+
+impl Compliance {
+    /// the store of a tenant, the default store without one
+    fn store(&self, tenant: Option<&str>) -> Result<SharedState, StatusCode> {
+        match tenant {
+            Some(name) => self.tenants.store(name).ok_or(StatusCode::NOT_FOUND),
+            None => Ok(self.state.clone()),
+        }
+    }
+}
+
+
+/// the export and erase routes, protected by the admin key as they are run by staff on behalf of the student.
+/// The students of a tenant are under `/admin/tenants/{name}`, the tenant api keys do not give access to them.
+pub fn routes(compliance: Arc<Compliance>, admin_key: AdminKey) -> Router {
+    Router::new()
+        .route("/students/{id}/export", get(export_student))
+        .route("/students/{id}/erase", post(erase_student))
+        .route("/admin/tenants/{name}/students/{id}/export", get(export_tenant_student))
+        .route("/admin/tenants/{name}/students/{id}/erase", post(erase_tenant_student))
+        .route_layer(from_fn_with_state(admin_key, admin::require_key))
+        .with_state(compliance)
+}
+
+
+fn internal_error(e: impl std::fmt::Display) -> StatusCode {
+    eprintln!("compliance request failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// curl -X GET http://127.0.0.1:4500/students/{id}/export -H "X-Api-Key: {admin key}"
+async fn export_student(State(compliance): State<Arc<Compliance>>, Path(id): Path<String>) -> Result<Json<Export>, StatusCode> {
+    export(&compliance, None, id).await
+}
+
+/// curl -X GET http://127.0.0.1:4500/admin/tenants/{name}/students/{id}/export -H "X-Api-Key: {admin key}"
+async fn export_tenant_student(State(compliance): State<Arc<Compliance>>, Path((tenant, id)): Path<(String, String)>) -> Result<Json<Export>, StatusCode> {
+    export(&compliance, Some(&tenant), id).await
+}
+
+async fn export(compliance: &Compliance, tenant: Option<&str>, id: String) -> Result<Json<Export>, StatusCode> {
+    let state = compliance.store(tenant)?;
+    let student = {
+        let students = state.students.lock().await;
+        students.iter().find(|s| s.id == id).cloned().ok_or(StatusCode::NOT_FOUND)?
+    };
+    compliance.log.record(ComplianceAction::Export, &id, None).map_err(internal_error)?;
+    Ok(Json(Export {
+        exported_at: Utc::now(),
+        student,
+        audit: compliance.log.entries_for(&id).map_err(internal_error)?,
+        webhook_deliveries: compliance.webhooks.deliveries_for(&id),
+        backups: compliance.backups.containing(&id).map_err(internal_error)?,
+    }))
+}
+
+/// pseudonymize the student in the store, remove its photo, drop the webhook payloads about it and write
+/// the backups holding it again without its data. Restores of backups pseudonymize it again as well.
+/// The id stays, so references to the student keep working.
+///
+/// curl -X POST http://127.0.0.1:4500/students/{id}/erase -H "X-Api-Key: {admin key}"
+async fn erase_student(State(compliance): State<Arc<Compliance>>, Path(id): Path<String>) -> Result<Json<Erasure>, StatusCode> {
+    erase(&compliance, None, id).await
+}
+
+/// curl -X POST http://127.0.0.1:4500/admin/tenants/{name}/students/{id}/erase -H "X-Api-Key: {admin key}"
+async fn erase_tenant_student(State(compliance): State<Arc<Compliance>>, Path((tenant, id)): Path<(String, String)>) -> Result<Json<Erasure>, StatusCode> {
+    erase(&compliance, Some(&tenant), id).await
+}
+
+async fn erase(compliance: &Compliance, tenant: Option<&str>, id: String) -> Result<Json<Erasure>, StatusCode> {
+    let state = compliance.store(tenant)?;
+    let student = update(&state, &id, Student::pseudonymize).await?;
+    photo::prune(&state).await;
+    compliance.backups.record_erasure(&id).map_err(internal_error)?;
+    let removed = compliance.webhooks.forget_student(&id)?;
+    let scrubbed = compliance.backups.scrub(&id).await.map_err(internal_error)?;
+    let details = format!("{} webhook deliveries removed, {} backups scrubbed", removed, scrubbed.len());
+    let entry = compliance.log.record(ComplianceAction::Erase, &id, Some(details)).map_err(internal_error)?;
+    Ok(Json(Erasure { student, webhook_deliveries_removed: removed, backups_scrubbed: scrubbed, erased_at: entry.at }))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, state::AppState, tenant::Tenants, webhook::{self, RetryPolicy}};
+    use axum::{body::{to_bytes, Body}, http::Request};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json").body(body).unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        let status = response.status();
+        let data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&data).unwrap_or(Value::Null))
+    }
+
+    fn backup_content(dir: &std::path::Path, id: &str) -> String {
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(fs::File::open(dir.join(format!("{}.json.gz", id))).unwrap()), &mut content).unwrap();
+        content
+    }
+
+    #[tokio::test]
+    async fn erase_pseudonymizes_everywhere_and_export_shows_the_trail() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let policy = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+        let webhooks = Arc::new(Webhooks::load(dir.path().join("webhooks.json"), policy).unwrap());
+        webhook::spawn(webhooks.clone(), state.events.subscribe());
+        let tenants = Arc::new(Tenants::load(dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+        let backups = Arc::new(Backups::new(dir.path().join("backups"), state.clone(), webhooks.clone(), tenants.clone()));
+        let compliance = Arc::new(Compliance {
+            state: state.clone(),
+            tenants,
+            webhooks: webhooks.clone(),
+            backups: backups.clone(),
+            log: ComplianceLog::new(dir.path().join("compliance.jsonl")),
+        });
+        let app = app(state).merge(routes(compliance, None)).merge(crate::backup::routes(backups.clone(), None));
+
+        // a webhook nobody listens to, its failed delivery keeps the student's data
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        webhooks.create(webhook::WebhookInput { url: format!("http://{}/hook", closed), tenant: None, events: vec![], secret: None, active: true }).unwrap();
+
+        let aman = json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" });
+        assert_eq!(send(&app, "POST", "/students", Some(aman)).await.0, StatusCode::CREATED);
+        let id = send(&app, "GET", "/students", None).await.1[0]["id"].as_str().unwrap().to_string();
+        let backup = send(&app, "POST", "/admin/backups", None).await.1["id"].as_str().unwrap().to_string();
+        for _ in 0..200 {
+            if !webhooks.dead_letters().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let (status, export) = send(&app, "GET", &format!("/students/{}/export", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(export["student"]["email"], "aman@example.com");
+        assert_eq!(export["backups"], json!([backup]));
+        assert_eq!(export["webhook_deliveries"].as_array().unwrap().len(), 1);
+
+        let (status, erasure) = send(&app, "POST", &format!("/students/{}/erase", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(erasure["webhook_deliveries_removed"], 1);
+        let (_, student) = send(&app, "GET", &format!("/students/{}", id), None).await;
+        assert_eq!(student["id"], id.as_str());
+        assert_eq!(student["name"], "Erased student");
+        assert!(!fs::read_to_string(dir.path().join("students.json")).unwrap().contains("aman@example.com"));
+        assert!(!fs::read_to_string(dir.path().join("webhooks.json")).unwrap().contains("aman@example.com"));
+        // the backup is written again without the student
+        assert_eq!(erasure["backups_scrubbed"], json!([backup]));
+        assert!(!backup_content(&dir.path().join("backups"), &backup).contains("aman@example.com"));
+        // and still matches its checksum
+        assert_eq!(send(&app, "POST", &format!("/admin/backups/{}/restore?dry_run=true", backup), None).await.0, StatusCode::OK);
+
+        // restoring the older backup does not bring the student back
+        send(&app, "POST", &format!("/admin/backups/{}/restore", backup), None).await;
+        assert_eq!(send(&app, "GET", &format!("/students/{}", id), None).await.1["name"], "Erased student");
+
+        let (_, export) = send(&app, "GET", &format!("/students/{}/export", id), None).await;
+        let actions: Vec<&str> = export["audit"].as_array().unwrap().iter().map(|e| e["action"].as_str().unwrap()).collect();
+        assert_eq!(actions, vec!["export", "erase", "export"]);
+        assert!(!fs::read_to_string(dir.path().join("compliance.jsonl")).unwrap().contains("aman"));
+
+        assert_eq!(send(&app, "POST", "/students/missing/erase", None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn tenant_students_are_exported_and_erased() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let webhooks = Arc::new(Webhooks::load(dir.path().join("webhooks.json"), RetryPolicy::default()).unwrap());
+        let tenants = Arc::new(Tenants::load(dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+        let backups = Arc::new(Backups::new(dir.path().join("backups"), state.clone(), webhooks.clone(), tenants.clone()));
+        let compliance = Arc::new(Compliance {
+            state: state.clone(),
+            tenants: tenants.clone(),
+            webhooks,
+            backups: backups.clone(),
+            log: ComplianceLog::new(dir.path().join("compliance.jsonl")),
+        });
+        let app = crate::tenant::scoped(app(state).merge(routes(compliance, None)), tenants.clone(), None);
+
+        let (_, tenant) = send(&app, "POST", "/admin/tenants", Some(json!({ "name": "north" }))).await;
+        let key = tenant["api_keys"][0].as_str().unwrap().to_string();
+        let aman = json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" });
+        let req = Request::post("/t/north/students").header("content-type", "application/json").header("x-api-key", &key).body(Body::from(aman.to_string())).unwrap();
+        assert_eq!(app.clone().oneshot(req).await.unwrap().status(), StatusCode::CREATED);
+        let id = tenants.store("north").unwrap().students.lock().await[0].id.clone();
+        let backup = backups.create().await.unwrap();
+
+        // not in the default store
+        assert_eq!(send(&app, "GET", &format!("/students/{}/export", id), None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&app, "GET", &format!("/admin/tenants/south/students/{}/export", id), None).await.0, StatusCode::NOT_FOUND);
+        let (status, export) = send(&app, "GET", &format!("/admin/tenants/north/students/{}/export", id), None).await;
+        assert_eq!((status, &export["student"]["email"]), (StatusCode::OK, &json!("aman@example.com")));
+        assert_eq!(export["backups"], json!([backup.id]));
+
+        let (status, erasure) = send(&app, "POST", &format!("/admin/tenants/north/students/{}/erase", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(erasure["backups_scrubbed"], json!([backup.id]));
+        assert!(!fs::read_to_string(dir.path().join("tenants").join("north").join("students.json")).unwrap().contains("aman@example.com"));
+        assert!(!backup_content(&dir.path().join("backups"), &backup.id).contains("aman@example.com"));
+    }
+}
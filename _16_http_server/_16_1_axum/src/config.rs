@@ -0,0 +1,71 @@
+use std::{env, net::SocketAddr, path::PathBuf, time::Duration};
+use crate::{tls::TlsConfig, web::StaticConfig};
+
+
+/// server settings, read from environment variables with defaults for local development
+pub struct Config {
+    // STUDENT_API_ADDR
+    pub addr: SocketAddr,
+    // STUDENTS_FILE
+    pub data_file: PathBuf,
+    // WATCH_INTERVAL_MS - how often the data file is checked for edits made outside the server
+    pub watch_interval: Duration,
+    // TLS_CERT and TLS_KEY (plus optional TLS_CLIENT_CA) - serve https instead of http when set
+    pub tls: Option<TlsConfig>,
+    // TENANTS_DIR - where the tenant list and the data file of every tenant are stored
+    pub tenants_dir: PathBuf,
+    // ADMIN_API_KEY - required as `X-Api-Key` for the /admin routes when set
+    pub admin_key: Option<String>,
+    // STATIC_DIR, WASM_PKG_DIR and STATIC_MAX_AGE - serve a front-end next to the api when set
+    pub static_files: Option<StaticConfig>,
+    // CORS_ALLOWED_ORIGINS - comma separated origins allowed to call the api from a browser, `*` for any
+    pub cors_origins: Vec<String>,
+    // WEBHOOKS_FILE - registered webhooks and deliveries which failed for good
+    pub webhooks_file: PathBuf,
+    // BACKUP_DIR - snapshots created through /admin/backups
+    pub backup_dir: PathBuf,
+    // MASTER_KEY_FILE - json file with the master keys, reloaded when it changes, see `crypto::Keys`
+    pub master_key_file: Option<PathBuf>,
+    // MASTER_KEY - a single base64 master key, used when there is no key file
+    pub master_key: Option<String>,
+    // COMPLIANCE_LOG - exports and erasures of student data, one json object per line
+    pub compliance_log: PathBuf,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Config {
+            addr: env::var("STUDENT_API_ADDR")
+                .ok()
+                .and_then(|addr| addr.parse().ok())
+                .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 4500))),
+            data_file: env::var("STUDENTS_FILE").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("students.json")),
+            watch_interval: Duration::from_millis(
+                env::var("WATCH_INTERVAL_MS").ok().and_then(|ms| ms.parse().ok()).unwrap_or(1000),
+            ),
+            tls: match (env::var("TLS_CERT"), env::var("TLS_KEY")) {
+                (Ok(cert), Ok(key)) => Some(TlsConfig {
+                    cert: cert.into(),
+                    key: key.into(),
+                    client_ca: env::var("TLS_CLIENT_CA").ok().map(PathBuf::from),
+                }),
+                _ => None,
+            },
+            tenants_dir: env::var("TENANTS_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("tenants")),
+            admin_key: env::var("ADMIN_API_KEY").ok().filter(|key| !key.is_empty()),
+            static_files: env::var("STATIC_DIR").ok().map(|dir| StaticConfig {
+                dir: dir.into(),
+                pkg_dir: env::var("WASM_PKG_DIR").ok().map(PathBuf::from),
+                max_age: Duration::from_secs(env::var("STATIC_MAX_AGE").ok().and_then(|secs| secs.parse().ok()).unwrap_or(3600)),
+            }),
+            cors_origins: env::var("CORS_ALLOWED_ORIGINS")
+                .map(|origins| origins.split(',').map(|o| o.trim().to_string()).filter(|o| !o.is_empty()).collect())
+                .unwrap_or_default(),
+            webhooks_file: env::var("WEBHOOKS_FILE").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("webhooks.json")),
+            backup_dir: env::var("BACKUP_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("backups")),
+            master_key_file: env::var("MASTER_KEY_FILE").ok().map(PathBuf::from),
+            master_key: env::var("MASTER_KEY").ok().filter(|key| !key.is_empty()),
+            compliance_log: env::var("COMPLIANCE_LOG").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("compliance.jsonl")),
+        }
+    }
+}
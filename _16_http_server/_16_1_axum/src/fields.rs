@@ -0,0 +1,121 @@
+use std::{collections::HashMap, marker::PhantomData};
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+
+/// a resource the api returns, with the names clients may ask for in `?fields=` and `?include=`
+pub trait Fieldset {
+    const FIELDS: &'static [&'static str];
+    // related resources which can be embedded, none exist yet
+    const INCLUDES: &'static [&'static str] = &[];
+}
+
+
+/// `?fields=id,name` and `?include=...` of a request returning `T`, names unknown to `T` are rejected with 400
+pub struct Fields<T> {
+    pub fields: Option<Vec<String>>,
+    pub include: Vec<String>,
+    resource: PhantomData<fn() -> T>,
+}
+
+impl<T> Fields<T> {
+    /// `value` reduced to the requested fields when it is serialized
+    pub fn apply<V: Serialize>(&self, value: V) -> Sparse<V> {
+        Sparse { fields: self.fields.clone(), value }
+    }
+}
+
+fn names(list: Option<&String>) -> Option<Vec<String>> {
+    let names: Vec<String> = list?.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect();
+    // `?fields=` without names selects everything
+    Some(names).filter(|names| !names.is_empty())
+}
+
+fn check(names: &[String], known: &[&str], parameter: &str) -> Result<(), (StatusCode, String)> {
+    match names.iter().find(|name| !known.contains(&name.as_str())) {
+        Some(unknown) => Err((
+            StatusCode::BAD_REQUEST,
+            format!("unknown {} {:?}, expected one of: {}", parameter, unknown, known.join(", ")),
+        )),
+        None => Ok(()),
+    }
+}
+
+impl<T: Fieldset, S: Send + Sync> FromRequestParts<S> for Fields<T> {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        let fields = names(query.get("fields"));
+        let include = names(query.get("include")).unwrap_or_default();
+        check(fields.as_deref().unwrap_or_default(), T::FIELDS, "field")?;
+        check(&include, T::INCLUDES, "include")?;
+        Ok(Fields { fields, include, resource: PhantomData })
+    }
+}
+
+
+/// a resource or a list of resources with only the selected fields, all fields without a selection
+pub struct Sparse<V> {
+    fields: Option<Vec<String>>,
+    value: V,
+}
+
+impl<V: Serialize> Serialize for Sparse<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(fields) = &self.fields else { return self.value.serialize(serializer) };
+        let mut value = serde_json::to_value(&self.value).map_err(serde::ser::Error::custom)?;
+        let keep = |object: &mut serde_json::Map<String, Value>| object.retain(|key, _| fields.contains(key));
+        match &mut value {
+            Value::Array(items) => items.iter_mut().filter_map(Value::as_object_mut).for_each(keep),
+            Value::Object(object) => keep(object),
+            _ => {}
+        }
+        value.serialize(serializer)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{app, state::AppState};
+    use axum::{body::{to_bytes, Body}, http::{Request, StatusCode}};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn get(app: &axum::Router, uri: &str, accept: &str) -> (StatusCode, Vec<u8>) {
+        let response = app.clone().oneshot(Request::get(uri).header("accept", accept).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        (status, to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec())
+    }
+
+    #[tokio::test]
+    async fn fields_select_and_unknown_names_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = app(Arc::new(AppState::new(dir.path().join("students.json")).unwrap()));
+        let aman = json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" });
+        let req = Request::post("/students").header("content-type", "application/json").body(Body::from(aman.to_string())).unwrap();
+        app.clone().oneshot(req).await.unwrap();
+
+        let (_, list) = get(&app, "/students?fields=id,name", "application/json").await;
+        let list: Value = serde_json::from_slice(&list).unwrap();
+        assert_eq!(list[0].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["id", "name"]);
+        let id = list[0]["id"].as_str().unwrap();
+
+        let (_, item) = get(&app, &format!("/v2/students/{}?fields=name", id), "application/json").await;
+        assert_eq!(serde_json::from_slice::<Value>(&item).unwrap(), json!({ "name": { "given": "Aman", "family": null, "display": "Aman" } }));
+
+        let (_, csv) = get(&app, &format!("/v1/students/{}?fields=name,mobile", id), "text/csv").await;
+        assert_eq!(String::from_utf8(csv).unwrap(), "name,mobile\nAman,9876543210\n");
+
+        assert_eq!(get(&app, "/students?fields=id,password", "application/json").await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(get(&app, "/v2/students?include=courses", "application/json").await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(get(&app, "/v2/students?fields=", "application/json").await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,254 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+
+/// the body formats the api can read and write
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    MsgPack,
+    Cbor,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv",
+            Format::MsgPack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    /// the format for a media type like `text/csv`, parameters like `; charset=utf-8` are ignored
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        let essence = media_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Format::Json),
+            "text/csv" => Some(Format::Csv),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MsgPack),
+            "application/cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    /// pick the format the client prefers from an `Accept` header, `None` if nothing we offer is acceptable
+    pub fn from_accept(accept: &str) -> Option<Format> {
+        let mut ranges: Vec<(f32, &str)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next()?.trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                Some((quality, media_type))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+        // stable sort, so equally weighted ranges keep the order of the header
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        ranges.into_iter().find_map(|(_, media_type)| match media_type {
+            "*/*" | "application/*" => Some(Format::Json),
+            "text/*" => Some(Format::Csv),
+            _ => Format::from_media_type(media_type),
+        })
+    }
+
+    /// serialize a value in this format
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::Csv => to_csv(&serde_json::to_value(value).map_err(|e| e.to_string())?),
+            Format::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Cbor => {
+                let mut data = Vec::new();
+                ciborium::into_writer(value, &mut data).map_err(|e| e.to_string())?;
+                Ok(data)
+            }
+        }
+    }
+
+    /// deserialize a value from a body in this format, for csv the first row is used
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T, String> {
+        match self {
+            Format::Json => serde_json::from_slice(data).map_err(|e| e.to_string()),
+            Format::Csv => csv::Reader::from_reader(data)
+                .deserialize()
+                .next()
+                .ok_or_else(|| "csv body has no rows".to_string())?
+                .map_err(|e| e.to_string()),
+            Format::MsgPack => rmp_serde::from_slice(data).map_err(|e| e.to_string()),
+            Format::Cbor => ciborium::from_reader(data).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+
+/// write a list of objects (or a single object) as csv with a header row.
+///
+/// The columns are taken from the first object, nested values are written as json.
+fn to_csv(value: &Value) -> Result<Vec<u8>, String> {
+    let rows = match value {
+        Value::Array(rows) => rows.iter().collect(),
+        row => vec![row],
+    };
+    let headers: Vec<&String> = match rows.first() {
+        Some(Value::Object(first)) => first.keys().collect(),
+        Some(_) => return Err("only objects can be written as csv".to_string()),
+        None => vec![],
+    };
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if !headers.is_empty() {
+        writer.write_record(&headers).map_err(|e| e.to_string())?;
+    }
+    for row in rows {
+        let record = headers.iter().map(|key| match row.get(key.as_str()) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+        });
+        writer.write_record(record).map_err(|e| e.to_string())?;
+    }
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+
+/// the response format requested through the `Accept` header, rejects with 406 Not Acceptable
+pub struct Accept(pub Format);
+
+impl<S: Send + Sync> FromRequestParts<S> for Accept {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match accept_header(&parts.headers) {
+            None => Ok(Accept(Format::Json)),
+            Some(accept) => Format::from_accept(accept).map(Accept).ok_or(StatusCode::NOT_ACCEPTABLE),
+        }
+    }
+}
+
+fn accept_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()).filter(|value| !value.trim().is_empty())
+}
+
+
+/// a value sent in the format the client asked for
+pub struct Encoded<T>(pub Format, pub T);
+
+impl<T: Serialize> IntoResponse for Encoded<T> {
+    fn into_response(self) -> Response {
+        let Encoded(format, value) = self;
+        match format.encode(&value) {
+            Ok(data) => ([(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()))], data).into_response(),
+            Err(e) => {
+                eprintln!("could not encode response as {}: {}", format.content_type(), e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+
+/// request body in any supported format, chosen by `Content-Type` (json when missing).
+/// Rejects with 415 Unsupported Media Type or 400 Bad Request.
+pub struct Body<T>(pub T);
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for Body<T> {
+    type Rejection = StatusCode;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = match req.headers().get(header::CONTENT_TYPE) {
+            None => Format::Json,
+            Some(content_type) => content_type
+                .to_str()
+                .ok()
+                .and_then(Format::from_media_type)
+                .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?,
+        };
+        let data = Bytes::from_request(req, state).await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        format.decode(&data).map(Body).map_err(|_| StatusCode::BAD_REQUEST)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Student;
+
+    fn student() -> Student {
+        Student { id: "a".to_string(), name: "Alice, Jr.".to_string(), email: "alice@example.com".to_string(), mobile: "1234567890".to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn accept_header_picks_the_preferred_format() {
+        assert_eq!(Format::from_accept("text/csv"), Some(Format::Csv));
+        assert_eq!(Format::from_accept("application/cbor;q=0.5, application/msgpack"), Some(Format::MsgPack));
+        assert_eq!(Format::from_accept("text/html, */*;q=0.1"), Some(Format::Json));
+        assert_eq!(Format::from_accept("text/html, application/json;q=0"), None);
+    }
+
+    #[test]
+    fn every_format_round_trips_a_student() {
+        for format in [Format::Json, Format::Csv, Format::MsgPack, Format::Cbor] {
+            let data = format.encode(&student()).unwrap();
+            assert_eq!(format.decode::<Student>(&data).unwrap(), student(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn csv_lists_have_one_row_per_student() {
+        let data = Format::Csv.encode(&vec![student(), student()]).unwrap();
+        let text = String::from_utf8(data).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(text.lines().next(), Some("id,name,email,mobile"));
+    }
+
+    #[tokio::test]
+    async fn router_negotiates_format_and_compression() {
+        use axum::{body::{to_bytes, Body as HttpBody}, http::Request};
+        use std::io::Read;
+        use tower::ServiceExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = std::sync::Arc::new(crate::state::AppState::new(dir.path().join("students.json")).unwrap());
+        let app = crate::app(state);
+
+        let created = app.clone()
+            .oneshot(Request::post("/students")
+                .header("Content-Type", "application/cbor")
+                .body(HttpBody::from(Format::Cbor.encode(&student()).unwrap()))
+                .unwrap())
+            .await
+            .unwrap();
+        assert_eq!(created.status(), StatusCode::CREATED);
+
+        let response = app.clone()
+            .oneshot(Request::get("/students").header("Accept", "text/csv").header("Accept-Encoding", "gzip").body(HttpBody::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let compressed = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_string(&mut text).unwrap();
+        assert!(text.contains("\"Alice, Jr.\",alice@example.com"), "{}", text);
+
+        let rejected = app
+            .oneshot(Request::get("/students").header("Accept", "text/html").body(HttpBody::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rejected.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+}
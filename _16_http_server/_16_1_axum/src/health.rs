@@ -0,0 +1,246 @@
+use std::{collections::BTreeMap, fs, sync::{atomic::Ordering, Arc}, time::Instant};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::Json,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{admin::{self, AdminKey}, state::{AppState, LockStats, Persistence}, tenant::Tenants, SharedState};
+
+
+/// what /readyz checked, `None` for a check which passed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Readiness {
+    pub ready: bool,
+    pub storage: Option<String>,
+    pub persistence: Option<String>,
+    pub recovering: bool,
+    // the store of every tenant by name, `ready` only when all of them are
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tenants: BTreeMap<String, Readiness>,
+}
+
+/// body of `GET /admin/diagnostics`, the default store at the top level
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Diagnostics {
+    pub uptime_secs: u64,
+    pub started_at: DateTime<Utc>,
+    pub build: BuildInfo,
+    #[serde(flatten)]
+    pub store: StoreDiagnostics,
+    #[serde(default)]
+    pub tenants: BTreeMap<String, StoreDiagnostics>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StoreDiagnostics {
+    pub students: usize,
+    // size of the data file in bytes, `None` when it does not exist yet
+    pub data_file_size: Option<u64>,
+    pub persistence: Persistence,
+    pub lock: LockStats,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BuildInfo {
+    pub name: String,
+    pub version: String,
+    pub profile: String,
+}
+
+fn build_info() -> BuildInfo {
+    BuildInfo {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        profile: if cfg!(debug_assertions) { "debug" } else { "release" }.to_string(),
+    }
+}
+
+
+struct Health {
+    state: SharedState,
+    tenants: Arc<Tenants>,
+    started: Instant,
+    started_at: DateTime<Utc>,
+}
+
+
+/// `/healthz` and `/readyz` for orchestrators, `/admin/diagnostics` protected by the admin key.
+/// Both cover the default store and the store of every tenant.
+pub fn routes(state: SharedState, tenants: Arc<Tenants>, admin_key: AdminKey) -> Router {
+    let health = Arc::new(Health { state, tenants, started: Instant::now(), started_at: Utc::now() });
+    let admin = Router::new()
+        .route("/admin/diagnostics", get(diagnostics))
+        .route_layer(from_fn_with_state(admin_key, admin::require_key));
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .merge(admin)
+        .with_state(health)
+}
+
+
+/// the process is up and answering
+/// curl -X GET http://127.0.0.1:4500/healthz
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// 503 while a data file can not be used, the last write of a store failed or a backup is being restored
+/// curl -X GET http://127.0.0.1:4500/readyz
+async fn readyz(State(health): State<Arc<Health>>) -> (StatusCode, Json<Readiness>) {
+    let mut readiness = readiness(&health.state);
+    for (name, state) in tenant_stores(&health.tenants) {
+        let tenant = self::readiness(&state);
+        readiness.ready &= tenant.ready;
+        readiness.tenants.insert(name, tenant);
+    }
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness))
+}
+
+/// whether the store can take requests, shared with other front doors like the gRPC health service
+pub fn readiness(state: &AppState) -> Readiness {
+    let storage = state.check_storage().err().map(|e| e.to_string());
+    let persistence = state.persistence().error;
+    let recovering = state.recovering.load(Ordering::Relaxed);
+    let ready = storage.is_none() && persistence.is_none() && !recovering;
+    Readiness { ready, storage, persistence, recovering, tenants: BTreeMap::new() }
+}
+
+/// curl -X GET http://127.0.0.1:4500/admin/diagnostics -H "X-Api-Key: {admin key}"
+async fn diagnostics(State(health): State<Arc<Health>>) -> Json<Diagnostics> {
+    let mut tenants = BTreeMap::new();
+    for (name, state) in tenant_stores(&health.tenants) {
+        tenants.insert(name, store_diagnostics(&state).await);
+    }
+    Json(Diagnostics {
+        uptime_secs: health.started.elapsed().as_secs(),
+        started_at: health.started_at,
+        build: build_info(),
+        store: store_diagnostics(&health.state).await,
+        tenants,
+    })
+}
+
+async fn store_diagnostics(state: &AppState) -> StoreDiagnostics {
+    StoreDiagnostics {
+        students: state.students.lock().await.len(),
+        data_file_size: fs::metadata(&state.data_file).ok().map(|meta| meta.len()),
+        persistence: state.persistence(),
+        lock: state.students.stats(),
+    }
+}
+
+fn tenant_stores(tenants: &Tenants) -> Vec<(String, SharedState)> {
+    tenants.list().into_iter().filter_map(|info| Some((info.name.clone(), tenants.store(&info.name)?))).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, state::AppState, tenant::NewTenant};
+    use axum::{body::{to_bytes, Body}, http::Request};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+        let response = app.clone().oneshot(Request::get(uri).header("x-api-key", "admin-key").body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&data).unwrap_or(Value::Null))
+    }
+
+    fn tenants(dir: &tempfile::TempDir) -> Arc<Tenants> {
+        Arc::new(Tenants::load(dir.path().join("tenants"), std::time::Duration::from_secs(60)).unwrap())
+    }
+
+    #[tokio::test]
+    async fn readiness_follows_the_persistence_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let app = app(state.clone()).merge(routes(state.clone(), tenants(&dir), Some(Arc::from("admin-key"))));
+
+        assert_eq!(get(&app, "/healthz").await.0, StatusCode::OK);
+        assert_eq!(get(&app, "/readyz").await.0, StatusCode::OK);
+
+        // the data file turns into a directory, writes fail
+        std::fs::create_dir(dir.path().join("students.json")).unwrap();
+        let student = json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" });
+        let req = Request::post("/students").header("content-type", "application/json").body(Body::from(student.to_string())).unwrap();
+        assert_eq!(app.clone().oneshot(req).await.unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let (status, readiness) = get(&app, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(readiness["persistence"].is_string());
+        assert_eq!(get(&app, "/healthz").await.0, StatusCode::OK);
+
+        std::fs::remove_dir(dir.path().join("students.json")).unwrap();
+        state.sync(&mut *state.students.lock().await).unwrap();
+        assert_eq!(get(&app, "/readyz").await.0, StatusCode::OK);
+
+        state.recovering.store(true, Ordering::Relaxed);
+        assert_eq!(get(&app, "/readyz").await.1["recovering"], true);
+    }
+
+    #[tokio::test]
+    async fn the_directory_is_not_probed_on_every_poll() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let app = app(state.clone()).merge(routes(state.clone(), tenants(&dir), Some(Arc::from("admin-key"))));
+        assert_eq!(get(&app, "/readyz").await.0, StatusCode::OK);
+
+        // a directory in the way of the probe file, the next probe fails
+        std::fs::create_dir(dir.path().join(".students.json.probe")).unwrap();
+        assert_eq!(get(&app, "/readyz").await.0, StatusCode::OK);
+        let fresh = AppState::new(dir.path().join("students.json")).unwrap();
+        assert!(fresh.check_storage().is_err());
+    }
+
+    #[tokio::test]
+    async fn tenant_stores_are_checked_too() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let tenants = tenants(&dir);
+        tenants.create(NewTenant { name: "north".into(), max_students: None, api_keys: None }).unwrap();
+        let app = app(state.clone()).merge(routes(state.clone(), tenants.clone(), Some(Arc::from("admin-key"))));
+
+        let (status, readiness) = get(&app, "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(readiness["tenants"]["north"]["ready"], true);
+        let (_, diagnostics) = get(&app, "/admin/diagnostics").await;
+        assert_eq!(diagnostics["tenants"]["north"]["students"], 0);
+
+        tenants.store("north").unwrap().recovering.store(true, Ordering::Relaxed);
+        let (status, readiness) = get(&app, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(readiness["recovering"], false);
+        assert_eq!(readiness["tenants"]["north"]["recovering"], true);
+    }
+
+    #[tokio::test]
+    async fn diagnostics_report_store_and_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let app = app(state.clone()).merge(routes(state.clone(), tenants(&dir), Some(Arc::from("admin-key"))));
+
+        let student = json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" });
+        let req = Request::post("/students").header("content-type", "application/json").body(Body::from(student.to_string())).unwrap();
+        app.clone().oneshot(req).await.unwrap();
+
+        let (status, diagnostics) = get(&app, "/admin/diagnostics").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(diagnostics["students"], 1);
+        assert_eq!(diagnostics["build"]["name"], "studet-api");
+        assert!(diagnostics["data_file_size"].as_u64().unwrap() > 0);
+        assert!(diagnostics["persistence"]["last_success"].is_string());
+        assert!(diagnostics["lock"]["acquired"].as_u64().unwrap() >= 1);
+
+        let unauthorized = app.oneshot(Request::get("/admin/diagnostics").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,46 @@
+pub mod api;
+pub mod bindings;
+pub mod backup;
+pub mod compliance;
+pub mod health;
+pub mod photo;
+pub mod server;
+pub mod config;
+pub mod tls;
+pub mod format;
+pub mod fields;
+pub mod admin;
+pub mod tenant;
+pub mod v1;
+pub mod v2;
+pub mod web;
+pub mod webhook;
+
+use axum::{middleware, Router};
+use tower_http::compression::CompressionLayer;
+
+// the stores live in their own crate, shared with the gRPC service
+pub use student_store::{crypto, handler, model, state, watcher, SharedState};
+
+
+/// the student routes of one store, without state so they can be used for every tenant.
+/// The `/students` routes of every version come from the `google.api.http` bindings in student.proto.
+/// The unversioned `/students` routes stay for existing clients and serve v1, photos are the same in every version.
+pub fn routes() -> Router<SharedState> {
+    let v1 = bindings::router(api::wrapper)
+        .layer(middleware::from_fn(v1::deprecation_headers));
+
+    Router::new()
+        .merge(v1.clone())
+        .merge(photo::routes())
+        .nest("/v1", v1.merge(photo::routes()))
+        .nest("/v2", v2::routes().merge(photo::routes()))
+}
+
+
+/// build the router with all student routes, responses are compressed when the client sends `Accept-Encoding`
+pub fn app(state: SharedState) -> Router {
+    routes()
+        .layer(CompressionLayer::new())
+        .with_state(state)
+}
@@ -0,0 +1,269 @@
+use std::io;
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use imaging::{Filter, Format};
+use serde::Deserialize;
+use student_store::photos::{hash, seal, store, unseal};
+use crate::{api::commit, model::Photo, state::{ChangeEvent, ChangeKind, ChangeSource}, SharedState};
+
+// how photos are kept on disk is part of the store crate
+pub use student_store::photos::{copy_files, prune, reencrypt};
+
+
+/// largest upload, raw or multipart
+pub const MAX_UPLOAD: usize = 10 * 1024 * 1024;
+
+/// smallest width and height of a photo, nothing smaller makes a useful thumbnail
+pub const MIN_SIDE: u32 = 64;
+
+/// the thumbnails are squares of these sides
+pub const SMALL: u32 = 64;
+pub const MEDIUM: u32 = 256;
+
+// photos and thumbnails which were uploaded as JPEG are stored as JPEG again
+const JPEG_QUALITY: u8 = 90;
+
+// the url of a photo stays the same when a new one is uploaded, so clients have to ask every
+// time - thanks to the etag that is a cheap 304 while the photo did not change
+const CACHE_CONTROL: &str = "private, no-cache";
+
+/// the `size` query parameter of `GET /students/{id}/photo`
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Size {
+    Small,
+    Medium,
+    #[default]
+    Original,
+}
+
+#[derive(Deserialize)]
+pub struct PhotoQuery {
+    #[serde(default)]
+    size: Size,
+}
+
+
+/// the photo routes, merged next to the student routes of every api version which has photos.
+///
+/// An upload is decoded and encoded again, which drops EXIF and every other kind of metadata,
+/// and a small and a medium square thumbnail are made from it with the same code the wasm
+/// package uses in the browser. All files are stored in `AppState::photos_dir` named by the
+/// sha-256 of their content, the student only keeps the names. With a master key the files are
+/// encrypted like the personal fields of the data file and encrypted again when the key rotates.
+/// Files no student refers to any more are removed after uploads, deletions, erasures and
+/// restores, backups keep their own copy.
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/students/{id}/photo", get(get_photo).put(put_photo))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD))
+}
+
+
+/// upload a PNG or JPEG photo, from 64x64 up to 8192x8192 pixels. It replaces the photo the student had.
+///
+/// curl -X PUT http://127.0.0.1:4500/students/{id}/photo -H "Content-Type: image/jpeg" --data-binary @photo.jpg
+/// curl -X PUT http://127.0.0.1:4500/students/{id}/photo -F "photo=@photo.jpg"
+async fn put_photo(Path(id): Path<String>, State(state): State<SharedState>, headers: HeaderMap, body: Bytes) -> Result<Json<Photo>, Response> {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let upload = if content_type.starts_with("multipart/form-data") {
+        multipart_file(content_type, &body).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?
+    } else {
+        body.to_vec()
+    };
+    if !state.students.lock().await.iter().any(|s| s.id == id) {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+
+    // decoding and resizing takes a while for large photos, it must not block the other requests
+    let files = tokio::task::spawn_blocking(move || process(&upload))
+        .await
+        .map_err(|e| internal_error(io::Error::other(e)).into_response())?
+        .map_err(IntoResponse::into_response)?;
+    let [original, small, medium] = files.each_ref().map(|file| hash(file));
+    let photo = Photo { original, small, medium };
+    let sealed: Vec<_> = photo.files().into_iter().zip(&files).map(|(name, file)| (name, seal(&state, file))).collect();
+
+    // the files are written while the students are locked, so `prune` never sees them unused
+    let dir = state.photos_dir();
+    commit(&state, |students| {
+        let student = students.iter_mut().find(|s| s.id == id).ok_or(StatusCode::NOT_FOUND)?;
+        sealed.iter().try_for_each(|(name, file)| store(&dir.join(name), file)).map_err(internal_error)?;
+        student.photo = Some(photo.clone());
+        student.updated_at = Some(Utc::now());
+        Ok(ChangeEvent { kind: ChangeKind::Updated, id: id.clone(), student: Some(student.clone()), source: ChangeSource::Api })
+    })
+    .await
+    .map_err(IntoResponse::into_response)?;
+    prune(&state).await;
+    Ok(Json(photo))
+}
+
+/// the photo in its original size, or `small` or `medium` for the square thumbnails
+///
+/// curl -X GET "http://127.0.0.1:4500/students/{id}/photo?size=small" -o small.jpg
+async fn get_photo(Path(id): Path<String>, Query(query): Query<PhotoQuery>, State(state): State<SharedState>, headers: HeaderMap) -> Result<Response, StatusCode> {
+    let photo = {
+        let students = state.students.lock().await;
+        students.iter().find(|s| s.id == id).and_then(|s| s.photo.clone()).ok_or(StatusCode::NOT_FOUND)?
+    };
+    let name = match query.size {
+        Size::Small => photo.small,
+        Size::Medium => photo.medium,
+        Size::Original => photo.original,
+    };
+    let etag = format!("\"{}\"", name);
+    let cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').map(str::trim).any(|tag| tag == etag || tag == "*" || tag.strip_prefix("W/") == Some(&etag)));
+    if cached {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag), (header::CACHE_CONTROL, CACHE_CONTROL.to_string())]).into_response());
+    }
+
+    let bytes = match tokio::fs::read(state.photos_dir().join(&name)).await {
+        Ok(bytes) => unseal(&state, bytes).map_err(internal_error)?,
+        // replaced and pruned since we looked it up
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(StatusCode::NOT_FOUND),
+        Err(e) => return Err(internal_error(e)),
+    };
+    let (format, _, _) = imaging::probe(&bytes).map_err(|e| internal_error(io::Error::other(e)))?;
+    let headers = [
+        (header::CONTENT_TYPE, format.mime().to_string()),
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+    ];
+    Ok((headers, bytes).into_response())
+}
+
+
+/// check an upload and make the files of a photo from it: the original, small and medium
+pub fn process(upload: &[u8]) -> Result<[Vec<u8>; 3], (StatusCode, String)> {
+    let (format, width, height) = imaging::probe(upload).map_err(rejected)?;
+    if width < MIN_SIDE || height < MIN_SIDE {
+        let reason = format!("the photo is {}x{}, at least {}x{} is needed", width, height, MIN_SIDE, MIN_SIDE);
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, reason));
+    }
+    let image = imaging::decode(upload).map_err(rejected)?;
+    let quality = match format {
+        Format::Png => 0,
+        Format::Jpeg => JPEG_QUALITY,
+    };
+    let thumbnail = |side| imaging::cover(&image, side, side, Filter::Lanczos3).and_then(|thumbnail| imaging::encode(&thumbnail, format, quality));
+    Ok([
+        imaging::encode(&image, format, quality).map_err(rejected)?,
+        thumbnail(SMALL).map_err(rejected)?,
+        thumbnail(MEDIUM).map_err(rejected)?,
+    ])
+}
+
+fn rejected(e: imaging::Error) -> (StatusCode, String) {
+    let status = match e {
+        imaging::Error::Decode(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        imaging::Error::TooLarge { .. } | imaging::Error::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        imaging::Error::Encode(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string())
+}
+
+fn internal_error(e: io::Error) -> StatusCode {
+    eprintln!("could not store the photo: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+
+/// the file of a `multipart/form-data` body, the part named `photo` or else the first file
+fn multipart_file(content_type: &str, body: &[u8]) -> Result<Vec<u8>, String> {
+    let boundary = param(content_type, "boundary").filter(|b| !b.is_empty()).ok_or("the multipart body has no boundary")?;
+    let delimiter = format!("--{}", boundary);
+    let next = format!("\r\n{}", delimiter);
+
+    // everything before the first delimiter is a preamble nobody reads
+    let start = find(body, delimiter.as_bytes()).ok_or("the multipart body has no parts")?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n").ok_or("the multipart body is broken")?;
+        let end = find(rest, next.as_bytes()).ok_or("the multipart body is cut off")?;
+        let (part, after) = rest.split_at(end);
+        rest = &after[next.len()..];
+
+        let split = find(part, b"\r\n\r\n").ok_or("a part of the multipart body has no headers")?;
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let disposition = headers.lines().find(|line| line.to_ascii_lowercase().starts_with("content-disposition:")).unwrap_or_default();
+        parts.push((param(disposition, "name"), param(disposition, "filename").is_some(), &part[split + 4..]));
+    }
+    parts
+        .iter()
+        .find(|(name, _, _)| name.as_deref() == Some("photo"))
+        .or_else(|| parts.iter().find(|(_, file, _)| *file))
+        .map(|(_, _, data)| data.to_vec())
+        .ok_or_else(|| "the multipart body has no photo".to_string())
+}
+
+/// the value of `key=value` or `key="value"` after the first `;` of a header value
+fn param(value: &str, key: &str) -> Option<String> {
+    value
+        .split(';')
+        .skip(1)
+        .filter_map(|p| p.trim().strip_prefix(key)?.strip_prefix('='))
+        .map(|v| v.trim_matches('"').to_string())
+        .next()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = b"preamble\r\n".to_vec();
+        for (name, filename, data) in parts {
+            let filename = filename.map(|f| format!("; filename=\"{}\"", f)).unwrap_or_default();
+            body.extend(format!("--XyZ\r\nContent-Disposition: form-data; name=\"{}\"{}\r\n\r\n", name, filename).as_bytes());
+            body.extend(*data);
+            body.extend(b"\r\n");
+        }
+        body.extend(b"--XyZ--\r\n");
+        body
+    }
+
+    #[test]
+    fn multipart() {
+        let content_type = "multipart/form-data; boundary=XyZ";
+        let data: &[u8] = b"\x89PNG\r\n--Xy\r\n";
+        let named = body(&[("caption", None, b"me"), ("photo", None, data)]);
+        assert_eq!(multipart_file(content_type, &named).unwrap(), data);
+        let file = body(&[("caption", None, b"me"), ("upload", Some("me.png"), data)]);
+        assert_eq!(multipart_file("multipart/form-data; boundary=\"XyZ\"", &file).unwrap(), data);
+
+        assert!(multipart_file(content_type, &body(&[("caption", None, b"me")])).is_err());
+        assert!(multipart_file("multipart/form-data", &named).is_err());
+        assert!(multipart_file(content_type, &named[..named.len() - 12]).is_err());
+    }
+
+    #[test]
+    fn sizes_and_types() {
+        let png = |width, height| imaging::encode(&image::DynamicImage::new_rgb8(width, height), Format::Png, 0).unwrap();
+        let [original, small, medium] = process(&png(300, 100)).unwrap();
+        assert_eq!(imaging::probe(&original).unwrap(), (Format::Png, 300, 100));
+        assert_eq!(imaging::probe(&small).unwrap(), (Format::Png, SMALL, SMALL));
+        assert_eq!(imaging::probe(&medium).unwrap(), (Format::Png, MEDIUM, MEDIUM));
+
+        assert_eq!(process(&png(300, 10)).unwrap_err().0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(process(b"GIF89a").unwrap_err().0, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(process(&png(300, 100)[..60]).unwrap_err().0, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::{Arc, RwLock};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::net::TcpListener;
+use crate::{
+    admin::AdminKey,
+    app,
+    backup::{self, Backups},
+    compliance::{self, Compliance, ComplianceLog},
+    config::Config,
+    crypto::{self, Keys},
+    health,
+    state::AppState,
+    tenant::{self, Tenants},
+    tls, watcher, web,
+    webhook::{self, RetryPolicy, Webhooks},
+    SharedState,
+};
+
+
+/// the running parts of the server, so other front doors like gRPC can share the same stores
+pub struct Server {
+    pub app: Router,
+    // the default store
+    pub state: SharedState,
+    pub tenants: Arc<Tenants>,
+    pub admin_key: AdminKey,
+}
+
+
+/// load the stores and start the background tasks, must be called inside a tokio runtime
+pub fn build(config: &Config) -> Server {
+    // Email and mobile are encrypted in the data files when a master key is configured
+    let keys = Keys::configured(config.master_key_file.as_deref(), config.master_key.as_deref())
+        .unwrap_or_else(|e| panic!("{}", e))
+        .map(|keys| Arc::new(RwLock::new(keys)));
+
+    // Load student data from a file and initialize the shared state
+    // Refuse to start rather than serve an empty store, the first change would overwrite the file
+    let state = AppState::open(&config.data_file, keys.clone())
+        .unwrap_or_else(|e| panic!("could not load {}: {}", config.data_file.display(), e));
+    let state = Arc::new(state);
+
+    // Pick up edits of the data file made while the server is running
+    watcher::spawn(state.clone(), config.watch_interval);
+
+    // Calling Api From Following Curl Command
+        // curl -X GET http://127.0.0.1:4500/students
+        // curl -X POST http://127.0.0.1:4500/students -H "Content-Type: application/json" -d "{ \"name\": \"Aman\", \"email\": \"aman@example.com\", \"mobile\": \"9876543210\" }"
+        // curl -X PUT http://127.0.0.1:4500/students/5666cc48-2f9d-4db9-8725-5f7b5bb50231 -H "Content-Type: application/json" -d "{ \"name\": \"Aman Verasia\", \"email\": \"aman@example.com\", \"mobile\": \"9876543210\" }"
+        // curl -X DELETE http://127.0.0.1:4500/students/5666cc48-2f9d-4db9-8725-5f7b5bb50231
+
+    // Every tenant (school) has its own data file, reachable through /t/{tenant}/students or the X-Tenant header
+    let tenants = Arc::new(Tenants::load_encrypted(&config.tenants_dir, config.watch_interval, keys.clone()).expect("could not load the tenant list"));
+
+    // Encrypt plaintext and values of older master keys again in the background, see MASTER_KEY_FILE for rotation
+    if let Some(keys) = keys {
+        let (default, all) = (state.clone(), tenants.clone());
+        let stores = move || {
+            let tenants = all.list().into_iter().filter_map(|info| all.store(&info.name));
+            std::iter::once(default.clone()).chain(tenants).collect()
+        };
+        crypto::spawn_rotation(keys, config.master_key_file.clone(), stores, config.watch_interval);
+    }
+    if config.admin_key.is_none() {
+        println!("ADMIN_API_KEY is not set, the /admin routes are open to everybody");
+    }
+
+    // Send student changes to the registered webhooks in the background
+    let webhooks = Arc::new(Webhooks::load(&config.webhooks_file, RetryPolicy::default()).expect("could not load the webhooks"));
+    webhook::spawn(webhooks.clone(), state.events.subscribe());
+    webhook::follow_tenants(webhooks.clone(), tenants.events());
+
+    // The front-end (e.g. _18_2_Web with the wasm package) can be served from the same origin,
+    // other origins need to be allowed with CORS_ALLOWED_ORIGINS
+    let admin_key: AdminKey = config.admin_key.clone().map(Into::into);
+    let backups = Arc::new(Backups::new(&config.backup_dir, state.clone(), webhooks.clone(), tenants.clone()));
+    let compliance = Arc::new(Compliance {
+        state: state.clone(),
+        tenants: tenants.clone(),
+        webhooks: webhooks.clone(),
+        backups: backups.clone(),
+        log: ComplianceLog::new(&config.compliance_log),
+    });
+    let mut api = app(state.clone())
+        .merge(webhook::routes(webhooks, admin_key.clone()))
+        .merge(backup::routes(backups, admin_key.clone()))
+        .merge(compliance::routes(compliance, admin_key.clone()))
+        .merge(health::routes(state.clone(), tenants.clone(), admin_key.clone()));
+    if let Some(static_files) = config.static_files.clone() {
+        println!("Serving front-end from {}", static_files.dir.display());
+        api = api.merge(web::static_files(static_files));
+    }
+    let app = tenant::scoped(api, tenants.clone(), admin_key.clone()).layer(web::cors(&config.cors_origins));
+
+    Server { app, state, tenants, admin_key }
+}
+
+
+/// serve `app` on the configured address until the process ends
+pub async fn serve(config: Config, app: Router) {
+    let addr = config.addr;
+
+    // Serve https when a certificate is configured, see `cargo run --bin dev-certs` for local certificates
+    if let Some(tls_config) = config.tls {
+        let server_config = tls::server_config(&tls_config).unwrap_or_else(|e| panic!("could not load tls certificate: {}", e));
+        let rustls = RustlsConfig::from_config(Arc::new(server_config));
+        tls::spawn_reload(tls_config, rustls.clone(), config.watch_interval);
+
+        println!("Server running at https://{}", addr);
+        axum_server::bind_rustls(addr, rustls)
+            .serve(app.into_make_service())
+            .await
+            .unwrap();
+        return;
+    }
+
+    println!("Server running at http://{}", addr);
+
+    // Create a TCP listener
+    let listener = TcpListener::bind(addr).await.unwrap();
+
+    // Run the server
+    axum::serve(listener, app)
+        .await
+        .unwrap();
+}
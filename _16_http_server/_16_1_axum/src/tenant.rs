@@ -0,0 +1,445 @@
+use std::{collections::HashMap, fs, io, path::PathBuf, sync::{Arc, RwLock}, time::Duration};
+use axum::{
+    extract::{Path, Request, State},
+    http::{HeaderMap, StatusCode, Uri},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast, task::JoinHandle};
+use tower::ServiceExt;
+use uuid::Uuid;
+use crate::{admin::{self, AdminKey}, api::store_error, app, crypto::SharedKeys, state::{AppState, ChangeEvent, StoreError}, watcher, SharedState};
+
+
+/// header selecting the tenant for the plain `/students` routes
+pub const TENANT_HEADER: &str = "x-tenant";
+
+
+/// a school hosted by this server, stored in `tenants.json`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TenantInfo {
+    pub name: String,
+    #[serde(default)]
+    pub max_students: Option<usize>,
+    // keys accepted in the `X-Api-Key` header for this tenant's students
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    #[serde(default)]
+    pub suspended: bool,
+}
+
+/// body of `POST /admin/tenants`, a key is generated when no `api_keys` are given
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewTenant {
+    pub name: String,
+    pub max_students: Option<usize>,
+    pub api_keys: Option<Vec<String>>,
+}
+
+
+/// a change in the store of a tenant, see `Tenants::events`
+#[derive(Clone, Debug)]
+pub struct TenantEvent {
+    pub tenant: String,
+    pub event: ChangeEvent,
+}
+
+
+struct Tenant {
+    info: TenantInfo,
+    state: SharedState,
+    // the student routes bound to this tenant's store
+    router: Router,
+    watcher: JoinHandle<()>,
+    // passes the events of the store on to `Tenants::events`
+    forwarder: JoinHandle<()>,
+}
+
+impl Tenant {
+    fn stop(&self) {
+        self.watcher.abort();
+        self.forwarder.abort();
+    }
+}
+
+
+/// all tenants, every tenant has its own data file in `dir/<name>/students.json`
+pub struct Tenants {
+    dir: PathBuf,
+    watch_interval: Duration,
+    keys: Option<SharedKeys>,
+    tenants: RwLock<HashMap<String, Tenant>>,
+    events: broadcast::Sender<TenantEvent>,
+}
+
+impl Tenants {
+    /// load the tenant list from `dir/tenants.json` and start watching the data file of every tenant
+    pub fn load(dir: impl Into<PathBuf>, watch_interval: Duration) -> io::Result<Self> {
+        Tenants::load_encrypted(dir, watch_interval, None)
+    }
+
+    /// like `load`, with the personal fields of every tenant's data file encrypted by `keys`
+    pub fn load_encrypted(dir: impl Into<PathBuf>, watch_interval: Duration, keys: Option<SharedKeys>) -> io::Result<Self> {
+        let dir = dir.into();
+        let infos: Vec<TenantInfo> = match fs::read_to_string(dir.join("tenants.json")) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        let (events, _) = broadcast::channel(256);
+        let tenants = Tenants { dir, watch_interval, keys, tenants: RwLock::new(HashMap::new()), events };
+        {
+            let mut map = tenants.tenants.write().unwrap();
+            for info in infos {
+                let name = info.name.clone();
+                let tenant = tenants.open(info).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("tenant {}: {}", name, e)))?;
+                map.insert(name, tenant);
+            }
+        }
+        Ok(tenants)
+    }
+
+    fn open(&self, info: TenantInfo) -> Result<Tenant, StoreError> {
+        let data_file = self.dir.join(&info.name).join("students.json");
+        let state = Arc::new(AppState::open(data_file, self.keys.clone())?.with_quota(info.max_students));
+        // subscribed before anybody can reach the store, so no change is missed
+        let (mut changes, events, tenant) = (state.events.subscribe(), self.events.clone(), info.name.clone());
+        let forwarder = tokio::spawn(async move {
+            loop {
+                match changes.recv().await {
+                    Ok(event) => {
+                        let _ = events.send(TenantEvent { tenant: tenant.clone(), event });
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("tenant {} dropped {} student changes", tenant, missed),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(Tenant {
+            router: app(state.clone()),
+            watcher: watcher::spawn(state.clone(), self.watch_interval),
+            forwarder,
+            state,
+            info,
+        })
+    }
+
+    /// the changes of every tenant's store, tenants created later included
+    pub fn events(&self) -> broadcast::Receiver<TenantEvent> {
+        self.events.subscribe()
+    }
+
+    fn save(&self, tenants: &HashMap<String, Tenant>) -> io::Result<()> {
+        let mut infos: Vec<&TenantInfo> = tenants.values().map(|t| &t.info).collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join("tenants.json"), serde_json::to_string_pretty(&infos).map_err(io::Error::other)?)
+    }
+
+    pub fn list(&self) -> Vec<TenantInfo> {
+        let mut infos: Vec<TenantInfo> = self.tenants.read().unwrap().values().map(|t| t.info.clone()).collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    pub fn get(&self, name: &str) -> Option<TenantInfo> {
+        self.tenants.read().unwrap().get(name).map(|t| t.info.clone())
+    }
+
+    /// the store of a tenant, regardless of whether it is suspended
+    pub fn store(&self, name: &str) -> Option<SharedState> {
+        self.tenants.read().unwrap().get(name).map(|t| t.state.clone())
+    }
+
+    pub fn create(&self, new: NewTenant) -> Result<TenantInfo, StatusCode> {
+        // the name is used as directory name, so only allow a safe set of characters
+        let valid_name = !new.name.is_empty()
+            && new.name.len() <= 40
+            && new.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid_name {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let mut tenants = self.tenants.write().unwrap();
+        if tenants.contains_key(&new.name) {
+            return Err(StatusCode::CONFLICT);
+        }
+        let info = TenantInfo {
+            name: new.name,
+            max_students: new.max_students,
+            api_keys: new.api_keys.unwrap_or_else(|| vec![Uuid::new_v4().simple().to_string()]),
+            suspended: false,
+        };
+        fs::create_dir_all(self.dir.join(&info.name)).map_err(internal_error)?;
+        tenants.insert(info.name.clone(), self.open(info.clone()).map_err(store_error)?);
+        self.save(&tenants).map_err(internal_error)?;
+        Ok(info)
+    }
+
+    pub fn set_suspended(&self, name: &str, suspended: bool) -> Result<TenantInfo, StatusCode> {
+        let mut tenants = self.tenants.write().unwrap();
+        let tenant = tenants.get_mut(name).ok_or(StatusCode::NOT_FOUND)?;
+        tenant.info.suspended = suspended;
+        let info = tenant.info.clone();
+        self.save(&tenants).map_err(internal_error)?;
+        Ok(info)
+    }
+
+    /// remove the tenant together with its data file
+    pub fn delete(&self, name: &str) -> Result<(), StatusCode> {
+        let mut tenants = self.tenants.write().unwrap();
+        let tenant = tenants.remove(name).ok_or(StatusCode::NOT_FOUND)?;
+        tenant.stop();
+        self.save(&tenants).map_err(internal_error)?;
+        match fs::remove_dir_all(self.dir.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(internal_error(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// the router of a tenant if the request may use it
+    fn router(&self, name: &str, headers: &HeaderMap) -> Result<Router, StatusCode> {
+        let tenants = self.tenants.read().unwrap();
+        let tenant = tenants.get(name).ok_or(StatusCode::NOT_FOUND)?;
+        if tenant.info.suspended {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        let key = admin::api_key(headers);
+        if !tenant.info.api_keys.iter().any(|k| Some(k.as_str()) == key) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(tenant.router.clone())
+    }
+
+    /// pass a request on to the student routes of a tenant
+    async fn forward(&self, name: &str, req: Request) -> Response {
+        match self.router(name, req.headers()) {
+            Ok(router) => router.oneshot(req).await.into_response(),
+            Err(status) => status.into_response(),
+        }
+    }
+}
+
+impl Drop for Tenants {
+    fn drop(&mut self) {
+        for tenant in self.tenants.get_mut().unwrap().values() {
+            tenant.stop();
+        }
+    }
+}
+
+fn internal_error(e: io::Error) -> StatusCode {
+    eprintln!("could not update tenants: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+
+/// add the tenant routes to `app`:
+/// - `/t/{tenant}/students...` and `/students...` with an `X-Tenant` header go to the tenant's store
+/// - `/admin/tenants` to create, list, suspend, resume and delete tenants
+pub fn scoped(app: Router, tenants: Arc<Tenants>, admin_key: AdminKey) -> Router {
+    let admin = Router::new()
+        .route("/admin/tenants", get(list_tenants).post(create_tenant))
+        .route("/admin/tenants/{name}", get(get_tenant).delete(delete_tenant))
+        .route("/admin/tenants/{name}/suspend", post(suspend_tenant))
+        .route("/admin/tenants/{name}/resume", post(resume_tenant))
+        .route_layer(from_fn_with_state(admin_key, admin::require_key))
+        .with_state(tenants.clone());
+
+    app.merge(admin).layer(from_fn_with_state(tenants, dispatch))
+}
+
+
+/// send requests for a tenant to its student routes, by path prefix or by header.
+/// Done as middleware instead of a route, so the tenant routes see a request without path parameters.
+///
+/// curl -X GET http://127.0.0.1:4500/t/{tenant}/students -H "X-Api-Key: {key}"
+/// curl -X GET http://127.0.0.1:4500/students -H "X-Tenant: {tenant}" -H "X-Api-Key: {key}"
+async fn dispatch(State(tenants): State<Arc<Tenants>>, mut req: Request, next: Next) -> Response {
+    if let Some(scoped) = req.uri().path().strip_prefix("/t/") {
+        let (name, rest) = scoped.split_at(scoped.find('/').unwrap_or(scoped.len()));
+        let name = name.to_string();
+        // `/t/{tenant}` is the root of the tenant
+        let rest = if rest.is_empty() { "/" } else { rest };
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{}?{}", rest, query),
+            None => rest.to_string(),
+        };
+        match path_and_query.parse::<Uri>() {
+            Ok(uri) => *req.uri_mut() = uri,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        }
+        return tenants.forward(&name, req).await;
+    }
+    if req.uri().path().starts_with("/admin/") {
+        return next.run(req).await;
+    }
+    match req.headers().get(TENANT_HEADER).map(|value| value.to_str().map(str::to_string)) {
+        None => next.run(req).await,
+        Some(Ok(name)) => tenants.forward(&name, req).await,
+        Some(Err(_)) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+
+/// curl -X GET http://127.0.0.1:4500/admin/tenants -H "X-Api-Key: {admin key}"
+async fn list_tenants(State(tenants): State<Arc<Tenants>>) -> Json<Vec<TenantInfo>> {
+    Json(tenants.list())
+}
+
+/// curl -X POST http://127.0.0.1:4500/admin/tenants -H "Content-Type: application/json" -d "{ \"name\": \"north-school\", \"max_students\": 500 }"
+async fn create_tenant(State(tenants): State<Arc<Tenants>>, Json(new): Json<NewTenant>) -> Result<(StatusCode, Json<TenantInfo>), StatusCode> {
+    tenants.create(new).map(|info| (StatusCode::CREATED, Json(info)))
+}
+
+async fn get_tenant(State(tenants): State<Arc<Tenants>>, Path(name): Path<String>) -> Result<Json<TenantInfo>, StatusCode> {
+    tenants.get(&name).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// curl -X POST http://127.0.0.1:4500/admin/tenants/{name}/suspend
+async fn suspend_tenant(State(tenants): State<Arc<Tenants>>, Path(name): Path<String>) -> Result<Json<TenantInfo>, StatusCode> {
+    tenants.set_suspended(&name, true).map(Json)
+}
+
+async fn resume_tenant(State(tenants): State<Arc<Tenants>>, Path(name): Path<String>) -> Result<Json<TenantInfo>, StatusCode> {
+    tenants.set_suspended(&name, false).map(Json)
+}
+
+/// curl -X DELETE http://127.0.0.1:4500/admin/tenants/{name}
+async fn delete_tenant(State(tenants): State<Arc<Tenants>>, Path(name): Path<String>) -> StatusCode {
+    tenants.delete(&name).map_or_else(|status| status, |_| StatusCode::NO_CONTENT)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::{to_bytes, Body}, http::Request};
+    use serde_json::{json, Value};
+
+    struct Setup {
+        app: Router,
+        dir: tempfile::TempDir,
+        keys: HashMap<String, String>,
+    }
+
+    async fn setup() -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let default = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let tenants = Arc::new(Tenants::load(dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+        let app = scoped(crate::app(default), tenants, Some(Arc::from("admin-key")));
+
+        let mut keys = HashMap::new();
+        for (name, max) in [("north", None), ("south", Some(1))] {
+            let (status, body) = send(&app, "POST", "/admin/tenants", &[("x-api-key", "admin-key")], Some(json!({ "name": name, "max_students": max }))).await;
+            assert_eq!(status, StatusCode::CREATED);
+            keys.insert(name.to_string(), body["api_keys"][0].as_str().unwrap().to_string());
+        }
+        Setup { app, dir, keys }
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, headers: &[(&str, &str)], body: Option<Value>) -> (StatusCode, Value) {
+        let mut req = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+        let response = app.clone().oneshot(req.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&data).unwrap_or(Value::Null))
+    }
+
+    fn aman() -> Option<Value> {
+        Some(json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" }))
+    }
+
+    #[tokio::test]
+    async fn tenants_only_see_their_own_students() {
+        let s = setup().await;
+        let north = [("x-api-key", s.keys["north"].as_str())];
+        let south = [("x-api-key", s.keys["south"].as_str())];
+
+        assert_eq!(send(&s.app, "POST", "/t/north/students", &north, aman()).await.0, StatusCode::CREATED);
+        let (_, listed) = send(&s.app, "GET", "/t/north/students", &north, None).await;
+        let id = listed[0]["id"].as_str().unwrap().to_string();
+
+        // the other tenant and the default store do not see the student
+        assert_eq!(send(&s.app, "GET", "/t/south/students", &south, None).await.1, json!([]));
+        assert_eq!(send(&s.app, "GET", "/students", &[], None).await.1, json!([]));
+        let other_path = format!("/t/south/students/{}", id);
+        assert_eq!(send(&s.app, "GET", &other_path, &south, None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&s.app, "PUT", &other_path, &south, aman()).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&s.app, "DELETE", &other_path, &south, None).await.0, StatusCode::NOT_FOUND);
+
+        // a key of one tenant does not open another tenant, neither by path nor by header
+        assert_eq!(send(&s.app, "GET", "/t/north/students", &south, None).await.0, StatusCode::UNAUTHORIZED);
+        let south_key_for_north = [("x-tenant", "north"), ("x-api-key", s.keys["south"].as_str())];
+        assert_eq!(send(&s.app, "GET", "/students", &south_key_for_north, None).await.0, StatusCode::UNAUTHORIZED);
+
+        let north_by_header = [("x-tenant", "north"), ("x-api-key", s.keys["north"].as_str())];
+        assert_eq!(send(&s.app, "GET", "/students", &north_by_header, None).await.1, listed);
+
+        // the root of a tenant is a path of the tenant too
+        assert_eq!(send(&s.app, "GET", "/t/north", &south, None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&s.app, "GET", "/t/north?fields=id", &north, None).await.0, StatusCode::NOT_FOUND);
+
+        assert!(s.dir.path().join("tenants/north/students.json").exists());
+        assert!(!s.dir.path().join("tenants/south/students.json").exists());
+    }
+
+    #[tokio::test]
+    async fn quota_suspension_and_deletion() {
+        let s = setup().await;
+        let south = [("x-api-key", s.keys["south"].as_str())];
+        let admin = [("x-api-key", "admin-key")];
+
+        assert_eq!(send(&s.app, "POST", "/t/south/students", &south, aman()).await.0, StatusCode::CREATED);
+        assert_eq!(send(&s.app, "POST", "/t/south/students", &south, aman()).await.0, StatusCode::FORBIDDEN);
+
+        assert_eq!(send(&s.app, "POST", "/admin/tenants/south/suspend", &[], None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&s.app, "POST", "/admin/tenants/south/suspend", &admin, None).await.0, StatusCode::OK);
+        assert_eq!(send(&s.app, "GET", "/t/south/students", &south, None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(send(&s.app, "POST", "/admin/tenants/south/resume", &admin, None).await.0, StatusCode::OK);
+        assert_eq!(send(&s.app, "GET", "/t/south/students", &south, None).await.0, StatusCode::OK);
+
+        assert_eq!(send(&s.app, "DELETE", "/admin/tenants/south", &admin, None).await.0, StatusCode::NO_CONTENT);
+        assert_eq!(send(&s.app, "GET", "/t/south/students", &south, None).await.0, StatusCode::NOT_FOUND);
+        assert!(!s.dir.path().join("tenants/south").exists());
+
+        let (_, listed) = send(&s.app, "GET", "/admin/tenants", &admin, None).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn tenant_names_can_not_escape_the_tenants_directory() {
+        let s = setup().await;
+        let (status, _) = send(&s.app, "POST", "/admin/tenants", &[("x-api-key", "admin-key")], Some(json!({ "name": "../north" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn a_tenant_with_a_wrong_key_is_an_error_not_an_empty_store() {
+        use crate::{crypto::{generate_key, Keys}, model::Student};
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("north").join("students.json");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(dir.path().join("tenants.json"), r#"[{ "name": "north" }]"#).unwrap();
+        let keys = || -> SharedKeys { Arc::new(RwLock::new(Keys::single(&generate_key()).unwrap())) };
+
+        let state = AppState::open(&file, Some(keys())).unwrap();
+        let mut students = state.students.lock().await;
+        students.push(Student { id: "aman".to_string(), name: "Aman".to_string(), email: "aman@example.com".to_string(), mobile: "9876543210".to_string(), ..Default::default() });
+        state.sync(&mut students).unwrap();
+        let written = fs::read(&file).unwrap();
+
+        for wrong in [None, Some(keys())] {
+            assert!(Tenants::load_encrypted(dir.path(), Duration::from_secs(60), wrong).is_err());
+        }
+        assert_eq!(fs::read(&file).unwrap(), written);
+    }
+}
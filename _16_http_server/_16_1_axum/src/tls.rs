@@ -0,0 +1,248 @@
+use std::{fs, io, path::{Path, PathBuf}, sync::Arc, time::Duration};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use tokio::task::JoinHandle;
+use crate::handler;
+
+
+/// where to find the PEM files for https
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    // TLS_CERT - certificate chain of the server
+    pub cert: PathBuf,
+    // TLS_KEY - private key of the server
+    pub key: PathBuf,
+    // TLS_CLIENT_CA - when set, clients must present a certificate signed by one of these CAs
+    pub client_ca: Option<PathBuf>,
+}
+
+
+/// the crypto provider used for all tls connections of the server
+pub fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+
+/// read the PEM files and build the rustls server config
+pub fn server_config(tls: &TlsConfig) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| pem_error(&tls.cert, e))?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key).map_err(|e| pem_error(&tls.key, e))?;
+
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?;
+
+    let builder = match &tls.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(client_ca).map_err(|e| pem_error(client_ca, e))? {
+                roots.add(cert.map_err(|e| pem_error(client_ca, e))?).map_err(io::Error::other)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider())
+                .build()
+                .map_err(io::Error::other)?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder.with_single_cert(certs, key).map_err(io::Error::other)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn pem_error(path: &Path, e: rustls::pki_types::pem::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+}
+
+
+/// watch the PEM files and swap in the new certificates when one of them changes.
+///
+/// New connections use the new certificates, open connections keep the old ones. If the new
+/// files can not be loaded (e.g. the key was written but the certificate not yet) the old
+/// config stays active and loading is retried on the next change.
+pub fn spawn_reload(tls: TlsConfig, rustls: RustlsConfig, interval: Duration) -> JoinHandle<()> {
+    let mut last = fingerprints(&tls);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let current = fingerprints(&tls);
+            if current == last {
+                continue;
+            }
+            last = current;
+            match server_config(&tls) {
+                Ok(config) => {
+                    rustls.reload_from_config(Arc::new(config));
+                    println!("reloaded tls certificate from {}", tls.cert.display());
+                }
+                Err(e) => eprintln!("could not reload tls certificate, keeping the old one: {}", e),
+            }
+        }
+    })
+}
+
+fn fingerprints(tls: &TlsConfig) -> Vec<Option<u64>> {
+    [Some(&tls.cert), Some(&tls.key), tls.client_ca.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|path| handler::read_file(path).ok().flatten().map(|data| handler::fingerprint(&data)))
+        .collect()
+}
+
+
+/// self-signed certificates for local development and tests
+pub mod dev {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair, KeyUsagePurpose};
+
+    /// file names written by `generate`
+    pub const CA_CERT: &str = "ca.pem";
+    pub const CA_KEY: &str = "ca-key.pem";
+    pub const SERVER_CERT: &str = "server.pem";
+    pub const SERVER_KEY: &str = "server-key.pem";
+    pub const CLIENT_CERT: &str = "client.pem";
+    pub const CLIENT_KEY: &str = "client-key.pem";
+
+    /// write a new CA plus a server certificate for `localhost`/`127.0.0.1` and a client
+    /// certificate signed by it into `dir`, existing files are overwritten
+    pub fn generate(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)?;
+
+        let mut ca_params = CertificateParams::new(Vec::<String>::new())?;
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.distinguished_name.push(DnType::CommonName, "studet-api dev CA");
+        ca_params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+        let ca_key = KeyPair::generate()?;
+        let ca_cert = ca_params.self_signed(&ca_key)?;
+        fs::write(dir.join(CA_CERT), ca_cert.pem())?;
+        fs::write(dir.join(CA_KEY), ca_key.serialize_pem())?;
+        let issuer = Issuer::new(ca_params, ca_key);
+
+        let leaves = [
+            (SERVER_CERT, SERVER_KEY, "localhost", ExtendedKeyUsagePurpose::ServerAuth),
+            (CLIENT_CERT, CLIENT_KEY, "studet-api dev client", ExtendedKeyUsagePurpose::ClientAuth),
+        ];
+        for (cert_file, key_file, name, usage) in leaves {
+            let mut params = CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+            params.distinguished_name.push(DnType::CommonName, name);
+            params.use_authority_key_identifier_extension = true;
+            params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+            params.extended_key_usages = vec![usage];
+            let key = KeyPair::generate()?;
+            let cert = params.signed_by(&key, &issuer)?;
+            fs::write(dir.join(cert_file), cert.pem())?;
+            fs::write(dir.join(key_file), key.serialize_pem())?;
+        }
+        Ok(())
+    }
+
+    /// tls config for a server using the files written by `generate`
+    pub fn server(dir: &Path, require_client_cert: bool) -> TlsConfig {
+        TlsConfig {
+            cert: dir.join(SERVER_CERT),
+            key: dir.join(SERVER_KEY),
+            client_ca: require_client_cert.then(|| dir.join(CA_CERT)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, state::AppState};
+    use rustls::ClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    async fn start(tls: &TlsConfig, data_dir: &Path) -> (std::net::SocketAddr, RustlsConfig) {
+        let rustls = RustlsConfig::from_config(Arc::new(server_config(tls).unwrap()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app(Arc::new(AppState::new(data_dir.join("students.json")).unwrap()));
+        let server = axum_server::from_tcp_rustls(listener, rustls.clone());
+        tokio::spawn(async move { server.serve(app.into_make_service()).await });
+        (addr, rustls)
+    }
+
+    async fn get(addr: std::net::SocketAddr, pki: &Path, with_client_cert: bool) -> io::Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_file(pki.join(dev::CA_CERT)).unwrap()).unwrap();
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = if with_client_cert {
+            let certs = vec![CertificateDer::from_pem_file(pki.join(dev::CLIENT_CERT)).unwrap()];
+            let key = PrivateKeyDer::from_pem_file(pki.join(dev::CLIENT_KEY)).unwrap();
+            builder.with_client_auth_cert(certs, key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(config)).connect("localhost".try_into().unwrap(), stream).await?;
+        stream.write_all(b"GET /students HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn serves_https_with_dev_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        dev::generate(dir.path()).unwrap();
+        let (addr, _) = start(&dev::server(dir.path(), false), dir.path()).await;
+
+        let response = get(addr, dir.path(), false).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_rejects_clients_without_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        dev::generate(dir.path()).unwrap();
+        let (addr, _) = start(&dev::server(dir.path(), true), dir.path()).await;
+
+        let response = get(addr, dir.path(), true).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        let rejected = get(addr, dir.path(), false).await;
+        assert!(!matches!(rejected, Ok(response) if response.starts_with("HTTP/1.1 200")));
+    }
+
+    #[tokio::test]
+    async fn certificate_is_reloaded_when_the_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        dev::generate(dir.path()).unwrap();
+        let tls = dev::server(dir.path(), false);
+        let (addr, rustls) = start(&tls, dir.path()).await;
+        spawn_reload(tls, rustls, Duration::from_millis(20));
+
+        // a new CA and server certificate are written, clients trusting only the new CA must connect
+        let renewed = dir.path().join("renewed");
+        dev::generate(&renewed).unwrap();
+        fs::copy(renewed.join(dev::SERVER_CERT), dir.path().join(dev::SERVER_CERT)).unwrap();
+        fs::copy(renewed.join(dev::SERVER_KEY), dir.path().join(dev::SERVER_KEY)).unwrap();
+
+        let mut response = get(addr, &renewed, false).await;
+        for _ in 0..50 {
+            if response.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            response = get(addr, &renewed, false).await;
+        }
+        assert!(response.unwrap().starts_with("HTTP/1.1 200"));
+    }
+}
@@ -0,0 +1,74 @@
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::{fields::Fieldset, model::Student};
+
+
+/// the date v1 was deprecated, as unix timestamp for the `Deprecation` header (RFC 9745)
+pub const DEPRECATED_SINCE: &str = "@1792368000";
+
+/// the date v1 will be removed, for the `Sunset` header (RFC 8594)
+pub const SUNSET: &str = "Fri, 30 Apr 2027 00:00:00 GMT";
+
+
+/// the first shape of a student, served under /students and /v1/students
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StudentV1 {
+    // for ignore the id field on creating time
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub email: String,
+    pub mobile: String,
+}
+
+impl From<&Student> for StudentV1 {
+    fn from(student: &Student) -> Self {
+        StudentV1 {
+            id: student.id.clone(),
+            name: student.name.clone(),
+            email: student.email.clone(),
+            mobile: student.mobile.clone(),
+        }
+    }
+}
+
+impl Fieldset for StudentV1 {
+    const FIELDS: &'static [&'static str] = &["id", "name", "email", "mobile"];
+}
+
+impl StudentV1 {
+    /// a new stored student, the id is set by the caller
+    pub fn into_student(self) -> Student {
+        Student {
+            name: self.name,
+            email: self.email,
+            mobile: self.mobile,
+            created_at: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    /// update a stored student, the fields v1 does not know about are kept.
+    /// The name parts are dropped when the full name changes, they would no longer match.
+    pub fn apply_to(self, student: &mut Student) {
+        if student.name != self.name {
+            student.name_parts = None;
+        }
+        student.name = self.name;
+        student.email = self.email;
+        student.mobile = self.mobile;
+        student.updated_at = Some(Utc::now());
+    }
+}
+
+
+/// middleware for the v1 routes, tells clients that v1 goes away and where to go instead
+pub async fn deprecation_headers(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static(DEPRECATED_SINCE));
+    headers.insert("sunset", HeaderValue::from_static(SUNSET));
+    headers.insert("link", HeaderValue::from_static("</v2/students>; rel=\"successor-version\""));
+    response
+}
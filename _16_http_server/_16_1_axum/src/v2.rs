@@ -0,0 +1,221 @@
+use std::convert::Infallible;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
+    routing::{get, on, MethodFilter, MethodRouter},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_stream::{wrappers::{errors::BroadcastStreamRecvError, BroadcastStream}, Stream, StreamExt};
+use crate::{
+    api::{create, delete_student, find_by_email, update},
+    bindings,
+    fields::{Fields, Fieldset, Sparse},
+    format::{Accept, Body, Encoded},
+    model::{Contact, ContactKind, NameParts, Student},
+    state::{ChangeEvent, ChangeKind},
+    SharedState,
+};
+
+
+/// a student in v2 - structured name, any number of contacts and timestamps
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StudentV2 {
+    pub id: String,
+    pub name: NameV2,
+    pub contacts: Vec<ContactV2>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NameV2 {
+    pub given: String,
+    pub family: Option<String>,
+    pub display: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ContactV2 {
+    pub kind: ContactKind,
+    pub value: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+/// body of `POST /v2/students` and `PUT /v2/students/{id}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StudentInputV2 {
+    pub name: NameInputV2,
+    pub contacts: Vec<ContactV2>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NameInputV2 {
+    pub given: String,
+    pub family: Option<String>,
+}
+
+
+impl From<&Student> for StudentV2 {
+    fn from(student: &Student) -> Self {
+        // students stored through v1 have no name parts, the full name is the given name then
+        let parts = student.name_parts.clone().unwrap_or_else(|| NameParts { given: student.name.clone(), family: None });
+        let primary = [(ContactKind::Email, &student.email), (ContactKind::Mobile, &student.mobile)]
+            .into_iter()
+            .map(|(kind, value)| ContactV2 { kind, value: value.clone(), primary: true });
+        let others = student.contacts.iter().map(|c| ContactV2 { kind: c.kind, value: c.value.clone(), primary: false });
+
+        StudentV2 {
+            id: student.id.clone(),
+            name: NameV2 { given: parts.given, family: parts.family, display: student.name.clone() },
+            contacts: primary.chain(others).collect(),
+            created_at: student.created_at,
+            updated_at: student.updated_at,
+        }
+    }
+}
+
+impl Fieldset for StudentV2 {
+    const FIELDS: &'static [&'static str] = &["id", "name", "contacts", "created_at", "updated_at"];
+}
+
+impl StudentInputV2 {
+    /// the stored student without id and timestamps.
+    ///
+    /// The primary email and mobile become the `email` and `mobile` every version knows about,
+    /// the first one of a kind is taken when none is marked as primary. The student has to pass
+    /// `Student::validate`, v2 is stricter than v1.
+    pub fn into_student(self) -> Result<Student, String> {
+        let mut contacts = self.contacts;
+        let mut take_primary = |kind: ContactKind| {
+            let index = contacts
+                .iter()
+                .position(|c| c.kind == kind && c.primary)
+                .or_else(|| contacts.iter().position(|c| c.kind == kind))
+                .ok_or_else(|| format!("a contact of kind {:?} is required", kind))?;
+            Ok::<_, String>(contacts.remove(index).value)
+        };
+        let email = take_primary(ContactKind::Email)?;
+        let mobile = take_primary(ContactKind::Mobile)?;
+
+        let given = self.name.given.trim().to_string();
+        let family = self.name.family.map(|f| f.trim().to_string()).filter(|f| !f.is_empty());
+        let name = match &family {
+            Some(family) => format!("{} {}", given, family),
+            None => given.clone(),
+        };
+
+        let student = Student {
+            name,
+            email,
+            mobile,
+            name_parts: Some(NameParts { given, family }),
+            contacts: contacts.into_iter().map(|c| Contact { kind: c.kind, value: c.value }).collect(),
+            ..Default::default()
+        };
+        student.validate()?;
+        Ok(student)
+    }
+}
+
+
+/// replace everything a v2 input sets, the id, the photo and the creation time stay
+pub fn replace(student: &mut Student, changed: Student) {
+    *student = Student { id: student.id.clone(), photo: student.photo.take(), created_at: student.created_at, updated_at: Some(Utc::now()), ..changed };
+}
+
+
+/// a change of a student as v2 clients see it, one `change` event of `GET /v2/students/events`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StudentEventV2 {
+    pub kind: ChangeKind,
+    pub id: String,
+    // the student after the change, `None` for deleted students
+    pub student: Option<StudentV2>,
+}
+
+impl From<&ChangeEvent> for StudentEventV2 {
+    fn from(event: &ChangeEvent) -> Self {
+        StudentEventV2 { kind: event.kind, id: event.id.clone(), student: event.student.as_ref().map(StudentV2::from) }
+    }
+}
+
+
+/// query of `GET /v2/students`
+#[derive(Deserialize, Debug, Default)]
+pub struct Lookup {
+    // only the students with this email, compared like the uniqueness check does
+    pub email: Option<String>,
+}
+
+
+/// the v2 student routes, nested under /v2. The events have no method in student.proto, the
+/// gRPC service streams them with WatchStudents.
+pub fn routes() -> Router<SharedState> {
+    bindings::router(wrapper)
+        .route("/students/events", get(student_events))
+}
+
+/// the v2 handler of a method of student.proto, see `bindings::router`
+pub fn wrapper(rpc: &str, filter: MethodFilter) -> Option<MethodRouter<SharedState>> {
+    Some(match rpc {
+        "ListStudents" => on(filter, get_students),
+        "GetStudent" => on(filter, get_student),
+        "CreateStudent" => on(filter, add_student),
+        "UpdateStudent" => on(filter, update_student),
+        "DeleteStudent" => on(filter, delete_student),
+        _ => return None,
+    })
+}
+
+
+/// every change of the students from now on as server-sent events. A client which falls behind
+/// misses changes, it gets a `resync` event then and should load all students again.
+///
+/// curl -N http://127.0.0.1:4500/v2/students/events
+async fn student_events(State(state): State<SharedState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let resync = || Event::default().event("resync").data("");
+    let events = BroadcastStream::new(state.events.subscribe()).map(move |event| {
+        Ok(match event {
+            Ok(event) => Event::default().event("change").json_data(StudentEventV2::from(&event)).unwrap_or_else(|_| resync()),
+            Err(BroadcastStreamRecvError::Lagged(_)) => resync(),
+        })
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// curl -X GET http://127.0.0.1:4500/v2/students
+/// only some fields - curl -X GET "http://127.0.0.1:4500/v2/students?fields=id,name"
+/// by email - curl -X GET "http://127.0.0.1:4500/v2/students?email=aman@example.com"
+async fn get_students(Accept(format): Accept, fields: Fields<StudentV2>, Query(lookup): Query<Lookup>, State(state): State<SharedState>) -> Encoded<Sparse<Vec<StudentV2>>> {
+    let students = state.students.lock().await;
+    let found = match &lookup.email {
+        Some(email) => find_by_email(&state, &students, email).into_iter().map(StudentV2::from).collect(),
+        None => students.iter().map(StudentV2::from).collect(),
+    };
+    Encoded(format, fields.apply(found))
+}
+
+/// curl -X GET http://127.0.0.1:4500/v2/students/{id}
+async fn get_student(Path(id): Path<String>, Accept(format): Accept, fields: Fields<StudentV2>, State(state): State<SharedState>) -> Result<Encoded<Sparse<StudentV2>>, StatusCode> {
+    let students = state.students.lock().await;
+    students.iter().find(|s| s.id == id).map(|s| Encoded(format, fields.apply(StudentV2::from(s)))).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// curl -X POST http://127.0.0.1:4500/v2/students -H "Content-Type: application/json" -d "{ \"name\": { \"given\": \"Aman\", \"family\": \"Verasia\" }, \"contacts\": [{ \"kind\": \"email\", \"value\": \"aman@example.com\" }, { \"kind\": \"mobile\", \"value\": \"9876543210\" }] }"
+async fn add_student(Accept(format): Accept, fields: Fields<StudentV2>, State(state): State<SharedState>, Body(input): Body<StudentInputV2>) -> Result<Response, StatusCode> {
+    let student = input.into_student().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let student = create(&state, Student { created_at: Some(Utc::now()), ..student }).await?;
+    let location = format!("/v2/students/{}", student.id);
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], Encoded(format, fields.apply(StudentV2::from(&student)))).into_response())
+}
+
+/// curl -X PUT http://127.0.0.1:4500/v2/students/{id} -H "Content-Type: application/json" -d "{ \"name\": { \"given\": \"Aman\" }, \"contacts\": [{ \"kind\": \"email\", \"value\": \"aman@example.com\" }, { \"kind\": \"mobile\", \"value\": \"9876543210\" }] }"
+async fn update_student(Path(id): Path<String>, Accept(format): Accept, fields: Fields<StudentV2>, State(state): State<SharedState>, Body(input): Body<StudentInputV2>) -> Result<Encoded<Sparse<StudentV2>>, StatusCode> {
+    let changed = input.into_student().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let student = update(&state, &id, |student| replace(student, changed)).await?;
+    Ok(Encoded(format, fields.apply(StudentV2::from(&student))))
+}
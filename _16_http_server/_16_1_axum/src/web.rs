@@ -0,0 +1,182 @@
+use std::{path::{Path, PathBuf}, sync::Arc, time::Duration};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use tower::ServiceExt;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    services::{ServeDir, ServeFile},
+};
+use crate::{admin::API_KEY_HEADER, tenant::TENANT_HEADER};
+
+
+/// where the front-end files are and how long browsers may cache them
+#[derive(Clone, Debug)]
+pub struct StaticConfig {
+    // STATIC_DIR - e.g. _18_rust_and_web_assembly/_18_2_Web
+    pub dir: PathBuf,
+    // WASM_PKG_DIR - the `pkg` output of wasm-pack for _18_1_wasm_package, served under /pkg
+    pub pkg_dir: Option<PathBuf>,
+    // STATIC_MAX_AGE - seconds, html pages are always revalidated
+    pub max_age: Duration,
+}
+
+
+/// paths which belong to the api, they never fall back to the front-end's index.html
+fn is_api_path(path: &str) -> bool {
+    ["/students", "/v1/", "/v2/", "/t/", "/admin/", "/webhooks", "/healthz", "/readyz"].iter().any(|prefix| path.starts_with(prefix))
+}
+
+
+/// router serving the front-end for every path no api route matched.
+///
+/// Paths without a file extension which do not exist are answered with `index.html`, so a
+/// single page app can use its own routes. Missing assets like `app.js` stay 404.
+pub fn static_files(config: StaticConfig) -> Router {
+    let config = Arc::new(config);
+    let mut router = Router::new();
+    if let Some(pkg_dir) = &config.pkg_dir {
+        router = router.nest_service("/pkg", ServeDir::new(pkg_dir));
+    }
+    router
+        .fallback(serve_static)
+        .layer(axum::middleware::from_fn_with_state(config.clone(), cache_headers))
+        .with_state(config)
+}
+
+async fn serve_static(State(config): State<Arc<StaticConfig>>, req: Request) -> Response {
+    let path = req.uri().path().to_string();
+    if is_api_path(&path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let response = ServeDir::new(&config.dir).oneshot(req).await.into_response();
+    if response.status() == StatusCode::NOT_FOUND && Path::new(&path).extension().is_none() {
+        return ServeFile::new(config.dir.join("index.html")).oneshot(Request::new(Body::empty())).await.into_response();
+    }
+    response
+}
+
+/// html is revalidated on every load so new releases show up, everything else is cached for `max_age`
+async fn cache_headers(State(config): State<Arc<StaticConfig>>, req: Request, next: axum::middleware::Next) -> Response {
+    let mut response = next.run(req).await;
+    if !(response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED) {
+        return response;
+    }
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    let cache_control = if is_html {
+        HeaderValue::from_static("no-cache")
+    } else {
+        HeaderValue::from_str(&format!("public, max-age={}", config.max_age.as_secs())).unwrap()
+    };
+    response.headers_mut().insert(header::CACHE_CONTROL, cache_control);
+    response
+}
+
+
+/// CORS policy for the api - `origins` are full origins like `http://localhost:8080` or `*` for any.
+/// Without origins no cross-origin requests are allowed.
+pub fn cors(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()))
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::AUTHORIZATION,
+            HeaderName::from_static(API_KEY_HEADER),
+            HeaderName::from_static(TENANT_HEADER),
+        ])
+        .expose_headers([
+            header::LOCATION,
+            HeaderName::from_static("deprecation"),
+            HeaderName::from_static("sunset"),
+            header::LINK,
+        ])
+        .max_age(Duration::from_secs(3600))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, state::AppState};
+    use axum::body::to_bytes;
+    use std::fs;
+
+    fn setup() -> (Router, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let web = dir.path().join("web");
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&web).unwrap();
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(web.join("index.html"), "<html>roster</html>").unwrap();
+        fs::write(web.join("index.js"), "run();").unwrap();
+        fs::write(pkg.join("_18_1_wasm_package_bg.wasm"), b"\0asm").unwrap();
+
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let config = StaticConfig { dir: web, pkg_dir: Some(pkg), max_age: Duration::from_secs(600) };
+        let app = app(state).merge(static_files(config)).layer(cors(&["http://localhost:8080".to_string()]));
+        (app, dir)
+    }
+
+    async fn get(app: &Router, uri: &str) -> Response {
+        app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_wasm_with_mime_type_and_cache_headers() {
+        let (app, _dir) = setup();
+        let response = get(&app, "/pkg/_18_1_wasm_package_bg.wasm").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/wasm");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=600");
+
+        let response = get(&app, "/").await;
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+    }
+
+    #[tokio::test]
+    async fn unknown_pages_fall_back_to_index_html() {
+        let (app, _dir) = setup();
+        let response = get(&app, "/roster/42").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&to_bytes(response.into_body(), usize::MAX).await.unwrap()[..], b"<html>roster</html>");
+
+        assert_eq!(get(&app, "/missing.js").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&app, "/v2/unknown").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&app, "/students").await.headers()[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn cors_allows_only_configured_origins() {
+        let (app, _dir) = setup();
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/students")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let allowed = app.clone().oneshot(preflight("http://localhost:8080")).await.unwrap();
+        assert_eq!(allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "http://localhost:8080");
+
+        let denied = app.clone().oneshot(preflight("http://evil.example.com")).await.unwrap();
+        assert!(denied.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+}
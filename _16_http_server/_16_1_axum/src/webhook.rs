@@ -0,0 +1,662 @@
+use std::{fs, io, path::PathBuf, sync::{Arc, Mutex}, time::Duration};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::{sync::{broadcast, Notify}, task::JoinHandle, time::Instant};
+use uuid::Uuid;
+use crate::{admin::{self, AdminKey}, state::{ChangeEvent, ChangeKind}, tenant::TenantEvent};
+
+
+/// header with the HMAC-SHA256 of `<timestamp>.<body>`, or `<timestamp>.<tenant>.<body>` for the
+/// changes of a tenant, hex encoded and prefixed with `sha256=`
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+/// header with the unix timestamp which was signed, receivers should reject old timestamps
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+/// header with the tenant whose student changed, not sent for the default store
+pub const TENANT_HEADER: &str = "x-webhook-tenant";
+
+
+/// a registered receiver of student changes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    // the tenant whose changes are sent, `None` for the default store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    // the kinds of changes to send, all kinds when empty
+    #[serde(default)]
+    pub events: Vec<ChangeKind>,
+    // shared secret used to sign the payloads
+    pub secret: String,
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+fn active() -> bool {
+    true
+}
+
+/// body of `POST /webhooks` and `PUT /webhooks/{id}`, a secret is generated when none is given.
+/// Without a tenant the webhook gets the changes of the default store, the one /webhooks belongs to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WebhookInput {
+    pub url: String,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub events: Vec<ChangeKind>,
+    pub secret: Option<String>,
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+/// one payload on its way to one webhook
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    // the tenant whose student changed, `None` for the default store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    // the json sent, kept as text so a redelivery sends exactly the same bytes
+    pub body: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip, default = "Instant::now")]
+    next_attempt: Instant,
+}
+
+impl Delivery {
+    fn is_about(&self, student_id: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(&self.body).is_ok_and(|body| body["data"]["id"] == student_id)
+    }
+}
+
+/// the json body sent to the webhooks
+#[derive(Serialize)]
+struct Payload<'a> {
+    delivery_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<&'a str>,
+    // e.g. `student.created`
+    event: String,
+    occurred_at: DateTime<Utc>,
+    data: &'a ChangeEvent,
+}
+
+
+/// how often and how fast failed deliveries are retried
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    // attempts before a delivery goes to the dead letters
+    pub max_attempts: u32,
+    // wait after the first failure, doubled after every further failure
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // how long to wait for a webhook to answer
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 6,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(300),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// wait before the next attempt after `attempts` failed ones
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+
+/// what is stored in the webhooks file
+#[derive(Serialize, Deserialize, Default)]
+struct Stored {
+    webhooks: Vec<Webhook>,
+    dead_letters: Vec<Delivery>,
+}
+
+
+/// the registered webhooks and the deliveries waiting to be sent
+pub struct Webhooks {
+    file: PathBuf,
+    policy: RetryPolicy,
+    stored: Mutex<Stored>,
+    queue: Mutex<Vec<Delivery>>,
+    // wakes the worker when something was added to the queue
+    queued: Notify,
+    client: reqwest::Client,
+}
+
+impl Webhooks {
+    /// load the webhooks and dead letters from `file`, pending deliveries are not kept over restarts
+    pub fn load(file: impl Into<PathBuf>, policy: RetryPolicy) -> io::Result<Self> {
+        let file = file.into();
+        let stored = match fs::read_to_string(&file) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Stored::default(),
+            Err(e) => return Err(e),
+        };
+        let client = reqwest::Client::builder().timeout(policy.timeout).build().map_err(io::Error::other)?;
+        Ok(Webhooks { file, policy, stored: Mutex::new(stored), queue: Mutex::new(vec![]), queued: Notify::new(), client })
+    }
+
+    fn save(&self, stored: &Stored) -> Result<(), StatusCode> {
+        let data = serde_json::to_string_pretty(stored).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        fs::write(&self.file, data).map_err(|e| {
+            eprintln!("could not save {}: {}", self.file.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        self.stored.lock().unwrap().webhooks.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Webhook> {
+        self.stored.lock().unwrap().webhooks.iter().find(|w| w.id == id).cloned()
+    }
+
+    pub fn create(&self, input: WebhookInput) -> Result<Webhook, StatusCode> {
+        let webhook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            url: valid_url(input.url)?,
+            tenant: input.tenant,
+            events: input.events,
+            secret: input.secret.unwrap_or_else(|| Uuid::new_v4().simple().to_string()),
+            active: input.active,
+        };
+        let mut stored = self.stored.lock().unwrap();
+        stored.webhooks.push(webhook.clone());
+        self.save(&stored)?;
+        Ok(webhook)
+    }
+
+    /// replace url, tenant, events and active flag, the secret only when a new one is given
+    pub fn update(&self, id: &str, input: WebhookInput) -> Result<Webhook, StatusCode> {
+        let url = valid_url(input.url)?;
+        let mut stored = self.stored.lock().unwrap();
+        let webhook = stored.webhooks.iter_mut().find(|w| w.id == id).ok_or(StatusCode::NOT_FOUND)?;
+        webhook.url = url;
+        webhook.tenant = input.tenant;
+        webhook.events = input.events;
+        webhook.active = input.active;
+        if let Some(secret) = input.secret {
+            webhook.secret = secret;
+        }
+        let webhook = webhook.clone();
+        self.save(&stored)?;
+        Ok(webhook)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), StatusCode> {
+        let mut stored = self.stored.lock().unwrap();
+        let count = stored.webhooks.len();
+        stored.webhooks.retain(|w| w.id != id);
+        if stored.webhooks.len() == count {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        self.save(&stored)?;
+        self.queue.lock().unwrap().retain(|d| d.webhook_id != id);
+        Ok(())
+    }
+
+    /// swap all webhooks for `webhooks`, used when a backup is restored
+    pub fn replace(&self, webhooks: Vec<Webhook>) -> Result<(), StatusCode> {
+        let mut stored = self.stored.lock().unwrap();
+        stored.webhooks = webhooks;
+        self.save(&stored)?;
+        let ids: Vec<String> = stored.webhooks.iter().map(|w| w.id.clone()).collect();
+        self.queue.lock().unwrap().retain(|d| ids.contains(&d.webhook_id));
+        Ok(())
+    }
+
+    /// the dead letters and queued deliveries about one student
+    pub fn deliveries_for(&self, student_id: &str) -> Vec<Delivery> {
+        let stored = self.stored.lock().unwrap();
+        let queue = self.queue.lock().unwrap();
+        stored.dead_letters.iter().chain(queue.iter()).filter(|d| d.is_about(student_id)).cloned().collect()
+    }
+
+    /// drop every dead letter and queued delivery about a student, their payloads hold the student's data.
+    /// Returns how many were dropped.
+    pub fn forget_student(&self, student_id: &str) -> Result<usize, StatusCode> {
+        let mut stored = self.stored.lock().unwrap();
+        let before = stored.dead_letters.len();
+        stored.dead_letters.retain(|d| !d.is_about(student_id));
+        let mut dropped = before - stored.dead_letters.len();
+        if dropped > 0 {
+            self.save(&stored)?;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        let before = queue.len();
+        queue.retain(|d| !d.is_about(student_id));
+        dropped += before - queue.len();
+        Ok(dropped)
+    }
+
+    pub fn dead_letters(&self) -> Vec<Delivery> {
+        self.stored.lock().unwrap().dead_letters.clone()
+    }
+
+    /// move a dead letter back into the queue with a fresh number of attempts
+    pub fn redeliver(&self, delivery_id: &str) -> Result<(), StatusCode> {
+        let mut stored = self.stored.lock().unwrap();
+        let index = stored.dead_letters.iter().position(|d| d.id == delivery_id).ok_or(StatusCode::NOT_FOUND)?;
+        if !stored.webhooks.iter().any(|w| w.id == stored.dead_letters[index].webhook_id) {
+            // the webhook was deleted in the meantime
+            return Err(StatusCode::GONE);
+        }
+        let mut delivery = stored.dead_letters.remove(index);
+        self.save(&stored)?;
+        delivery.attempts = 0;
+        delivery.next_attempt = Instant::now();
+        self.queue.lock().unwrap().push(delivery);
+        self.queued.notify_one();
+        Ok(())
+    }
+
+    /// queue a delivery of `event` in the store of `tenant`, or the default store, for every active
+    /// webhook of that store interested in it
+    pub fn enqueue(&self, tenant: Option<&str>, event: &ChangeEvent) {
+        let webhooks: Vec<Webhook> = self
+            .list()
+            .into_iter()
+            .filter(|w| w.active && w.tenant.as_deref() == tenant && (w.events.is_empty() || w.events.contains(&event.kind)))
+            .collect();
+        if webhooks.is_empty() {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        for webhook in webhooks {
+            let id = Uuid::new_v4().to_string();
+            let kind = serde_json::to_value(event.kind).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+            let payload = Payload { delivery_id: &id, tenant, event: format!("student.{}", kind), occurred_at: Utc::now(), data: event };
+            let Ok(body) = serde_json::to_string(&payload) else { continue };
+            queue.push(Delivery {
+                id,
+                webhook_id: webhook.id,
+                tenant: tenant.map(str::to_string),
+                body,
+                attempts: 0,
+                last_error: None,
+                created_at: Utc::now(),
+                next_attempt: Instant::now(),
+            });
+        }
+        self.queued.notify_one();
+    }
+
+    /// take the deliveries which are due, and the time the next one will be due
+    fn take_due(&self) -> (Vec<Delivery>, Option<Instant>) {
+        let now = Instant::now();
+        let mut queue = self.queue.lock().unwrap();
+        let (due, later): (Vec<_>, Vec<_>) = queue.drain(..).partition(|d| d.next_attempt <= now);
+        *queue = later;
+        (due, queue.iter().map(|d| d.next_attempt).min())
+    }
+
+    /// send one delivery, on failure it is queued again or becomes a dead letter
+    async fn attempt(self: Arc<Self>, mut delivery: Delivery) {
+        let Some(webhook) = self.get(&delivery.webhook_id) else { return };
+        delivery.attempts += 1;
+        let result = self.send(&webhook, &delivery).await;
+
+        let Err(error) = result else { return };
+        delivery.last_error = Some(error);
+        if delivery.attempts >= self.policy.max_attempts {
+            eprintln!("giving up on delivery {} to {} after {} attempts", delivery.id, webhook.url, delivery.attempts);
+            let mut stored = self.stored.lock().unwrap();
+            stored.dead_letters.push(delivery);
+            let _ = self.save(&stored);
+        } else {
+            delivery.next_attempt = Instant::now() + self.policy.backoff(delivery.attempts);
+            self.queue.lock().unwrap().push(delivery);
+            self.queued.notify_one();
+        }
+    }
+
+    async fn send(&self, webhook: &Webhook, delivery: &Delivery) -> Result<(), String> {
+        let timestamp = Utc::now().timestamp().to_string();
+        let tenant = delivery.tenant.as_deref();
+        let mut request = self
+            .client
+            .post(&webhook.url)
+            .header("content-type", "application/json")
+            .header(DELIVERY_HEADER, &delivery.id)
+            .header(TIMESTAMP_HEADER, &timestamp)
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, &timestamp, tenant, &delivery.body));
+        if let Some(tenant) = tenant {
+            request = request.header(TENANT_HEADER, tenant);
+        }
+        let response = request.body(delivery.body.clone()).send().await.map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("webhook answered {}", response.status()))
+        }
+    }
+}
+
+fn valid_url(url: String) -> Result<String, StatusCode> {
+    match reqwest::Url::parse(&url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(url),
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+
+/// the signature header value for a payload, receivers compute the same and compare.
+/// The tenant is signed too, so a delivery can not be passed off as one of another tenant.
+pub fn sign(secret: &str, timestamp: &str, tenant: Option<&str>, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    if let Some(tenant) = tenant {
+        mac.update(tenant.as_bytes());
+        mac.update(b".");
+    }
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+
+/// queue deliveries for every change in `events` and send them in the background
+pub fn spawn(webhooks: Arc<Webhooks>, mut events: broadcast::Receiver<ChangeEvent>) -> JoinHandle<()> {
+    let listener = webhooks.clone();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => listener.enqueue(None, &event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("webhooks missed {} student changes", missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            let (due, next) = webhooks.take_due();
+            for delivery in due {
+                tokio::spawn(webhooks.clone().attempt(delivery));
+            }
+            let queued = webhooks.queued.notified();
+            match next {
+                Some(next) => {
+                    tokio::select! {
+                        _ = queued => {}
+                        _ = tokio::time::sleep_until(next) => {}
+                    }
+                }
+                None => queued.await,
+            }
+        }
+    })
+}
+
+
+/// queue deliveries for the changes of every tenant, `spawn` sends them
+pub fn follow_tenants(webhooks: Arc<Webhooks>, mut events: broadcast::Receiver<TenantEvent>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(TenantEvent { tenant, event }) => webhooks.enqueue(Some(&tenant), &event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("webhooks missed {} student changes of tenants", missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+
+/// the /webhooks routes, protected by the admin key
+pub fn routes(webhooks: Arc<Webhooks>, admin_key: AdminKey) -> Router {
+    Router::new()
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/dead-letters", get(list_dead_letters))
+        .route("/webhooks/dead-letters/{id}/redeliver", post(redeliver))
+        .route("/webhooks/{id}", get(get_webhook).put(update_webhook).delete(delete_webhook))
+        .route_layer(from_fn_with_state(admin_key, admin::require_key))
+        .with_state(webhooks)
+}
+
+
+/// curl -X GET http://127.0.0.1:4500/webhooks
+async fn list_webhooks(State(webhooks): State<Arc<Webhooks>>) -> Json<Vec<Webhook>> {
+    Json(webhooks.list())
+}
+
+/// curl -X POST http://127.0.0.1:4500/webhooks -H "Content-Type: application/json" -d "{ \"url\": \"http://127.0.0.1:9000/hooks\", \"events\": [\"created\", \"deleted\"] }"
+/// for a tenant - curl -X POST http://127.0.0.1:4500/webhooks -H "Content-Type: application/json" -d "{ \"url\": \"http://127.0.0.1:9000/hooks\", \"tenant\": \"north-school\" }"
+async fn create_webhook(State(webhooks): State<Arc<Webhooks>>, Json(input): Json<WebhookInput>) -> Result<(StatusCode, Json<Webhook>), StatusCode> {
+    webhooks.create(input).map(|webhook| (StatusCode::CREATED, Json(webhook)))
+}
+
+async fn get_webhook(State(webhooks): State<Arc<Webhooks>>, Path(id): Path<String>) -> Result<Json<Webhook>, StatusCode> {
+    webhooks.get(&id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn update_webhook(State(webhooks): State<Arc<Webhooks>>, Path(id): Path<String>, Json(input): Json<WebhookInput>) -> Result<Json<Webhook>, StatusCode> {
+    webhooks.update(&id, input).map(Json)
+}
+
+async fn delete_webhook(State(webhooks): State<Arc<Webhooks>>, Path(id): Path<String>) -> StatusCode {
+    webhooks.delete(&id).map_or_else(|status| status, |_| StatusCode::NO_CONTENT)
+}
+
+/// curl -X GET http://127.0.0.1:4500/webhooks/dead-letters
+async fn list_dead_letters(State(webhooks): State<Arc<Webhooks>>) -> Json<Vec<Delivery>> {
+    Json(webhooks.dead_letters())
+}
+
+/// curl -X POST http://127.0.0.1:4500/webhooks/dead-letters/{id}/redeliver
+async fn redeliver(State(webhooks): State<Arc<Webhooks>>, Path(id): Path<String>) -> StatusCode {
+    webhooks.redeliver(&id).map_or_else(|status| status, |_| StatusCode::ACCEPTED)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app, state::AppState};
+    use axum::{body::{to_bytes, Body}, http::{HeaderMap, Request}};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    /// in-process stand-in for a webhook target, fails the first `failures` requests
+    struct Receiver {
+        received: Mutex<Vec<(HeaderMap, String)>>,
+        failures: AtomicUsize,
+    }
+
+    async fn receiver(failures: usize) -> (Arc<Receiver>, String) {
+        let receiver = Arc::new(Receiver { received: Mutex::new(vec![]), failures: AtomicUsize::new(failures) });
+        let router = Router::new()
+            .route("/hook", post(|State(r): State<Arc<Receiver>>, headers: HeaderMap, body: String| async move {
+                if r.failures.load(Ordering::SeqCst) > 0 {
+                    r.failures.fetch_sub(1, Ordering::SeqCst);
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+                r.received.lock().unwrap().push((headers, body));
+                StatusCode::NO_CONTENT
+            }))
+            .with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (receiver, url)
+    }
+
+    struct Setup {
+        app: Router,
+        webhooks: Arc<Webhooks>,
+        dir: tempfile::TempDir,
+    }
+
+    fn setup(max_attempts: u32) -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+        let policy = RetryPolicy { max_attempts, base_delay: Duration::from_millis(10), max_delay: Duration::from_millis(40), timeout: Duration::from_secs(2) };
+        let webhooks = Arc::new(Webhooks::load(dir.path().join("webhooks.json"), policy).unwrap());
+        spawn(webhooks.clone(), state.events.subscribe());
+        let app = app(state).merge(routes(webhooks.clone(), None));
+        Setup { app, webhooks, dir }
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json").body(body).unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        let status = response.status();
+        let data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&data).unwrap_or(Value::Null))
+    }
+
+    async fn add_student(app: &Router) {
+        let student = json!({ "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" });
+        assert_eq!(send(app, "POST", "/students", Some(student)).await.0, StatusCode::CREATED);
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy { max_attempts: 10, base_delay: Duration::from_secs(1), max_delay: Duration::from_secs(5), timeout: Duration::from_secs(1) };
+        let delays: Vec<u64> = (1..=5).map(|attempt| policy.backoff(attempt).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_signed_and_filtered() {
+        let s = setup(3);
+        let (receiver, url) = receiver(0).await;
+        let (status, _) = send(&s.app, "POST", "/webhooks", Some(json!({ "url": url, "events": ["created"], "secret": "s3cret" }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        add_student(&s.app).await;
+        wait_for(|| receiver.received.lock().unwrap().len() == 1).await;
+
+        let (headers, body) = receiver.received.lock().unwrap()[0].clone();
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), sign("s3cret", timestamp, None, &body));
+        assert!(headers.get(TENANT_HEADER).is_none());
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["event"], "student.created");
+        assert_eq!(payload["data"]["student"]["name"], "Aman");
+
+        // deletions are not subscribed
+        let id = payload["data"]["id"].as_str().unwrap().to_string();
+        assert_eq!(send(&s.app, "DELETE", &format!("/students/{}", id), None).await.0, StatusCode::OK);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(receiver.received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried() {
+        let s = setup(5);
+        let (receiver, url) = receiver(2).await;
+        send(&s.app, "POST", "/webhooks", Some(json!({ "url": url }))).await;
+
+        add_student(&s.app).await;
+        wait_for(|| receiver.received.lock().unwrap().len() == 1).await;
+        assert!(s.webhooks.dead_letters().is_empty());
+    }
+
+    #[tokio::test]
+    async fn exhausted_deliveries_become_dead_letters_and_can_be_redelivered() {
+        let s = setup(2);
+        let (receiver, url) = receiver(2).await;
+        send(&s.app, "POST", "/webhooks", Some(json!({ "url": url }))).await;
+
+        add_student(&s.app).await;
+        wait_for(|| s.webhooks.dead_letters().len() == 1).await;
+        assert!(receiver.received.lock().unwrap().is_empty());
+
+        let (_, dead_letters) = send(&s.app, "GET", "/webhooks/dead-letters", None).await;
+        assert_eq!(dead_letters[0]["attempts"], 2);
+        let id = dead_letters[0]["id"].as_str().unwrap();
+        let redeliver = format!("/webhooks/dead-letters/{}/redeliver", id);
+        assert_eq!(send(&s.app, "POST", &redeliver, None).await.0, StatusCode::ACCEPTED);
+
+        wait_for(|| receiver.received.lock().unwrap().len() == 1).await;
+        assert!(s.webhooks.dead_letters().is_empty());
+        assert_eq!(receiver.received.lock().unwrap()[0].0[DELIVERY_HEADER], id);
+    }
+
+    #[tokio::test]
+    async fn changes_of_tenants_created_later_are_delivered_with_the_tenant() {
+        let s = setup(3);
+        let tenants = Arc::new(crate::tenant::Tenants::load(s.dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+        follow_tenants(s.webhooks.clone(), tenants.events());
+        let (receiver, url) = receiver(0).await;
+        send(&s.app, "POST", "/webhooks", Some(json!({ "url": url, "tenant": "north", "secret": "s3cret" }))).await;
+
+        tenants.create(crate::tenant::NewTenant { name: "north".to_string(), max_students: None, api_keys: None }).unwrap();
+        add_student(&app(tenants.store("north").unwrap())).await;
+        wait_for(|| receiver.received.lock().unwrap().len() == 1).await;
+
+        let (headers, body) = receiver.received.lock().unwrap()[0].clone();
+        assert_eq!(headers[TENANT_HEADER], "north");
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), sign("s3cret", timestamp, Some("north"), &body));
+        assert_ne!(sign("s3cret", timestamp, Some("north"), &body), sign("s3cret", timestamp, None, &body));
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((&payload["tenant"], &payload["event"]), (&json!("north"), &json!("student.created")));
+    }
+
+    #[tokio::test]
+    async fn webhooks_only_get_the_changes_of_their_store() {
+        let s = setup(3);
+        let tenants = Arc::new(crate::tenant::Tenants::load(s.dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+        follow_tenants(s.webhooks.clone(), tenants.events());
+        let (default, default_url) = receiver(0).await;
+        let (north, north_url) = receiver(0).await;
+        send(&s.app, "POST", "/webhooks", Some(json!({ "url": default_url }))).await;
+        send(&s.app, "POST", "/webhooks", Some(json!({ "url": north_url, "tenant": "north" }))).await;
+        for name in ["north", "south"] {
+            tenants.create(crate::tenant::NewTenant { name: name.to_string(), max_students: None, api_keys: None }).unwrap();
+        }
+
+        add_student(&app(tenants.store("south").unwrap())).await;
+        add_student(&app(tenants.store("north").unwrap())).await;
+        add_student(&s.app).await;
+        wait_for(|| north.received.lock().unwrap().len() == 1 && default.received.lock().unwrap().len() == 1).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let north = north.received.lock().unwrap();
+        assert_eq!(north.len(), 1);
+        assert_eq!(north[0].0[TENANT_HEADER], "north");
+        let default = default.received.lock().unwrap();
+        assert_eq!(default.len(), 1);
+        assert!(default[0].0.get(TENANT_HEADER).is_none());
+    }
+}
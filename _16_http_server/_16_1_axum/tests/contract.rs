@@ -0,0 +1,179 @@
+// Contract tests: pin the json shape of every api version, a failing test here means clients break.
+
+use std::sync::Arc;
+use axum::{body::{to_bytes, Body}, http::{Request, StatusCode}, response::Response, Router};
+use serde_json::{json, Value};
+use studet_api::{app, state::AppState};
+use tokio_stream::StreamExt;
+use tower::ServiceExt;
+
+
+/// a store with one student which has everything v2 knows about
+fn setup() -> (Router, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("students.json");
+    let stored = json!([{
+        "id": "9d73e21e-672e-4184-a682-d3bf339e3664",
+        "name": "Ellis Tarmaster",
+        "email": "ellis@example.com",
+        "mobile": "1234567890",
+        "name_parts": { "given": "Ellis", "family": "Tarmaster" },
+        "contacts": [{ "kind": "phone", "value": "0201234567" }],
+        "created_at": "2025-03-01T10:00:00Z",
+        "updated_at": "2025-04-01T12:30:00Z"
+    }]);
+    std::fs::write(&file, stored.to_string()).unwrap();
+    (app(Arc::new(AppState::new(&file).unwrap())), dir)
+}
+
+async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> Response {
+    let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+    let req = Request::builder().method(method).uri(uri).header("content-type", "application/json").body(body).unwrap();
+    app.clone().oneshot(req).await.unwrap()
+}
+
+async fn json_body(response: Response) -> Value {
+    serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
+
+
+#[tokio::test]
+async fn v1_shape_is_unchanged() {
+    let (app, _dir) = setup();
+    let expected = json!({
+        "id": "9d73e21e-672e-4184-a682-d3bf339e3664",
+        "name": "Ellis Tarmaster",
+        "email": "ellis@example.com",
+        "mobile": "1234567890"
+    });
+
+    for uri in ["/students", "/v1/students"] {
+        assert_eq!(json_body(send(&app, "GET", uri, None).await).await, json!([expected]));
+    }
+    let one = send(&app, "GET", "/v1/students/9d73e21e-672e-4184-a682-d3bf339e3664", None).await;
+    assert_eq!(json_body(one).await, expected);
+}
+
+#[tokio::test]
+async fn v2_shape() {
+    let (app, _dir) = setup();
+    let response = send(&app, "GET", "/v2/students/9d73e21e-672e-4184-a682-d3bf339e3664", None).await;
+
+    assert_eq!(json_body(response).await, json!({
+        "id": "9d73e21e-672e-4184-a682-d3bf339e3664",
+        "name": { "given": "Ellis", "family": "Tarmaster", "display": "Ellis Tarmaster" },
+        "contacts": [
+            { "kind": "email", "value": "ellis@example.com", "primary": true },
+            { "kind": "mobile", "value": "1234567890", "primary": true },
+            { "kind": "phone", "value": "0201234567", "primary": false }
+        ],
+        "created_at": "2025-03-01T10:00:00Z",
+        "updated_at": "2025-04-01T12:30:00Z"
+    }));
+}
+
+#[tokio::test]
+async fn v1_is_deprecated_and_v2_is_not() {
+    let (app, _dir) = setup();
+
+    for uri in ["/students", "/v1/students"] {
+        let response = send(&app, "GET", uri, None).await;
+        assert_eq!(response.headers()["deprecation"], "@1792368000");
+        assert_eq!(response.headers()["sunset"], "Fri, 30 Apr 2027 00:00:00 GMT");
+        assert_eq!(response.headers()["link"], "</v2/students>; rel=\"successor-version\"");
+    }
+    let response = send(&app, "GET", "/v2/students", None).await;
+    assert!(response.headers().get("deprecation").is_none());
+}
+
+#[tokio::test]
+async fn versions_share_one_store() {
+    let (app, _dir) = setup();
+    let created = send(&app, "POST", "/v2/students", Some(json!({
+        "name": { "given": "Aman", "family": "Verasia" },
+        "contacts": [
+            { "kind": "mobile", "value": "9876543210" },
+            { "kind": "email", "value": "aman@school.example.com" },
+            { "kind": "email", "value": "aman@example.com", "primary": true }
+        ]
+    }))).await;
+    assert_eq!(created.status(), StatusCode::CREATED);
+    let location = created.headers()["location"].to_str().unwrap().to_string();
+    let id = location.trim_start_matches("/v2/students/").to_string();
+
+    let v1 = json_body(send(&app, "GET", &format!("/v1/students/{}", id), None).await).await;
+    assert_eq!(v1, json!({ "id": id, "name": "Aman Verasia", "email": "aman@example.com", "mobile": "9876543210" }));
+
+    // an update through v1 keeps the contacts only v2 knows about
+    let updated = send(&app, "PUT", &format!("/v1/students/{}", id), Some(json!({ "name": "Aman Verasia", "email": "aman@example.com", "mobile": "1111111111" }))).await;
+    assert_eq!(updated.status(), StatusCode::OK);
+    let v2 = json_body(send(&app, "GET", &location, None).await).await;
+    assert_eq!(v2["contacts"][1], json!({ "kind": "mobile", "value": "1111111111", "primary": true }));
+    assert_eq!(v2["contacts"][2], json!({ "kind": "email", "value": "aman@school.example.com", "primary": false }));
+    assert_eq!(v2["name"]["family"], "Verasia");
+    assert!(v2["created_at"].is_string() && v2["updated_at"].is_string());
+}
+
+#[tokio::test]
+async fn v2_requires_an_email_and_a_mobile() {
+    let (app, _dir) = setup();
+    let response = send(&app, "POST", "/v2/students", Some(json!({
+        "name": { "given": "Aman" },
+        "contacts": [{ "kind": "email", "value": "aman@example.com" }]
+    }))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn v1_takes_what_the_first_version_took() {
+    let (app, _dir) = setup();
+    let loose = json!({ "name": "", "email": "aman at example", "mobile": "+91 98765 43210" });
+    assert_eq!(send(&app, "POST", "/v1/students", Some(loose.clone())).await.status(), StatusCode::CREATED);
+    let url = "/v1/students/9d73e21e-672e-4184-a682-d3bf339e3664";
+    assert_eq!(send(&app, "PUT", url, Some(json!({ "name": "Ellis", "email": "ellis", "mobile": "n/a" }))).await.status(), StatusCode::OK);
+
+    // v2 checks the fields
+    let response = send(&app, "POST", "/v2/students", Some(json!({
+        "name": { "given": "Aman" },
+        "contacts": [{ "kind": "email", "value": "aman at example" }, { "kind": "mobile", "value": "9876543210" }]
+    }))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn a_data_file_of_the_first_version_loads() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("students.json");
+    let stored = json!([
+        { "name": "Aman", "email": "aman", "mobile": "+91 98765 43210" },
+        { "id": "1", "name": "", "email": "bela@example.com", "mobile": "" },
+        { "id": "1", "name": "Chen", "email": "chen@example.com", "mobile": "123" }
+    ]);
+    std::fs::write(&file, stored.to_string()).unwrap();
+    let app = app(Arc::new(AppState::new(&file).unwrap()));
+
+    // every student has a unique id now, kept in the file
+    let students = json_body(send(&app, "GET", "/v1/students", None).await).await;
+    let ids: Vec<&str> = students.as_array().unwrap().iter().map(|s| s["id"].as_str().unwrap()).collect();
+    assert_eq!((ids.len(), ids[1]), (3, "1"));
+    assert!(!ids[0].is_empty() && ids[2] != "1" && ids[0] != ids[2]);
+    let written: Value = serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+    assert_eq!(written[0]["id"], ids[0]);
+    assert_eq!(send(&app, "GET", &format!("/v1/students/{}", ids[2]), None).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn v2_change_events() {
+    let (app, _dir) = setup();
+    let response = send(&app, "GET", "/v2/students/events", None).await;
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body().into_data_stream();
+
+    let id = "9d73e21e-672e-4184-a682-d3bf339e3664";
+    send(&app, "DELETE", &format!("/v2/students/{}", id), None).await;
+    let frame = body.next().await.unwrap().unwrap();
+    assert_eq!(
+        String::from_utf8(frame.to_vec()).unwrap(),
+        format!("event: change\ndata: {}\n\n", json!({ "kind": "deleted", "id": id, "student": null }))
+    );
+}
@@ -0,0 +1,200 @@
+// Student photos: uploads, thumbnails, caching and what happens to the files afterwards.
+
+use std::{fs, sync::{Arc, RwLock}};
+use axum::{body::{to_bytes, Body}, http::{header, Request, StatusCode}, response::Response, Router};
+use image::{DynamicImage, Rgb, RgbImage};
+use imaging::Format;
+use serde_json::{json, Value};
+use studet_api::{app, crypto::Keys, photo, state::AppState, SharedState};
+use tower::ServiceExt;
+
+
+const ID: &str = "9d73e21e-672e-4184-a682-d3bf339e3664";
+
+fn setup() -> (Router, SharedState, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("students.json");
+    let stored = json!([
+        { "id": ID, "name": "Ellis Tarmaster", "email": "ellis@example.com", "mobile": "1234567890" },
+        { "id": "other", "name": "Aman", "email": "aman@example.com", "mobile": "9876543210" }
+    ]);
+    fs::write(&file, stored.to_string()).unwrap();
+    let state = Arc::new(AppState::new(&file).unwrap());
+    (app(state.clone()), state, dir)
+}
+
+/// a gradient, so the thumbnails of different sizes differ too
+fn picture(width: u32, height: u32, format: Format) -> Vec<u8> {
+    let image = RgbImage::from_fn(width, height, |x, y| Rgb([(x * 255 / width) as u8, (y * 255 / height) as u8, 128]));
+    imaging::encode(&DynamicImage::ImageRgb8(image), format, 80).unwrap()
+}
+
+/// a JPEG with an EXIF segment holding a camera serial number and a position
+fn with_exif(jpeg: &[u8]) -> Vec<u8> {
+    let payload = b"Exif\0\0SERIAL-4711 GPS 52.5200N 13.4050E";
+    let mut with = jpeg[..2].to_vec();
+    with.extend([0xFF, 0xE1]);
+    with.extend(((payload.len() + 2) as u16).to_be_bytes());
+    with.extend(payload);
+    with.extend(&jpeg[2..]);
+    with
+}
+
+async fn put(app: &Router, uri: &str, content_type: &str, body: Vec<u8>) -> Response {
+    let req = Request::builder().method("PUT").uri(uri).header("content-type", content_type).body(Body::from(body)).unwrap();
+    app.clone().oneshot(req).await.unwrap()
+}
+
+async fn get(app: &Router, uri: &str, etag: Option<&str>) -> Response {
+    let mut req = Request::builder().uri(uri);
+    if let Some(etag) = etag {
+        req = req.header("if-none-match", etag);
+    }
+    app.clone().oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+async fn bytes(response: Response) -> Vec<u8> {
+    to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+fn stored_files(state: &SharedState) -> usize {
+    fs::read_dir(state.photos_dir()).map(|entries| entries.count()).unwrap_or(0)
+}
+
+
+#[tokio::test]
+async fn upload_and_thumbnails() {
+    let (app, state, _dir) = setup();
+    let upload = with_exif(&picture(400, 300, Format::Jpeg));
+    let response = put(&app, &format!("/students/{}/photo", ID), "image/jpeg", upload).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let photo: Value = serde_json::from_slice(&bytes(response).await).unwrap();
+    assert_eq!(stored_files(&state), 3);
+
+    for (size, width, height) in [("", 400, 300), ("?size=original", 400, 300), ("?size=medium", 256, 256), ("?size=small", 64, 64)] {
+        let response = get(&app, &format!("/students/{}/photo{}", ID, size), None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+        let body = bytes(response).await;
+        assert_eq!(imaging::probe(&body).unwrap(), (Format::Jpeg, width, height));
+        assert!(!contains(&body, b"Exif") && !contains(&body, b"SERIAL-4711"), "metadata kept for size {:?}", size);
+    }
+
+    // the etag is the name of the file, which is the hash of its content
+    let response = get(&app, &format!("/v2/students/{}/photo?size=small", ID), None).await;
+    let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", photo["small"].as_str().unwrap()));
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "private, no-cache");
+    assert_eq!(get(&app, &format!("/v1/students/{}/photo?size=small", ID), None).await.headers()[header::ETAG], etag.as_str());
+    let cached = get(&app, &format!("/students/{}/photo?size=small", ID), Some(&etag)).await;
+    assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(cached.headers()[header::ETAG], etag.as_str());
+    let other = get(&app, &format!("/students/{}/photo?size=medium", ID), Some(&etag)).await;
+    assert_eq!(other.status(), StatusCode::OK);
+
+    // the photo stays out of the student json of every version
+    for uri in [format!("/students/{}", ID), format!("/v2/students/{}", ID)] {
+        let student: Value = serde_json::from_slice(&bytes(get(&app, &uri, None).await).await).unwrap();
+        assert!(student.get("photo").is_none(), "{}", student);
+    }
+}
+
+#[tokio::test]
+async fn multipart_upload() {
+    let (app, _state, _dir) = setup();
+    let mut body = b"--boundary\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"me.png\"\r\nContent-Type: image/png\r\n\r\n".to_vec();
+    body.extend(picture(100, 200, Format::Png));
+    body.extend(b"\r\n--boundary--\r\n");
+    let response = put(&app, &format!("/students/{}/photo", ID), "multipart/form-data; boundary=boundary", body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = get(&app, &format!("/students/{}/photo", ID), None).await;
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+    assert_eq!(imaging::probe(&bytes(response).await).unwrap(), (Format::Png, 100, 200));
+
+    let broken = put(&app, &format!("/students/{}/photo", ID), "multipart/form-data; boundary=boundary", b"--boundary\r\n".to_vec()).await;
+    assert_eq!(broken.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn bad_uploads() {
+    let (app, state, _dir) = setup();
+    let uri = format!("/students/{}/photo", ID);
+    let gif = put(&app, &uri, "image/gif", b"GIF89a\x01\x00\x01\x00".to_vec()).await;
+    assert_eq!(gif.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let text = put(&app, &uri, "image/png", b"just text".to_vec()).await;
+    assert_eq!(text.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let tiny = put(&app, &uri, "image/png", picture(32, 200, Format::Png)).await;
+    assert_eq!(tiny.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(String::from_utf8(bytes(tiny).await).unwrap().contains("at least 64x64"));
+    let huge = put(&app, &uri, "image/png", vec![0; studet_api::photo::MAX_UPLOAD + 1]).await;
+    assert_eq!(huge.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let nobody = put(&app, "/students/nobody/photo", "image/png", picture(100, 100, Format::Png)).await;
+    assert_eq!(nobody.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get(&app, &uri, None).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(stored_files(&state), 0);
+
+    put(&app, &uri, "image/png", picture(100, 100, Format::Png)).await;
+    assert_eq!(get(&app, &format!("{}?size=huge", uri), None).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unused_files_are_removed() {
+    let (app, state, _dir) = setup();
+    let first = format!("/students/{}/photo", ID);
+    put(&app, &first, "image/png", picture(100, 100, Format::Png)).await;
+    // the same photo for somebody else is stored once
+    put(&app, "/students/other/photo", "image/png", picture(100, 100, Format::Png)).await;
+    assert_eq!(stored_files(&state), 3);
+
+    // a file still being written is left alone
+    fs::write(state.photos_dir().join("upload.tmp"), b"partial").unwrap();
+    put(&app, &first, "image/png", picture(200, 100, Format::Png)).await;
+    assert_eq!(stored_files(&state), 7);
+    fs::remove_file(state.photos_dir().join("upload.tmp")).unwrap();
+    assert_eq!(stored_files(&state), 6);
+    let req = Request::builder().method("DELETE").uri("/students/other").body(Body::empty()).unwrap();
+    assert_eq!(app.clone().oneshot(req).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(stored_files(&state), 3);
+
+    // keeps the photo while the rest of the student changes
+    let changed = json!({ "name": { "given": "Ellis" }, "contacts": [{ "kind": "email", "value": "ellis@example.com" }, { "kind": "mobile", "value": "1234567890" }] });
+    let req = Request::builder().method("PUT").uri(format!("/v2/students/{}", ID)).header("content-type", "application/json").body(Body::from(changed.to_string())).unwrap();
+    assert_eq!(app.clone().oneshot(req).await.unwrap().status(), StatusCode::OK);
+    let response = get(&app, &first, None).await;
+    assert_eq!(imaging::probe(&bytes(response).await).unwrap(), (Format::Png, 200, 100));
+}
+
+#[tokio::test]
+async fn files_are_encrypted_with_the_master_key() {
+    let (without_key, state, _dir) = setup();
+    let uri = format!("/students/{}/photo", ID);
+    put(&without_key, &uri, "image/png", picture(100, 100, Format::Png)).await;
+    let plain = bytes(get(&without_key, &uri, None).await).await;
+
+    // the same store with a master key, the files from before are encrypted in the background
+    let keys = Keys::single(&studet_api::crypto::generate_key()).unwrap();
+    let encrypted = Arc::new(AppState::open(&state.data_file, Some(Arc::new(RwLock::new(keys)))).unwrap());
+    let with_key = app(encrypted.clone());
+    assert_eq!(bytes(get(&with_key, &uri, None).await).await, plain);
+    assert_eq!(photo::reencrypt(&encrypted).await.unwrap(), 3);
+    assert_eq!(photo::reencrypt(&encrypted).await.unwrap(), 0);
+
+    put(&with_key, "/students/other/photo", "image/png", picture(120, 80, Format::Png)).await;
+    assert_eq!(stored_files(&encrypted), 6);
+    for entry in fs::read_dir(encrypted.photos_dir()).unwrap() {
+        let stored = fs::read(entry.unwrap().path()).unwrap();
+        assert!(stored.starts_with(b"enc:v1:") && imaging::probe(&stored).is_err());
+    }
+    assert_eq!(bytes(get(&with_key, &uri, None).await).await, plain);
+    let other = bytes(get(&with_key, "/students/other/photo", None).await).await;
+    assert_eq!(imaging::probe(&other).unwrap(), (Format::Png, 120, 80));
+
+    // without the key the photos can not be read
+    assert_eq!(get(&without_key, &uri, None).await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
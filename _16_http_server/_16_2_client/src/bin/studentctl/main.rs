@@ -0,0 +1,172 @@
+use std::{fs, io::{self, Read, Write}, path::{Path, PathBuf}, process::ExitCode, time::Duration};
+use clap::{CommandFactory, Parser, Subcommand};
+use studet_api::crypto::Keys;
+use studet_client::{Client, StudentV1};
+
+mod output;
+mod store;
+
+use output::{FileFormat, Output};
+use store::{Failure, Store};
+
+
+/// manage the students of the student api, over http or directly in the data file.
+///
+/// Exit codes: 0 done, 1 failed, 2 wrong usage, 3 not found, 4 api key missing or refused,
+/// 5 server unreachable or too slow.
+///
+/// studentctl list
+/// studentctl --server https://students.example.com --api-key {key} -o csv list
+/// studentctl --file students.json create --name Aman --email aman@example.com --mobile 9876543210
+/// studentctl completions bash > /etc/bash_completion.d/studentctl
+#[derive(Parser)]
+#[command(name = "studentctl", version, verbatim_doc_comment)]
+struct Cli {
+    /// url of a running server
+    #[arg(long, global = true, env = "STUDENTCTL_SERVER", default_value = "http://127.0.0.1:4500")]
+    server: String,
+    /// work on this data file directly instead of a server, MASTER_KEY or MASTER_KEY_FILE decrypt it
+    #[arg(long, global = true, env = "STUDENTCTL_FILE")]
+    file: Option<PathBuf>,
+    /// the admin key or the key of a tenant
+    #[arg(long, global = true, env = "STUDENTCTL_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    /// use the students of this tenant
+    #[arg(long, global = true, env = "STUDENTCTL_TENANT")]
+    tenant: Option<String>,
+    /// seconds to wait for the server per request
+    #[arg(long, global = true, default_value_t = 30)]
+    timeout: u64,
+    #[arg(short, long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// list all students
+    List,
+    /// show one student
+    Get { id: String },
+    /// add a student, prints it with its new id
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        mobile: String,
+    },
+    /// change the given fields of a student
+    Update {
+        id: String,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        email: Option<String>,
+        #[arg(long)]
+        mobile: Option<String>,
+    },
+    /// delete a student
+    Delete { id: String },
+    /// add all students of a json or csv file as new students, `-` reads stdin
+    Import {
+        path: PathBuf,
+        /// format of the file, guessed from the extension when not given
+        #[arg(long, value_enum)]
+        format: Option<FileFormat>,
+    },
+    /// write all students to a json or csv file, stdout without a path
+    Export {
+        path: Option<PathBuf>,
+        /// format of the file, guessed from the extension when not given, json unless `-o csv`
+        #[arg(long, value_enum)]
+        format: Option<FileFormat>,
+    },
+    /// print the completion script for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("studentctl: {}", failure.message);
+            ExitCode::from(failure.code)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Failure> {
+    if let Command::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "studentctl", &mut io::stdout());
+        return Ok(());
+    }
+    let store = open(&cli)?;
+    let mut stdout = io::stdout().lock();
+    match cli.command {
+        Command::List => output::write(&store.list().await?, cli.output, false, &mut stdout)?,
+        Command::Get { id } => output::write(&[store.get(&id).await?], cli.output, true, &mut stdout)?,
+        Command::Create { name, email, mobile } => {
+            let student = store.create(StudentV1 { id: String::new(), name, email, mobile }).await?;
+            output::write(&[student], cli.output, true, &mut stdout)?;
+        }
+        Command::Update { id, name, email, mobile } => {
+            let student = store.update(&id, name, email, mobile).await?;
+            output::write(&[student], cli.output, true, &mut stdout)?;
+        }
+        Command::Delete { id } => {
+            store.delete(&id).await?;
+            eprintln!("deleted {}", id);
+        }
+        Command::Import { path, format } => {
+            let format = format.or_else(|| FileFormat::from_path(&path)).unwrap_or(FileFormat::Json);
+            let students = format.parse(&read_input(&path)?)?;
+            let created = store.import(students).await?;
+            eprintln!("imported {} students", created);
+        }
+        Command::Export { path, format } => {
+            let format = format
+                .or_else(|| path.as_deref().and_then(FileFormat::from_path))
+                .unwrap_or(if matches!(cli.output, Output::Csv) { FileFormat::Csv } else { FileFormat::Json });
+            let data = format.render(&store.list().await?)?;
+            match path.filter(|path| path != Path::new("-")) {
+                Some(path) => fs::write(&path, data).map_err(|e| Failure::new(format!("could not write {}: {}", path.display(), e)))?,
+                None => stdout.write_all(&data)?,
+            }
+        }
+        Command::Completions { .. } => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+fn open(cli: &Cli) -> Result<Store, Failure> {
+    if let Some(file) = &cli.file {
+        let key_file = std::env::var_os("MASTER_KEY_FILE").map(PathBuf::from);
+        let key = std::env::var("MASTER_KEY").ok().filter(|key| !key.is_empty());
+        let keys = Keys::configured(key_file.as_deref(), key.as_deref()).map_err(|e| Failure::new(format!("invalid master key: {}", e)))?;
+        return Ok(Store::File { path: file.clone(), keys });
+    }
+    let mut builder = Client::builder(&cli.server).timeout(Duration::from_secs(cli.timeout));
+    if let Some(key) = &cli.api_key {
+        builder = builder.api_key(key);
+    }
+    if let Some(tenant) = &cli.tenant {
+        builder = builder.tenant(tenant);
+    }
+    Ok(Store::Server(builder.build()?))
+}
+
+fn read_input(path: &Path) -> Result<Vec<u8>, Failure> {
+    let mut data = vec![];
+    let read = if path == Path::new("-") { io::stdin().read_to_end(&mut data).map(|_| ()) } else { fs::read(path).map(|d| data = d) };
+    read.map_err(|e| Failure::new(format!("could not read {}: {}", path.display(), e)))?;
+    Ok(data)
+}
@@ -0,0 +1,94 @@
+use std::{io::{self, Write}, path::Path};
+use clap::ValueEnum;
+use studet_client::StudentV1;
+use crate::store::Failure;
+
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Output {
+    Table,
+    Json,
+    Csv,
+}
+
+/// write students in the chosen format, `single` prints json as one object instead of a list
+pub fn write(students: &[StudentV1], output: Output, single: bool, out: &mut impl Write) -> Result<(), Failure> {
+    match output {
+        Output::Table => table(students, out)?,
+        Output::Json if single => out.write_all(&FileFormat::Json.render_value(&students[0])?)?,
+        Output::Json => out.write_all(&FileFormat::Json.render(students)?)?,
+        Output::Csv => out.write_all(&FileFormat::Csv.render(students)?)?,
+    }
+    Ok(())
+}
+
+fn table(students: &[StudentV1], out: &mut impl Write) -> io::Result<()> {
+    let header = ["ID", "NAME", "EMAIL", "MOBILE"];
+    let rows: Vec<[&str; 4]> = students.iter().map(|s| [s.id.as_str(), &s.name, &s.email, &s.mobile]).collect();
+    let mut widths = header.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+
+/// formats of the files for import and export
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FileFormat {
+    Json,
+    Csv,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &Path) -> Option<FileFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(FileFormat::Json),
+            "csv" => Some(FileFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn render(self, students: &[StudentV1]) -> Result<Vec<u8>, Failure> {
+        match self {
+            FileFormat::Json => self.render_value(&students),
+            FileFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(vec![]);
+                if students.is_empty() {
+                    writer.write_record(["id", "name", "email", "mobile"]).map_err(csv_error)?;
+                }
+                for student in students {
+                    writer.serialize(student).map_err(csv_error)?;
+                }
+                writer.into_inner().map_err(|e| Failure::new(e.to_string()))
+            }
+        }
+    }
+
+    fn render_value(self, value: &impl serde::Serialize) -> Result<Vec<u8>, Failure> {
+        let mut data = serde_json::to_vec_pretty(value).map_err(|e| Failure::new(e.to_string()))?;
+        data.push(b'\n');
+        Ok(data)
+    }
+
+    /// students of an import file, their ids are ignored
+    pub fn parse(self, data: &[u8]) -> Result<Vec<StudentV1>, Failure> {
+        match self {
+            FileFormat::Json => serde_json::from_slice(data).map_err(|e| Failure::new(format!("invalid json: {}", e))),
+            FileFormat::Csv => csv::Reader::from_reader(data)
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .map_err(|e| Failure::new(format!("invalid csv: {}", e))),
+        }
+    }
+}
+
+fn csv_error(e: csv::Error) -> Failure {
+    Failure::new(e.to_string())
+}
@@ -0,0 +1,190 @@
+use std::{io, path::PathBuf};
+use studet_api::{crypto::Keys, handler, model::Student};
+use studet_client::{Client, ContactKind, ContactV2, Error, NameInputV2, StudentInputV2, StudentV1};
+use uuid::Uuid;
+
+
+const FAILED: u8 = 1;
+const NOT_FOUND: u8 = 3;
+const DENIED: u8 = 4;
+const UNREACHABLE: u8 = 5;
+
+/// why a command failed and the exit code telling scripts about it
+#[derive(Debug)]
+pub struct Failure {
+    pub message: String,
+    pub code: u8,
+}
+
+impl Failure {
+    pub fn new(message: impl Into<String>) -> Self {
+        Failure { message: message.into(), code: FAILED }
+    }
+
+    fn not_found(id: &str) -> Self {
+        Failure { message: format!("no student with id {}", id), code: NOT_FOUND }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::NotFound => NOT_FOUND,
+            Error::Unauthorized | Error::Forbidden => DENIED,
+            Error::Transport(_) | Error::Timeout => UNREACHABLE,
+            _ => FAILED,
+        };
+        Failure { message: e.to_string(), code }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self {
+        Failure::new(e.to_string())
+    }
+}
+
+
+/// where the students are: a running server or the data file of one.
+///
+/// The file is changed in place, a server using it merges the change in like any other edit of the file.
+pub enum Store {
+    Server(Client),
+    File { path: PathBuf, keys: Option<Keys> },
+}
+
+impl Store {
+    pub async fn list(&self) -> Result<Vec<StudentV1>, Failure> {
+        match self {
+            Store::Server(client) => Ok(client.list_students().await?),
+            Store::File { .. } => Ok(self.load()?.iter().map(StudentV1::from).collect()),
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Result<StudentV1, Failure> {
+        match self {
+            Store::Server(client) => Ok(client.get_student(id).await?),
+            Store::File { .. } => self.load()?.iter().find(|s| s.id == id).map(StudentV1::from).ok_or_else(|| Failure::not_found(id)),
+        }
+    }
+
+    /// store a new student, returned with its id
+    pub async fn create(&self, student: StudentV1) -> Result<StudentV1, Failure> {
+        match self {
+            // v2 answers with the new student, v1 does not
+            Store::Server(client) => {
+                let created = client.create_student_v2(&input(&student)).await?;
+                Ok(StudentV1 { id: created.id, ..student })
+            }
+            Store::File { .. } => {
+                let mut students = self.load()?;
+                let created = new_student(student)?;
+                students.push(created.clone());
+                self.save(&students)?;
+                Ok(StudentV1::from(&created))
+            }
+        }
+    }
+
+    /// change the given fields, the others stay as they are
+    pub async fn update(&self, id: &str, name: Option<String>, email: Option<String>, mobile: Option<String>) -> Result<StudentV1, Failure> {
+        let change = |current: StudentV1| StudentV1 {
+            name: name.unwrap_or(current.name),
+            email: email.unwrap_or(current.email),
+            mobile: mobile.unwrap_or(current.mobile),
+            ..current
+        };
+        match self {
+            Store::Server(client) => {
+                let changed = change(client.get_student(id).await?);
+                client.update_student(id, &changed).await?;
+                Ok(changed)
+            }
+            Store::File { .. } => {
+                let mut students = self.load()?;
+                let student = students.iter_mut().find(|s| s.id == id).ok_or_else(|| Failure::not_found(id))?;
+                let mut changed = student.clone();
+                change(StudentV1::from(&*student)).apply_to(&mut changed);
+                *student = changed;
+                let updated = StudentV1::from(&*student);
+                self.save(&students)?;
+                Ok(updated)
+            }
+        }
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), Failure> {
+        match self {
+            Store::Server(client) => Ok(client.delete_student(id).await?),
+            Store::File { .. } => {
+                let mut students = self.load()?;
+                let count = students.len();
+                students.retain(|s| s.id != id);
+                if students.len() == count {
+                    return Err(Failure::not_found(id));
+                }
+                self.save(&students)
+            }
+        }
+    }
+
+    /// add the students under new ids. The file gets all or none of them, a server all up to
+    /// the first one it refuses.
+    pub async fn import(&self, new: Vec<StudentV1>) -> Result<usize, Failure> {
+        match self {
+            Store::Server(client) => {
+                for (number, student) in new.iter().enumerate() {
+                    if let Err(e) = client.create_student_v2(&input(student)).await {
+                        let failure = Failure::from(e);
+                        return Err(Failure { message: format!("student {} of the file: {}, {} imported before", number + 1, failure.message, number), ..failure });
+                    }
+                }
+                Ok(new.len())
+            }
+            Store::File { .. } => {
+                let mut students = self.load()?;
+                for (number, student) in new.iter().enumerate() {
+                    students.push(new_student(student.clone()).map_err(|e| Failure::new(format!("student {} of the file: {}, nothing imported", number + 1, e.message)))?);
+                }
+                self.save(&students)?;
+                Ok(new.len())
+            }
+        }
+    }
+
+
+    fn load(&self) -> Result<Vec<Student>, Failure> {
+        let Store::File { path, keys } = self else { unreachable!("only used for files") };
+        let data = handler::read_file(path).map_err(|e| Failure::new(format!("could not read {}: {}", path.display(), e)))?;
+        match data {
+            Some(data) => handler::parse_students(&data, keys.as_ref()).map_err(|e| Failure::new(format!("invalid data in {}: {}", path.display(), e))),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn save(&self, students: &[Student]) -> Result<(), Failure> {
+        let Store::File { path, keys } = self else { unreachable!("only used for files") };
+        handler::save_students(path, students, keys.as_ref())
+            .map(|_| ())
+            .map_err(|e| Failure::new(format!("could not write {}: {}", path.display(), e)))
+    }
+}
+
+
+/// a validated student with a new id, as the server creates it
+fn new_student(student: StudentV1) -> Result<Student, Failure> {
+    let mut student = student.into_student();
+    student.validate().map_err(Failure::new)?;
+    student.id = Uuid::new_v4().to_string();
+    Ok(student)
+}
+
+fn input(student: &StudentV1) -> StudentInputV2 {
+    StudentInputV2 {
+        name: NameInputV2 { given: student.name.clone(), family: None },
+        contacts: vec![
+            ContactV2 { kind: ContactKind::Email, value: student.email.clone(), primary: true },
+            ContactV2 { kind: ContactKind::Mobile, value: student.mobile.clone(), primary: true },
+        ],
+    }
+}
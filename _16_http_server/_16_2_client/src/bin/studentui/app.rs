@@ -0,0 +1,363 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use studet_client::{ChangeKind, ContactKind, ContactV2, NameInputV2, StudentEventV2, StudentInputV2, StudentV2};
+
+
+const PAGE: usize = 10;
+pub const FORM_LABELS: [&str; 4] = ["Given name", "Family name", "Email", "Mobile"];
+
+
+/// what the user is doing right now
+pub enum Mode {
+    Browse,
+    // typing into the search line
+    Search,
+    Edit(Form),
+    ConfirmDelete { id: String, name: String },
+}
+
+/// the fields of a student being edited or created
+pub struct Form {
+    // `None` while creating a new student
+    pub id: Option<String>,
+    pub values: [String; 4],
+    pub focus: usize,
+    // the contacts besides the primary email and mobile, kept as they are
+    others: Vec<ContactV2>,
+    // somebody else changed the student since the form was opened
+    pub stale: bool,
+}
+
+impl Form {
+    fn new() -> Self {
+        Form { id: None, values: Default::default(), focus: 0, others: vec![], stale: false }
+    }
+
+    fn edit(student: &StudentV2) -> Self {
+        let primary = |kind: ContactKind| student.contacts.iter().find(|c| c.kind == kind && c.primary).map(|c| c.value.clone()).unwrap_or_default();
+        Form {
+            id: Some(student.id.clone()),
+            values: [
+                student.name.given.clone(),
+                student.name.family.clone().unwrap_or_default(),
+                primary(ContactKind::Email),
+                primary(ContactKind::Mobile),
+            ],
+            focus: 0,
+            others: student.contacts.iter().filter(|c| !(c.primary && matches!(c.kind, ContactKind::Email | ContactKind::Mobile))).cloned().collect(),
+            stale: false,
+        }
+    }
+
+    fn input(&self) -> StudentInputV2 {
+        let [given, family, email, mobile] = self.values.clone().map(|value| value.trim().to_string());
+        let mut contacts = vec![
+            ContactV2 { kind: ContactKind::Email, value: email, primary: true },
+            ContactV2 { kind: ContactKind::Mobile, value: mobile, primary: true },
+        ];
+        contacts.extend(self.others.iter().cloned());
+        StudentInputV2 { name: NameInputV2 { given, family: Some(family).filter(|f| !f.is_empty()) }, contacts }
+    }
+}
+
+
+/// what the caller has to do after a key press, the app itself never talks to the server
+#[derive(Debug)]
+pub enum Action {
+    None,
+    Save { id: Option<String>, input: StudentInputV2 },
+    Delete(String),
+    Reload,
+    Quit,
+}
+
+
+pub struct App {
+    // all students, sorted by name
+    students: Vec<StudentV2>,
+    pub filter: String,
+    // position in `visible()`
+    pub selected: usize,
+    pub mode: Mode,
+    // the last error or notice, shown in the status line until the next key press
+    pub status: Option<String>,
+    // the live updates are connected
+    pub live: bool,
+}
+
+impl App {
+    pub fn new() -> Self {
+        App { students: vec![], filter: String::new(), selected: 0, mode: Mode::Browse, status: None, live: false }
+    }
+
+    /// the students matching the search, by name, any contact or the start of the id
+    pub fn visible(&self) -> Vec<&StudentV2> {
+        let filter = self.filter.to_lowercase();
+        self.students
+            .iter()
+            .filter(|s| {
+                filter.is_empty()
+                    || s.name.display.to_lowercase().contains(&filter)
+                    || s.contacts.iter().any(|c| c.value.to_lowercase().contains(&filter))
+                    || s.id.starts_with(&filter)
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> usize {
+        self.students.len()
+    }
+
+    pub fn selected_student(&self) -> Option<&StudentV2> {
+        self.visible().get(self.selected).copied()
+    }
+
+    /// all students were loaded
+    pub fn loaded(&mut self, students: Vec<StudentV2>) {
+        let selected = self.selected_id();
+        self.students = students;
+        self.sort_and_keep(selected);
+    }
+
+    /// a change pushed by the server, made by this app or anybody else
+    pub fn change(&mut self, event: StudentEventV2) {
+        let selected = self.selected_id();
+        self.students.retain(|s| s.id != event.id);
+        if let Some(student) = event.student {
+            self.students.push(student);
+        }
+        if let Mode::Edit(form) = &mut self.mode
+            && form.id.as_deref() == Some(event.id.as_str())
+        {
+            if event.kind == ChangeKind::Deleted {
+                self.mode = Mode::Browse;
+                self.status = Some("the student was deleted by somebody else".to_string());
+            } else {
+                form.stale = true;
+            }
+        }
+        self.sort_and_keep(selected);
+    }
+
+    /// a save started by `Action::Save` is done
+    pub fn saved(&mut self, result: Result<StudentV2, String>) {
+        match result {
+            Ok(student) => {
+                self.status = Some(format!("saved {}", student.name.display));
+                let id = student.id.clone();
+                self.change(StudentEventV2 { kind: ChangeKind::Updated, id: id.clone(), student: Some(student) });
+                self.mode = Mode::Browse;
+                self.select(&id);
+            }
+            // the form stays open, nothing typed is lost
+            Err(e) => self.status = Some(format!("could not save: {}", e)),
+        }
+    }
+
+    /// a delete started by `Action::Delete` is done
+    pub fn deleted(&mut self, id: &str, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.change(StudentEventV2 { kind: ChangeKind::Deleted, id: id.to_string(), student: None });
+                self.status = Some("deleted".to_string());
+            }
+            Err(e) => self.status = Some(format!("could not delete: {}", e)),
+        }
+    }
+
+    pub fn key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        self.status = None;
+        match &mut self.mode {
+            Mode::Browse => self.browse_key(key),
+            Mode::Search => {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Down => self.mode = Mode::Browse,
+                    KeyCode::Esc => {
+                        self.filter.clear();
+                        self.mode = Mode::Browse;
+                    }
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Char(c) => self.filter.push(c),
+                    _ => {}
+                }
+                self.selected = 0;
+                Action::None
+            }
+            Mode::Edit(form) => match key.code {
+                KeyCode::Esc => {
+                    self.mode = Mode::Browse;
+                    Action::None
+                }
+                KeyCode::Enter => Action::Save { id: form.id.clone(), input: form.input() },
+                KeyCode::Tab | KeyCode::Down => {
+                    form.focus = (form.focus + 1) % FORM_LABELS.len();
+                    Action::None
+                }
+                KeyCode::BackTab | KeyCode::Up => {
+                    form.focus = (form.focus + FORM_LABELS.len() - 1) % FORM_LABELS.len();
+                    Action::None
+                }
+                KeyCode::Backspace => {
+                    form.values[form.focus].pop();
+                    Action::None
+                }
+                KeyCode::Char(c) => {
+                    form.values[form.focus].push(c);
+                    Action::None
+                }
+                _ => Action::None,
+            },
+            Mode::ConfirmDelete { id, .. } => {
+                let id = id.clone();
+                self.mode = Mode::Browse;
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => Action::Delete(id),
+                    _ => Action::None,
+                }
+            }
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) -> Action {
+        let last = self.visible().len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::PageDown => self.selected = (self.selected + PAGE).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(PAGE),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('r') => return Action::Reload,
+            KeyCode::Char('n') => self.mode = Mode::Edit(Form::new()),
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(student) = self.selected_student() {
+                    self.mode = Mode::Edit(Form::edit(student));
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(student) = self.selected_student() {
+                    self.mode = Mode::ConfirmDelete { id: student.id.clone(), name: student.name.display.clone() };
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        self.selected_student().map(|s| s.id.clone())
+    }
+
+    /// sort again and keep the selection on the same student while it is still there
+    fn sort_and_keep(&mut self, selected: Option<String>) {
+        self.students.sort_by(|a, b| a.name.display.to_lowercase().cmp(&b.name.display.to_lowercase()).then(a.id.cmp(&b.id)));
+        match selected {
+            Some(id) if self.visible().iter().any(|s| s.id == id) => self.select(&id),
+            _ => self.selected = self.selected.min(self.visible().len().saturating_sub(1)),
+        }
+    }
+
+    fn select(&mut self, id: &str) {
+        if let Some(position) = self.visible().iter().position(|s| s.id == id) {
+            self.selected = position;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use studet_client::NameV2;
+
+    fn student(id: &str, given: &str, email: &str) -> StudentV2 {
+        StudentV2 {
+            id: id.to_string(),
+            name: NameV2 { given: given.to_string(), family: None, display: given.to_string() },
+            contacts: vec![
+                ContactV2 { kind: ContactKind::Email, value: email.to_string(), primary: true },
+                ContactV2 { kind: ContactKind::Mobile, value: "9876543210".to_string(), primary: true },
+                ContactV2 { kind: ContactKind::Phone, value: "0201234567".to_string(), primary: false },
+            ],
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn press(app: &mut App, keys: &str) -> Action {
+        keys.chars().map(|c| app.key(KeyEvent::from(KeyCode::Char(c)))).last().unwrap_or(Action::None)
+    }
+
+    fn code(app: &mut App, code: KeyCode) -> Action {
+        app.key(KeyEvent::from(code))
+    }
+
+    fn names(app: &App) -> Vec<&str> {
+        app.visible().iter().map(|s| s.name.display.as_str()).collect()
+    }
+
+    #[test]
+    fn search_and_selection_follow_changes() {
+        let mut app = App::new();
+        app.loaded(vec![student("2", "Bela", "bela@example.com"), student("1", "Aman", "aman@school.example.com")]);
+        assert_eq!(names(&app), vec!["Aman", "Bela"]);
+
+        press(&mut app, "j");
+        assert_eq!(app.selected_student().unwrap().id, "2");
+        // somebody adds a student sorted before the selected one, the selection stays on Bela
+        app.change(StudentEventV2 { kind: ChangeKind::Created, id: "3".into(), student: Some(student("3", "Abe", "abe@example.com")) });
+        assert_eq!(app.selected_student().unwrap().id, "2");
+
+        press(&mut app, "/school");
+        code(&mut app, KeyCode::Enter);
+        assert_eq!(names(&app), vec!["Aman"]);
+        press(&mut app, "/");
+        code(&mut app, KeyCode::Esc);
+        assert_eq!(app.visible().len(), 3);
+    }
+
+    #[test]
+    fn edit_keeps_other_contacts_and_survives_errors() {
+        let mut app = App::new();
+        app.loaded(vec![student("1", "Aman", "aman@example.com")]);
+
+        press(&mut app, "e");
+        code(&mut app, KeyCode::Tab);
+        press(&mut app, "Verasia");
+        let Action::Save { id, input } = code(&mut app, KeyCode::Enter) else { panic!("expected a save") };
+        assert_eq!(id.as_deref(), Some("1"));
+        assert_eq!(input.name.family.as_deref(), Some("Verasia"));
+        assert_eq!(input.contacts.len(), 3);
+        assert_eq!(input.contacts[2].kind, ContactKind::Phone);
+
+        app.saved(Err("bad request".into()));
+        assert!(matches!(&app.mode, Mode::Edit(form) if form.values[1] == "Verasia"));
+
+        // a change by somebody else marks the form, a delete closes it
+        app.change(StudentEventV2 { kind: ChangeKind::Updated, id: "1".into(), student: Some(student("1", "Aman", "new@example.com")) });
+        assert!(matches!(&app.mode, Mode::Edit(form) if form.stale));
+        app.change(StudentEventV2 { kind: ChangeKind::Deleted, id: "1".into(), student: None });
+        assert!(matches!(app.mode, Mode::Browse));
+        assert!(app.status.is_some());
+    }
+
+    #[test]
+    fn delete_needs_confirmation() {
+        let mut app = App::new();
+        app.loaded(vec![student("1", "Aman", "aman@example.com")]);
+
+        press(&mut app, "d");
+        assert!(matches!(press(&mut app, "n"), Action::None));
+        press(&mut app, "d");
+        assert!(matches!(press(&mut app, "y"), Action::Delete(id) if id == "1"));
+        app.deleted("1", Ok(()));
+        assert!(app.visible().is_empty());
+        assert!(matches!(press(&mut app, "q"), Action::Quit));
+    }
+}
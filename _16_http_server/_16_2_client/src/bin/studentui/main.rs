@@ -0,0 +1,164 @@
+use std::{process::ExitCode, time::Duration};
+use clap::Parser;
+use ratatui::crossterm::event::{self, Event, KeyEvent, KeyEventKind};
+use studet_client::{Change, Client, StudentV2};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+mod app;
+mod ui;
+
+use app::{Action, App};
+
+
+/// browse, search and edit the students of a running server in the terminal.
+///
+/// Changes made by others show up right away. Needs nothing but a terminal, so it works over ssh.
+///
+/// studentui --server https://students.example.com --api-key {key}
+#[derive(Parser)]
+#[command(name = "studentui", version, verbatim_doc_comment)]
+struct Cli {
+    /// url of a running server
+    #[arg(long, env = "STUDENTCTL_SERVER", default_value = "http://127.0.0.1:4500")]
+    server: String,
+    /// the admin key or the key of a tenant
+    #[arg(long, env = "STUDENTCTL_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    /// use the students of this tenant
+    #[arg(long, env = "STUDENTCTL_TENANT")]
+    tenant: Option<String>,
+}
+
+
+/// everything the main loop reacts to
+enum Message {
+    Key(KeyEvent),
+    // the terminal was resized, only a redraw is needed
+    Redraw,
+    Loaded(Result<Vec<StudentV2>, String>),
+    Change(Change),
+    Live(bool),
+    Saved(Result<StudentV2, String>),
+    Deleted(String, Result<(), String>),
+}
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut builder = Client::builder(&cli.server).timeout(Duration::from_secs(10));
+    if let Some(key) = &cli.api_key {
+        builder = builder.api_key(key);
+    }
+    if let Some(tenant) = &cli.tenant {
+        builder = builder.tenant(tenant);
+    }
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("studentui: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // restores the terminal on panics as well
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, client).await;
+    ratatui::restore();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("studentui: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(terminal: &mut ratatui::DefaultTerminal, client: Client) -> std::io::Result<()> {
+    let (tx, mut rx) = unbounded_channel();
+    spawn_input(tx.clone());
+    tokio::spawn(watch(client.clone(), tx.clone()));
+
+    // the first load does not wait for the stream, a proxy might not pass it
+    reload(&client, &tx);
+    let mut app = App::new();
+    loop {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+        let Some(message) = rx.recv().await else { return Ok(()) };
+        match message {
+            Message::Key(key) => match app.key(key) {
+                Action::None => {}
+                Action::Quit => return Ok(()),
+                Action::Reload => reload(&client, &tx),
+                Action::Save { id, input } => {
+                    let (client, tx) = (client.clone(), tx.clone());
+                    tokio::spawn(async move {
+                        let result = match id {
+                            Some(id) => client.update_student_v2(&id, &input).await,
+                            None => client.create_student_v2(&input).await,
+                        };
+                        let _ = tx.send(Message::Saved(result.map_err(|e| e.to_string())));
+                    });
+                }
+                Action::Delete(id) => {
+                    let (client, tx) = (client.clone(), tx.clone());
+                    tokio::spawn(async move {
+                        let result = client.delete_student_v2(&id).await;
+                        let _ = tx.send(Message::Deleted(id, result.map_err(|e| e.to_string())));
+                    });
+                }
+            },
+            Message::Redraw => {}
+            Message::Loaded(Ok(students)) => app.loaded(students),
+            Message::Loaded(Err(e)) => app.status = Some(format!("could not load the students: {}", e)),
+            Message::Change(Change::Student(event)) => app.change(event),
+            Message::Change(Change::Resync) => reload(&client, &tx),
+            Message::Live(live) => app.live = live,
+            Message::Saved(result) => app.saved(result),
+            Message::Deleted(id, result) => app.deleted(&id, result),
+        }
+    }
+}
+
+fn reload(client: &Client, tx: &UnboundedSender<Message>) {
+    let (client, tx) = (client.clone(), tx.clone());
+    tokio::spawn(async move {
+        let _ = tx.send(Message::Loaded(client.list_students_v2().await.map_err(|e| e.to_string())));
+    });
+}
+
+/// read the terminal on its own thread, crossterm only has a blocking api without extra features
+fn spawn_input(tx: UnboundedSender<Message>) {
+    std::thread::spawn(move || loop {
+        let message = match event::read() {
+            // windows reports releases as well
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => Message::Key(key),
+            Ok(Event::Resize(..)) => Message::Redraw,
+            Ok(_) => continue,
+            Err(_) => return,
+        };
+        if tx.send(message).is_err() {
+            return;
+        }
+    });
+}
+
+/// follow the changes of the server, reconnecting when the stream breaks. All students are
+/// loaded again after every connect, changes may have been missed in between.
+async fn watch(client: Client, tx: UnboundedSender<Message>) {
+    loop {
+        if let Ok(mut watch) = client.watch_students().await {
+            let _ = tx.send(Message::Live(true));
+            let _ = tx.send(Message::Change(Change::Resync));
+            while let Ok(Some(change)) = watch.next().await {
+                if tx.send(Message::Change(change)).is_err() {
+                    return;
+                }
+            }
+        }
+        if tx.send(Message::Live(false)).is_err() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
@@ -0,0 +1,183 @@
+use ratatui::{
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Row, Table, TableState, Wrap},
+    Frame,
+};
+use studet_client::{ContactKind, StudentV2};
+use crate::app::{App, Form, Mode, FORM_LABELS};
+
+
+// only the basic colors and reverse video, so it looks right in any terminal and over ssh
+const SELECTED: Style = Style::new().add_modifier(Modifier::REVERSED);
+const DIM: Style = Style::new().fg(Color::DarkGray);
+
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [top, main, bottom] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [list, detail] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+    draw_top(frame, app, top);
+    draw_table(frame, app, list);
+    draw_detail(frame, app.selected_student(), detail);
+    draw_status(frame, app, bottom);
+
+    match &app.mode {
+        Mode::Edit(form) => draw_form(frame, form),
+        Mode::ConfirmDelete { name, .. } => {
+            let area = centered(frame.area(), 44, 3);
+            frame.render_widget(Clear, area);
+            let question = Paragraph::new(format!("Delete {}? (y/n)", name)).block(Block::bordered().title(" Confirm "));
+            frame.render_widget(question, area);
+        }
+        _ => {}
+    }
+}
+
+fn draw_top(frame: &mut Frame, app: &App, area: Rect) {
+    let live = if app.live { Span::styled("● live", Style::new().fg(Color::Green)) } else { Span::styled("○ reconnecting", Style::new().fg(Color::Yellow)) };
+    let mut spans = vec![Span::raw(format!("Students {}/{}  ", app.visible().len(), app.total())), live];
+    if matches!(app.mode, Mode::Search) || !app.filter.is_empty() {
+        spans.push(Span::raw(format!("   search: {}", app.filter)));
+        if matches!(app.mode, Mode::Search) {
+            let x = area.x + Line::from(spans.clone()).width() as u16;
+            frame.set_cursor_position(Position::new(x.min(area.right().saturating_sub(1)), area.y));
+        }
+    }
+    frame.render_widget(Line::from(spans), area);
+}
+
+fn draw_table(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.visible().into_iter().map(|s| Row::new(vec![s.name.display.clone(), primary(s, ContactKind::Email), primary(s, ContactKind::Mobile)]));
+    let widths = [Constraint::Percentage(40), Constraint::Percentage(40), Constraint::Percentage(20)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["Name", "Email", "Mobile"]).style(Style::new().add_modifier(Modifier::BOLD)))
+        .row_highlight_style(SELECTED)
+        .block(Block::bordered());
+    let mut state = TableState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_detail(frame: &mut Frame, student: Option<&StudentV2>, area: Rect) {
+    let block = Block::bordered().title(" Details ");
+    let Some(student) = student else {
+        frame.render_widget(Paragraph::new(Span::styled("no student selected", DIM)).block(block), area);
+        return;
+    };
+    let time = |at: Option<chrono::DateTime<chrono::Utc>>| at.map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string()).unwrap_or_else(|| "-".to_string());
+    let mut lines = vec![
+        Line::from(Span::styled(student.name.display.clone(), Style::new().add_modifier(Modifier::BOLD))),
+        Line::from(Span::styled(student.id.clone(), DIM)),
+        Line::raw(""),
+    ];
+    for contact in &student.contacts {
+        let primary = if contact.primary { " (primary)" } else { "" };
+        lines.push(Line::raw(format!("{:<7} {}{}", kind_label(contact.kind), contact.value, primary)));
+    }
+    lines.push(Line::raw(""));
+    lines.push(Line::from(Span::styled(format!("created {}", time(student.created_at)), DIM)));
+    lines.push(Line::from(Span::styled(format!("updated {}", time(student.updated_at)), DIM)));
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), area);
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let line = match (&app.status, &app.mode) {
+        (Some(status), _) => Line::from(Span::styled(status.clone(), Style::new().fg(Color::Yellow))),
+        (None, Mode::Browse) => Line::styled("↑↓ move  / search  e edit  n new  d delete  r reload  q quit", DIM),
+        (None, Mode::Search) => Line::styled("type to search  enter done  esc clear", DIM),
+        (None, Mode::Edit(_)) => Line::styled("tab next field  enter save  esc cancel", DIM),
+        (None, Mode::ConfirmDelete { .. }) => Line::styled("y delete  any other key keeps the student", DIM),
+    };
+    frame.render_widget(line, area);
+}
+
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let area = centered(frame.area(), 60, FORM_LABELS.len() as u16 + 4);
+    frame.render_widget(Clear, area);
+    let title = if form.id.is_some() { " Edit student " } else { " New student " };
+    let block = Block::bordered().title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let mut lines: Vec<Line> = FORM_LABELS
+        .iter()
+        .zip(&form.values)
+        .enumerate()
+        .map(|(i, (label, value))| {
+            let style = if i == form.focus { SELECTED } else { Style::new() };
+            Line::from(vec![Span::raw(format!("{:>12}: ", label)), Span::styled(value.clone(), style)])
+        })
+        .collect();
+    if form.stale {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled("changed by somebody else meanwhile, saving overwrites it", Style::new().fg(Color::Yellow)));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+
+    let x = inner.x + 14 + form.values[form.focus].chars().count() as u16;
+    frame.set_cursor_position(Position::new(x.min(inner.right().saturating_sub(1)), inner.y + form.focus as u16));
+}
+
+
+fn primary(student: &StudentV2, kind: ContactKind) -> String {
+    student.contacts.iter().find(|c| c.primary && c.kind == kind).map(|c| c.value.clone()).unwrap_or_default()
+}
+
+fn kind_label(kind: ContactKind) -> &'static str {
+    match kind {
+        ContactKind::Email => "email",
+        ContactKind::Mobile => "mobile",
+        ContactKind::Phone => "phone",
+    }
+}
+
+/// a rectangle of the given size in the middle of `area`, smaller when `area` is
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, crossterm::event::{KeyCode, KeyEvent}, Terminal};
+    use studet_client::{ContactV2, NameV2};
+
+    fn screen(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 16)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>() + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn table_detail_and_dialogs() {
+        let mut app = App::new();
+        app.loaded(vec![StudentV2 {
+            id: "9d73e21e".into(),
+            name: NameV2 { given: "Ellis".into(), family: Some("Tarmaster".into()), display: "Ellis Tarmaster".into() },
+            contacts: vec![
+                ContactV2 { kind: ContactKind::Email, value: "ellis@example.com".into(), primary: true },
+                ContactV2 { kind: ContactKind::Phone, value: "0201234567".into(), primary: false },
+            ],
+            created_at: None,
+            updated_at: None,
+        }]);
+        let shown = screen(&app);
+        assert!(shown.contains("Students 1/1"));
+        assert!(shown.contains("Ellis Tarmaster") && shown.contains("ellis@example.com"));
+        assert!(shown.contains("phone   0201234567"));
+
+        app.key(KeyEvent::from(KeyCode::Char('d')));
+        assert!(screen(&app).contains("Delete Ellis Tarmaster? (y/n)"));
+        app.key(KeyEvent::from(KeyCode::Esc));
+        app.key(KeyEvent::from(KeyCode::Char('e')));
+        let shown = screen(&app);
+        assert!(shown.contains("Edit student") && shown.contains("Family name: Tarmaster"));
+    }
+}
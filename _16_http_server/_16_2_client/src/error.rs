@@ -0,0 +1,100 @@
+use std::fmt;
+
+
+/// what can go wrong calling the student api
+#[derive(Debug)]
+pub enum Error {
+    // 400, with the message of the server if it sent one
+    BadRequest(String),
+    // 401, the api key is missing or wrong
+    Unauthorized,
+    // 403, e.g. the quota is reached or the tenant is suspended
+    Forbidden,
+    // 404
+    NotFound,
+    // 409, the student was changed by somebody else at the same time
+    Conflict,
+    // 422, e.g. an email another student has or a backup which can not be restored
+    Unprocessable(String),
+    // 503, the server can not use its storage right now
+    Unavailable(String),
+    // any other status which is not a success
+    Status { status: u16, body: String },
+    // no answer within the configured timeout
+    Timeout,
+    // the server could not be reached or the connection broke
+    Transport(reqwest::Error),
+    // the answer was not what the endpoint returns
+    Decode(String),
+    InvalidUrl(String),
+}
+
+impl Error {
+    pub(crate) fn from_status(status: u16, body: String) -> Error {
+        match status {
+            400 => Error::BadRequest(body),
+            401 => Error::Unauthorized,
+            403 => Error::Forbidden,
+            404 => Error::NotFound,
+            409 => Error::Conflict,
+            422 => Error::Unprocessable(body),
+            503 => Error::Unavailable(body),
+            _ => Error::Status { status, body },
+        }
+    }
+
+    /// the status the server answered with, `None` when there was no answer
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::BadRequest(_) => Some(400),
+            Error::Unauthorized => Some(401),
+            Error::Forbidden => Some(403),
+            Error::NotFound => Some(404),
+            Error::Conflict => Some(409),
+            Error::Unprocessable(_) => Some(422),
+            Error::Unavailable(_) => Some(503),
+            Error::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadRequest(body) => write!(f, "bad request: {}", body),
+            Error::Unauthorized => write!(f, "missing or wrong api key"),
+            Error::Forbidden => write!(f, "forbidden"),
+            Error::NotFound => write!(f, "not found"),
+            Error::Conflict => write!(f, "conflicting change"),
+            Error::Unprocessable(body) => write!(f, "unprocessable: {}", body),
+            Error::Unavailable(body) => write!(f, "service unavailable: {}", body),
+            Error::Status { status, body } => write!(f, "server answered {}: {}", status, body),
+            Error::Timeout => write!(f, "request timed out"),
+            Error::Transport(e) => write!(f, "could not reach the server: {}", e),
+            Error::Decode(e) => write!(f, "unexpected response: {}", e),
+            Error::InvalidUrl(e) => write!(f, "invalid url: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Error::Timeout
+        } else if e.is_decode() {
+            Error::Decode(e.to_string())
+        } else {
+            Error::Transport(e)
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Typed client for the student api in `_16_1_axum`.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), studet_client::Error> {
+//! let client = studet_client::Client::builder("http://127.0.0.1:4500").api_key("secret").build()?;
+//! for student in client.list_students_v2().await? {
+//!     println!("{} {}", student.id, student.name.display);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+use reqwest::{header::RETRY_AFTER, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+mod error;
+mod watch;
+
+pub use error::Error;
+pub use watch::{Change, Watch};
+pub use studet_api::{
+    backup::{BackupInfo, RestorePlan},
+    compliance::{Erasure, Export},
+    health::{Diagnostics, Readiness, StoreDiagnostics},
+    model::{Contact, ContactKind, NameParts, Student},
+    state::ChangeKind,
+    tenant::{NewTenant, TenantInfo},
+    v1::StudentV1,
+    v2::{ContactV2, NameInputV2, NameV2, StudentEventV2, StudentInputV2, StudentV2},
+    webhook::{Delivery, Webhook, WebhookInput},
+};
+
+const API_KEY_HEADER: &str = "x-api-key";
+const TENANT_HEADER: &str = "x-tenant";
+// the server sends a keep-alive every 15 seconds
+const STREAM_READ_TIMEOUT: Duration = Duration::from_secs(45);
+
+
+/// how requests are retried when the server is unreachable or answers 429, 502, 503 or 504.
+///
+/// GET, PUT and DELETE are retried on all of these, POST only when the connection could not
+/// be made, so nothing is created twice.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    // retries after the first attempt, 0 turns retrying off
+    pub max_retries: u32,
+    // wait before the first retry, doubled for every further one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry { max_retries: 3, base_delay: Duration::from_millis(200), max_delay: Duration::from_secs(5) }
+    }
+}
+
+impl Retry {
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(retry)).min(self.max_delay)
+    }
+}
+
+
+pub struct ClientBuilder {
+    base_url: String,
+    api_key: Option<String>,
+    tenant: Option<String>,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: Retry,
+}
+
+impl ClientBuilder {
+    /// key for the `X-Api-Key` header - the admin key or the key of a tenant
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// send every request to the store of this tenant
+    pub fn tenant(mut self, name: impl Into<String>) -> Self {
+        self.tenant = Some(name.into());
+        self
+    }
+
+    /// time for a whole request including the body, per attempt
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let mut base = Url::parse(&self.base_url).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+        if base.cannot_be_a_base() {
+            return Err(Error::InvalidUrl(self.base_url));
+        }
+        // a base with a path like `http://host/api` keeps it in front of every route
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        let http = reqwest::Client::builder().timeout(self.timeout).connect_timeout(self.connect_timeout).build()?;
+        // event streams stay open, only a server which stops sending even its keep-alives times out
+        let streams = reqwest::Client::builder().read_timeout(STREAM_READ_TIMEOUT).connect_timeout(self.connect_timeout).build()?;
+        Ok(Client { http, streams, base, api_key: self.api_key, tenant: self.tenant, retry: self.retry })
+    }
+}
+
+
+/// async client with a method for every route of the student api
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    streams: reqwest::Client,
+    base: Url,
+    api_key: Option<String>,
+    tenant: Option<String>,
+    retry: Retry,
+}
+
+impl Client {
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            api_key: None,
+            tenant: None,
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+            retry: Retry::default(),
+        }
+    }
+
+    /// the url of a route, every segment is escaped so ids can not change the path
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut().expect("checked in build").pop_if_empty().extend(segments);
+        url
+    }
+
+    fn headers(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(key) = &self.api_key {
+            request = request.header(API_KEY_HEADER, key);
+        }
+        if let Some(tenant) = &self.tenant {
+            request = request.header(TENANT_HEADER, tenant);
+        }
+        request
+    }
+
+    /// send a request, retrying as described in `Retry`, and fail for every status which is not a success
+    async fn send(&self, method: Method, url: Url, body: Option<&dyn erased::Body>) -> Result<Response, Error> {
+        let idempotent = method != Method::POST;
+        let mut retry = 0;
+        loop {
+            let mut request = self.headers(self.http.request(method.clone(), url.clone()));
+            if let Some(body) = body {
+                request = request.json(&body.to_json()?);
+            }
+
+            let wait = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let transient = matches!(
+                        status,
+                        StatusCode::TOO_MANY_REQUESTS | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                    );
+                    if !(transient && idempotent && retry < self.retry.max_retries) {
+                        return Err(Error::from_status(status.as_u16(), response.text().await.unwrap_or_default()));
+                    }
+                    response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok()?.parse().ok())
+                        .map(Duration::from_secs)
+                }
+                // nothing reached the server, so even a POST can be sent again
+                Err(e) if e.is_connect() && retry < self.retry.max_retries => None,
+                Err(e) if e.is_timeout() && idempotent && retry < self.retry.max_retries => None,
+                Err(e) => return Err(e.into()),
+            };
+            tokio::time::sleep(wait.unwrap_or_else(|| self.retry.delay(retry)).min(self.retry.max_delay)).await;
+            retry += 1;
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T, Error> {
+        json(self.send(Method::GET, self.url(segments), None).await?).await
+    }
+
+    async fn post<T: DeserializeOwned>(&self, segments: &[&str], body: Option<&dyn erased::Body>) -> Result<T, Error> {
+        json(self.send(Method::POST, self.url(segments), body).await?).await
+    }
+
+    async fn put<T: DeserializeOwned>(&self, segments: &[&str], body: &dyn erased::Body) -> Result<T, Error> {
+        json(self.send(Method::PUT, self.url(segments), Some(body)).await?).await
+    }
+
+    async fn send_empty(&self, method: Method, segments: &[&str], body: Option<&dyn erased::Body>) -> Result<(), Error> {
+        self.send(method, self.url(segments), body).await.map(|_| ())
+    }
+
+
+    // v1, deprecated on the server, see the `Sunset` header
+
+    pub async fn list_students(&self) -> Result<Vec<StudentV1>, Error> {
+        self.get(&["v1", "students"]).await
+    }
+
+    pub async fn get_student(&self, id: &str) -> Result<StudentV1, Error> {
+        self.get(&["v1", "students", id]).await
+    }
+
+    /// v1 does not answer with the new student, use `create_student_v2` to get its id
+    pub async fn create_student(&self, student: &StudentV1) -> Result<(), Error> {
+        self.send_empty(Method::POST, &["v1", "students"], Some(student)).await
+    }
+
+    pub async fn update_student(&self, id: &str, student: &StudentV1) -> Result<(), Error> {
+        self.send_empty(Method::PUT, &["v1", "students", id], Some(student)).await
+    }
+
+    pub async fn delete_student(&self, id: &str) -> Result<(), Error> {
+        self.send_empty(Method::DELETE, &["v1", "students", id], None).await
+    }
+
+
+    // v2
+
+    pub async fn list_students_v2(&self) -> Result<Vec<StudentV2>, Error> {
+        self.get(&["v2", "students"]).await
+    }
+
+    pub async fn get_student_v2(&self, id: &str) -> Result<StudentV2, Error> {
+        self.get(&["v2", "students", id]).await
+    }
+
+    pub async fn create_student_v2(&self, student: &StudentInputV2) -> Result<StudentV2, Error> {
+        self.post(&["v2", "students"], Some(student)).await
+    }
+
+    pub async fn update_student_v2(&self, id: &str, student: &StudentInputV2) -> Result<StudentV2, Error> {
+        self.put(&["v2", "students", id], student).await
+    }
+
+    pub async fn delete_student_v2(&self, id: &str) -> Result<(), Error> {
+        self.send_empty(Method::DELETE, &["v2", "students", id], None).await
+    }
+
+    /// the changes of all students from now on, see `Watch`
+    pub async fn watch_students(&self) -> Result<Watch, Error> {
+        let response = self.headers(self.streams.get(self.url(&["v2", "students", "events"]))).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::from_status(response.status().as_u16(), response.text().await.unwrap_or_default()));
+        }
+        Ok(Watch::new(response))
+    }
+
+    /// only the given fields of every student, e.g. `&["id", "name"]`
+    pub async fn list_student_fields(&self, fields: &[&str]) -> Result<Vec<Value>, Error> {
+        let mut url = self.url(&["v2", "students"]);
+        url.query_pairs_mut().append_pair("fields", &fields.join(","));
+        json(self.send(Method::GET, url, None).await?).await
+    }
+
+
+    // compliance, needs the admin key
+
+    pub async fn export_student(&self, id: &str) -> Result<Export, Error> {
+        self.get(&["students", id, "export"]).await
+    }
+
+    pub async fn erase_student(&self, id: &str) -> Result<Erasure, Error> {
+        self.post(&["students", id, "erase"], None).await
+    }
+
+
+    // health
+
+    pub async fn healthz(&self) -> Result<(), Error> {
+        self.send_empty(Method::GET, &["healthz"], None).await
+    }
+
+    /// the readiness report, also when the server is not ready. Not retried, the answer is the point.
+    pub async fn readyz(&self) -> Result<Readiness, Error> {
+        let response = self.http.get(self.url(&["readyz"])).send().await?;
+        match response.status() {
+            StatusCode::OK | StatusCode::SERVICE_UNAVAILABLE => json(response).await,
+            status => Err(Error::from_status(status.as_u16(), response.text().await.unwrap_or_default())),
+        }
+    }
+
+    pub async fn diagnostics(&self) -> Result<Diagnostics, Error> {
+        self.get(&["admin", "diagnostics"]).await
+    }
+
+
+    // tenants, needs the admin key
+
+    pub async fn list_tenants(&self) -> Result<Vec<TenantInfo>, Error> {
+        self.get(&["admin", "tenants"]).await
+    }
+
+    pub async fn get_tenant(&self, name: &str) -> Result<TenantInfo, Error> {
+        self.get(&["admin", "tenants", name]).await
+    }
+
+    pub async fn create_tenant(&self, tenant: &NewTenant) -> Result<TenantInfo, Error> {
+        self.post(&["admin", "tenants"], Some(tenant)).await
+    }
+
+    pub async fn suspend_tenant(&self, name: &str) -> Result<TenantInfo, Error> {
+        self.post(&["admin", "tenants", name, "suspend"], None).await
+    }
+
+    pub async fn resume_tenant(&self, name: &str) -> Result<TenantInfo, Error> {
+        self.post(&["admin", "tenants", name, "resume"], None).await
+    }
+
+    pub async fn delete_tenant(&self, name: &str) -> Result<(), Error> {
+        self.send_empty(Method::DELETE, &["admin", "tenants", name], None).await
+    }
+
+
+    // webhooks, needs the admin key
+
+    pub async fn list_webhooks(&self) -> Result<Vec<Webhook>, Error> {
+        self.get(&["webhooks"]).await
+    }
+
+    pub async fn get_webhook(&self, id: &str) -> Result<Webhook, Error> {
+        self.get(&["webhooks", id]).await
+    }
+
+    pub async fn create_webhook(&self, webhook: &WebhookInput) -> Result<Webhook, Error> {
+        self.post(&["webhooks"], Some(webhook)).await
+    }
+
+    pub async fn update_webhook(&self, id: &str, webhook: &WebhookInput) -> Result<Webhook, Error> {
+        self.put(&["webhooks", id], webhook).await
+    }
+
+    pub async fn delete_webhook(&self, id: &str) -> Result<(), Error> {
+        self.send_empty(Method::DELETE, &["webhooks", id], None).await
+    }
+
+    pub async fn dead_letters(&self) -> Result<Vec<Delivery>, Error> {
+        self.get(&["webhooks", "dead-letters"]).await
+    }
+
+    pub async fn redeliver(&self, delivery_id: &str) -> Result<(), Error> {
+        self.send_empty(Method::POST, &["webhooks", "dead-letters", delivery_id, "redeliver"], None).await
+    }
+
+
+    // backups, needs the admin key
+
+    pub async fn list_backups(&self) -> Result<Vec<BackupInfo>, Error> {
+        self.get(&["admin", "backups"]).await
+    }
+
+    pub async fn create_backup(&self) -> Result<BackupInfo, Error> {
+        self.post(&["admin", "backups"], None).await
+    }
+
+    /// with `dry_run` only the changes are reported, nothing is restored
+    pub async fn restore_backup(&self, id: &str, dry_run: bool) -> Result<RestorePlan, Error> {
+        let mut url = self.url(&["admin", "backups", id, "restore"]);
+        if dry_run {
+            url.query_pairs_mut().append_pair("dry_run", "true");
+        }
+        json(self.send(Method::POST, url, None).await?).await
+    }
+}
+
+
+async fn json<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    let data = response.bytes().await?;
+    serde_json::from_slice(&data).map_err(|e| Error::Decode(e.to_string()))
+}
+
+
+/// request bodies of any type behind one reference, so `send` does not need to be generic
+mod erased {
+    use super::*;
+
+    pub trait Body: Sync {
+        fn to_json(&self) -> Result<Value, Error>;
+    }
+
+    impl<T: Serialize + Sync> Body for T {
+        fn to_json(&self) -> Result<Value, Error> {
+            serde_json::to_value(self).map_err(|e| Error::Decode(e.to_string()))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use reqwest::Response;
+use studet_api::v2::StudentEventV2;
+use crate::Error;
+
+
+/// what `Watch::next` reports
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Student(StudentEventV2),
+    // changes were missed, load all students again
+    Resync,
+}
+
+
+/// the changes of `GET /v2/students/events`, read from a server-sent event stream
+pub struct Watch {
+    response: Response,
+    buffer: Vec<u8>,
+}
+
+impl Watch {
+    pub(crate) fn new(response: Response) -> Self {
+        Watch { response, buffer: vec![] }
+    }
+
+    /// wait for the next change, `None` when the server closed the stream
+    pub async fn next(&mut self) -> Result<Option<Change>, Error> {
+        loop {
+            while let Some(block) = take_block(&mut self.buffer) {
+                if let Some(change) = parse_block(&block)? {
+                    return Ok(Some(change));
+                }
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+
+/// the next complete event of the stream, events end with an empty line
+fn take_block(buffer: &mut Vec<u8>) -> Option<String> {
+    let end = buffer.windows(2).position(|w| w == b"\n\n").map(|i| (i, 2))
+        .into_iter()
+        .chain(buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|i| (i, 4)))
+        .min_by_key(|(i, _)| *i)?;
+    let block: Vec<u8> = buffer.drain(..end.0 + end.1).take(end.0).collect();
+    Some(String::from_utf8_lossy(&block).into_owned())
+}
+
+/// the change in an event, `None` for keep-alive comments and unknown events
+fn parse_block(block: &str) -> Result<Option<Change>, Error> {
+    let mut event = "message";
+    let mut data = vec![];
+    for line in block.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = value,
+            "data" => data.push(value),
+            _ => {}
+        }
+    }
+    match event {
+        "change" => serde_json::from_str(&data.join("\n")).map(|e| Some(Change::Student(e))).map_err(|e| Error::Decode(e.to_string())),
+        "resync" => Ok(Some(Change::Resync)),
+        _ => Ok(None),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_split_and_comments_skipped() {
+        let mut buffer = b": keep-alive\n\nevent: resync\ndata: \n\nevent: change\r\ndata: {\"kind\":\"deleted\",\"id\":\"a\",\"student\":null}\r\n\r\nevent: cha".to_vec();
+        let blocks: Vec<String> = std::iter::from_fn(|| take_block(&mut buffer)).collect();
+        assert_eq!(buffer, b"event: cha");
+
+        let changes: Vec<Option<Change>> = blocks.iter().map(|b| parse_block(b).unwrap()).collect();
+        assert_eq!(changes[0], None);
+        assert_eq!(changes[1], Some(Change::Resync));
+        assert!(matches!(&changes[2], Some(Change::Student(e)) if e.id == "a" && e.student.is_none()));
+    }
+}
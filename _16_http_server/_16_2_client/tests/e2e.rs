@@ -0,0 +1,182 @@
+// End to end tests: the client against the real router on a local port.
+
+use std::{
+    sync::{atomic::{AtomicUsize, Ordering}, Arc},
+    time::Duration,
+};
+use axum::{http::StatusCode, routing::{get, post}, Router};
+use studet_api::{app, health, state::AppState, tenant::{self, Tenants}};
+use studet_client::{Change, ChangeKind, Client, ContactKind, ContactV2, Error, NameInputV2, NewTenant, Retry, StudentInputV2, StudentV1};
+use tokio::net::TcpListener;
+
+
+async fn serve(router: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+async fn server() -> (String, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+    let tenants = Arc::new(Tenants::load(dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+    let admin_key = Some(Arc::from("admin-key"));
+    let api = app(state.clone()).merge(health::routes(state, tenants.clone(), admin_key.clone()));
+    (serve(tenant::scoped(api, tenants, admin_key)).await, dir)
+}
+
+fn aman() -> StudentV1 {
+    StudentV1 { id: String::new(), name: "Aman".into(), email: "aman@example.com".into(), mobile: "9876543210".into() }
+}
+
+fn no_retry() -> Retry {
+    Retry { max_retries: 0, ..Retry::default() }
+}
+
+
+#[tokio::test]
+async fn crud_in_both_versions() {
+    let (url, _dir) = server().await;
+    let client = Client::builder(&url).build().unwrap();
+
+    client.create_student(&aman()).await.unwrap();
+    let listed = client.list_students().await.unwrap();
+    assert_eq!(listed.len(), 1);
+    let id = listed[0].id.clone();
+    client.update_student(&id, &StudentV1 { mobile: "1112223334".into(), ..aman() }).await.unwrap();
+    assert_eq!(client.get_student(&id).await.unwrap().mobile, "1112223334");
+
+    let input = StudentInputV2 {
+        name: NameInputV2 { given: "Ellis".into(), family: Some("Tarmaster".into()) },
+        contacts: vec![
+            ContactV2 { kind: ContactKind::Email, value: "ellis@example.com".into(), primary: true },
+            ContactV2 { kind: ContactKind::Mobile, value: "1234567890".into(), primary: true },
+        ],
+    };
+    let ellis = client.create_student_v2(&input).await.unwrap();
+    assert_eq!(ellis.name.display, "Ellis Tarmaster");
+    assert_eq!(client.get_student_v2(&ellis.id).await.unwrap(), ellis);
+    assert_eq!(client.list_students_v2().await.unwrap().len(), 2);
+
+    let names = client.list_student_fields(&["name"]).await.unwrap();
+    assert!(names.iter().all(|student| student.as_object().unwrap().keys().eq(["name"])));
+
+    client.delete_student_v2(&ellis.id).await.unwrap();
+    client.delete_student(&id).await.unwrap();
+    assert!(matches!(client.get_student(&id).await, Err(Error::NotFound)));
+    // ids are escaped, a slash can not reach another route
+    assert!(matches!(client.get_student_v2("a/../b").await, Err(Error::NotFound)));
+}
+
+#[tokio::test]
+async fn statuses_map_to_errors() {
+    let (url, _dir) = server().await;
+    let client = Client::builder(&url).build().unwrap();
+
+    // v1 takes any email like it always did, v2 checks it
+    let invalid = StudentInputV2 {
+        name: NameInputV2 { given: "Aman".into(), family: None },
+        contacts: vec![
+            ContactV2 { kind: ContactKind::Email, value: "not-an-email".into(), primary: true },
+            ContactV2 { kind: ContactKind::Mobile, value: "9876543210".into(), primary: true },
+        ],
+    };
+    let error = client.create_student_v2(&invalid).await.unwrap_err();
+    assert!(matches!(error, Error::BadRequest(_)), "{:?}", error);
+    assert_eq!(error.status(), Some(400));
+    assert!(matches!(client.list_tenants().await, Err(Error::Unauthorized)));
+    assert!(matches!(client.list_student_fields(&["password"]).await, Err(Error::BadRequest(_))));
+
+    let unreachable = Client::builder("http://127.0.0.1:1").retry(no_retry()).build().unwrap();
+    assert!(matches!(unreachable.healthz().await, Err(Error::Transport(_))));
+    assert!(matches!(Client::builder("not a url").build(), Err(Error::InvalidUrl(_))));
+}
+
+#[tokio::test]
+async fn admin_and_tenant_clients() {
+    let (url, _dir) = server().await;
+    let admin = Client::builder(&url).api_key("admin-key").build().unwrap();
+
+    let north = admin.create_tenant(&NewTenant { name: "north".into(), max_students: Some(1), api_keys: None }).await.unwrap();
+    assert_eq!(admin.list_tenants().await.unwrap(), vec![north.clone()]);
+    assert!(admin.diagnostics().await.unwrap().store.students == 0);
+    assert!(admin.readyz().await.unwrap().ready);
+
+    let client = Client::builder(&url).api_key(&north.api_keys[0]).tenant("north").build().unwrap();
+    client.create_student(&aman()).await.unwrap();
+    assert_eq!(client.list_students().await.unwrap().len(), 1);
+    assert!(admin.list_students().await.unwrap().is_empty());
+    // the quota of one student is reached
+    assert!(matches!(client.create_student(&aman()).await, Err(Error::Forbidden)));
+
+    admin.suspend_tenant("north").await.unwrap();
+    assert!(client.list_students().await.is_err());
+    admin.resume_tenant("north").await.unwrap();
+    admin.delete_tenant("north").await.unwrap();
+    assert!(matches!(admin.get_tenant("north").await, Err(Error::NotFound)));
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried_for_idempotent_requests_only() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = |attempts: Arc<AtomicUsize>| {
+        move || {
+            let attempts = attempts.clone();
+            async move {
+                // fails twice, then works
+                match attempts.fetch_add(1, Ordering::SeqCst) % 3 {
+                    2 => (StatusCode::OK, "ok"),
+                    _ => (StatusCode::SERVICE_UNAVAILABLE, "busy"),
+                }
+            }
+        }
+    };
+    let posts = Arc::new(AtomicUsize::new(0));
+    let router = Router::new()
+        .route("/healthz", get(counter(attempts.clone())))
+        .route("/admin/backups", post(counter(posts.clone())));
+    let url = serve(router).await;
+
+    let retry = Retry { max_retries: 3, base_delay: Duration::from_millis(5), max_delay: Duration::from_millis(20) };
+    let client = Client::builder(&url).retry(retry).build().unwrap();
+    client.healthz().await.unwrap();
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // a POST is sent once, it could have been applied already
+    assert!(matches!(client.create_backup().await, Err(Error::Unavailable(_))));
+    assert_eq!(posts.load(Ordering::SeqCst), 1);
+
+    // with too few retries the last answer is returned
+    attempts.store(0, Ordering::SeqCst);
+    let client = Client::builder(&url).retry(Retry { max_retries: 1, ..retry }).build().unwrap();
+    assert!(matches!(client.healthz().await, Err(Error::Unavailable(body)) if body == "busy"));
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn slow_answers_time_out() {
+    let router = Router::new().route("/healthz", get(|| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        "ok"
+    }));
+    let url = serve(router).await;
+    let client = Client::builder(&url).timeout(Duration::from_millis(100)).retry(no_retry()).build().unwrap();
+    assert!(matches!(client.healthz().await, Err(Error::Timeout)));
+}
+
+#[tokio::test]
+async fn changes_are_watched() {
+    let (url, _dir) = server().await;
+    let client = Client::builder(&url).build().unwrap();
+    let mut watch = client.watch_students().await.unwrap();
+
+    client.create_student(&aman()).await.unwrap();
+    let id = client.list_students().await.unwrap()[0].id.clone();
+    client.delete_student(&id).await.unwrap();
+
+    let Some(Change::Student(created)) = watch.next().await.unwrap() else { panic!("expected a change") };
+    assert_eq!((created.kind, created.student.unwrap().name.display), (ChangeKind::Created, "Aman".to_string()));
+    let Some(Change::Student(deleted)) = watch.next().await.unwrap() else { panic!("expected a change") };
+    assert_eq!((deleted.kind, deleted.id, deleted.student), (ChangeKind::Deleted, id, None));
+}
@@ -0,0 +1,96 @@
+// Tests of the studentctl binary, against a data file and against a running server.
+
+use std::{path::Path, process::Output, sync::Arc, time::Duration};
+use serde_json::Value;
+use studet_api::{app, state::AppState, tenant::{self, NewTenant, Tenants}};
+use tokio::{net::TcpListener, process::Command};
+
+
+async fn studentctl(args: &[&str], stdin: Option<&Path>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_studentctl"));
+    command.args(args).env_remove("STUDENTCTL_FILE").env_remove("MASTER_KEY").env_remove("MASTER_KEY_FILE");
+    if let Some(path) = stdin {
+        command.stdin(std::fs::File::open(path).unwrap());
+    }
+    command.output().await.unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn json(output: &Output) -> Value {
+    serde_json::from_str(&stdout(output)).unwrap()
+}
+
+
+#[tokio::test]
+async fn offline_crud_import_and_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("students.json");
+    let file = file.to_str().unwrap();
+    let with_file = |args: &[&'static str]| [&["--file", file][..], args].concat();
+
+    let created = json(&studentctl(&with_file(&["-o", "json", "create", "--name", "Aman", "--email", "aman@example.com", "--mobile", "9876543210"]), None).await);
+    let id = created["id"].as_str().unwrap().to_string();
+    let table = stdout(&studentctl(&with_file(&["list"]), None).await);
+    assert!(table.starts_with("ID"));
+    assert!(table.lines().nth(1).unwrap().contains("aman@example.com"));
+
+    let updated = studentctl(&[&with_file(&["-o", "csv", "update"])[..], &[id.as_str(), "--mobile", "1112223334"]].concat(), None).await;
+    assert_eq!(stdout(&updated), format!("id,name,email,mobile\n{},Aman,aman@example.com,1112223334\n", id));
+
+    // an export imports again under new ids
+    let export = dir.path().join("export.csv");
+    stdout(&studentctl(&[&with_file(&["export"])[..], &[export.to_str().unwrap()]].concat(), None).await);
+    let imported = studentctl(&with_file(&["import", "-", "--format", "csv"]), Some(&export)).await;
+    assert!(String::from_utf8_lossy(&imported.stderr).contains("imported 1 students"));
+    let listed = json(&studentctl(&with_file(&["-o", "json", "list"]), None).await);
+    assert_eq!(listed.as_array().unwrap().len(), 2);
+    assert_ne!(listed[1]["id"], id.as_str());
+
+    // a broken student in an import adds nothing
+    let broken = dir.path().join("broken.json");
+    std::fs::write(&broken, r#"[{ "name": "Ellis", "email": "ellis@example.com", "mobile": "1234567890" }, { "name": "Bad", "email": "bad", "mobile": "1" }]"#).unwrap();
+    let failed = studentctl(&[&with_file(&["import"])[..], &[broken.to_str().unwrap()]].concat(), None).await;
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("student 2 of the file"));
+    assert_eq!(json(&studentctl(&with_file(&["-o", "json", "list"]), None).await).as_array().unwrap().len(), 2);
+
+    stdout(&studentctl(&[&with_file(&["delete"])[..], &[id.as_str()]].concat(), None).await);
+    assert_eq!(studentctl(&[&with_file(&["get"])[..], &[id.as_str()]].concat(), None).await.status.code(), Some(3));
+    assert_eq!(studentctl(&with_file(&["frobnicate"]), None).await.status.code(), Some(2));
+}
+
+#[tokio::test]
+async fn talks_to_a_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+    let tenants = Arc::new(Tenants::load(dir.path().join("tenants"), Duration::from_secs(60)).unwrap());
+    tenants.create(NewTenant { name: "north".into(), max_students: None, api_keys: None }).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = format!("http://{}", listener.local_addr().unwrap());
+    let router = tenant::scoped(app(state), tenants, Some(Arc::from("admin-key")));
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let created = json(&studentctl(&["--server", &server, "-o", "json", "create", "--name", "Aman", "--email", "aman@example.com", "--mobile", "9876543210"], None).await);
+    let id = created["id"].as_str().unwrap();
+    let fetched = json(&studentctl(&["--server", &server, "-o", "json", "get", id], None).await);
+    assert_eq!(fetched, created);
+    assert_eq!(studentctl(&["--server", &server, "get", "missing"], None).await.status.code(), Some(3));
+
+    // tenants need their key
+    let denied = studentctl(&["--server", &server, "--tenant", "north", "--api-key", "wrong", "list"], None).await;
+    assert_eq!(denied.status.code(), Some(4));
+
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let unreachable = studentctl(&["--server", &format!("http://{}", closed), "list"], None).await;
+    assert_eq!(unreachable.status.code(), Some(5));
+}
+
+#[tokio::test]
+async fn prints_completions() {
+    let script = stdout(&studentctl(&["completions", "bash"], None).await);
+    assert!(script.contains("studentctl") && script.contains("import"));
+}
@@ -0,0 +1,20 @@
+use std::{env, path::PathBuf, process::Command};
+
+
+// Compile proto/student.proto into a descriptor set with the protoc shipped in protoc-bin-vendored,
+// so no protoc needs to be installed. The google.api.http options in it are the routes of the apis.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto");
+    let descriptors = PathBuf::from(env::var("OUT_DIR")?).join("student_descriptor.bin");
+    let status = Command::new(protoc_bin_vendored::protoc_bin_path()?)
+        .arg("--include_imports")
+        .arg(format!("--descriptor_set_out={}", descriptors.display()))
+        .arg("-Iproto")
+        .arg(format!("-I{}", protoc_bin_vendored::include_path()?.display()))
+        .arg("proto/student.proto")
+        .status()?;
+    if !status.success() {
+        return Err(format!("protoc failed with {}", status).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::sync::LazyLock;
+use http::Method;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, Value};
+
+
+/// proto/student.proto with its imports, compiled by build.rs
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/student_descriptor.bin"));
+
+/// the proto files of the service, with the `google.api.http` options of every method
+pub static POOL: LazyLock<DescriptorPool> = LazyLock::new(|| DescriptorPool::decode(FILE_DESCRIPTOR_SET).expect("the descriptor set is generated by build.rs"));
+
+
+/// an HTTP route of a gRPC method, from its `google.api.http` option
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub method: Method,
+    // path template like /v1/students/{id}, the same syntax axum uses
+    pub path: String,
+    // the gRPC path, /student.v1.StudentService/GetStudent
+    pub rpc: String,
+    // the request field the body goes into, "*" for the whole request, `None` without body
+    pub body: Option<String>,
+    pub input: MessageDescriptor,
+    pub output: MessageDescriptor,
+}
+
+impl Binding {
+    /// the name of the method, GetStudent
+    pub fn name(&self) -> &str {
+        self.rpc.rsplit('/').next().unwrap_or_default()
+    }
+}
+
+
+/// the bindings of all unary methods of the student service in `pool`, streaming methods have no
+/// HTTP route. `pool` is `POOL` or a pool compiled from the same proto file, like the one of tonic.
+pub fn bindings(pool: &DescriptorPool) -> Vec<Binding> {
+    let http = pool.get_extension_by_name("google.api.http").expect("student.proto imports google/api/annotations.proto");
+    let service = pool.get_service_by_name("student.v1.StudentService").expect("student.proto defines the service");
+    let mut bindings = Vec::new();
+    for method in service.methods().filter(|m| !m.is_client_streaming() && !m.is_server_streaming()) {
+        let options = method.options();
+        if !options.has_extension(&http) {
+            continue;
+        }
+        let Value::Message(rule) = options.get_extension(&http).into_owned() else { continue };
+        let rpc = format!("/{}/{}", service.full_name(), method.name());
+        let mut rules = vec![rule];
+        while let Some(rule) = rules.pop() {
+            if let Some(Value::List(more)) = rule.get_field_by_name("additional_bindings").map(|v| v.into_owned()) {
+                rules.extend(more.into_iter().filter_map(|v| if let Value::Message(m) = v { Some(m) } else { None }));
+            }
+            let Some((verb, path)) = pattern(&rule) else { continue };
+            let body = text(&rule, "body").filter(|body| !body.is_empty());
+            bindings.push(Binding { method: verb, path, rpc: rpc.clone(), body, input: method.input(), output: method.output() });
+        }
+    }
+    bindings
+}
+
+fn pattern(rule: &DynamicMessage) -> Option<(Method, String)> {
+    for (field, method) in [("get", Method::GET), ("put", Method::PUT), ("post", Method::POST), ("delete", Method::DELETE), ("patch", Method::PATCH)] {
+        if rule.has_field_by_name(field) {
+            return Some((method, text(rule, field)?));
+        }
+    }
+    let Some(Value::Message(custom)) = rule.get_field_by_name("custom").map(|v| v.into_owned()) else { return None };
+    Some((Method::from_bytes(text(&custom, "kind")?.as_bytes()).ok()?, text(&custom, "path")?))
+}
+
+fn text(message: &DynamicMessage, field: &str) -> Option<String> {
+    message.get_field_by_name(field).and_then(|value| value.as_str().map(str::to_string))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_come_from_the_proto() {
+        let routes: Vec<(Method, String, Option<String>)> = bindings(&POOL).into_iter().map(|b| (b.method, b.path, b.body)).collect();
+        assert_eq!(routes.len(), 5, "{:?}", routes);
+        assert!(routes.contains(&(Method::GET, "/v1/students/{id}".into(), None)));
+        assert!(routes.contains(&(Method::POST, "/v1/students".into(), Some("student".into()))));
+        assert!(routes.contains(&(Method::PUT, "/v1/students/{id}".into(), Some("student".into()))));
+        let update = bindings(&POOL).into_iter().find(|b| b.name() == "UpdateStudent").unwrap();
+        assert_eq!(update.rpc, "/student.v1.StudentService/UpdateStudent");
+        assert_eq!(update.input.full_name(), "student.v1.UpdateStudentRequest");
+    }
+}
@@ -0,0 +1,372 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::Duration};
+use aes_gcm::{aead::{Aead, AeadCore, KeyInit, OsRng, Payload}, Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+use crate::{handler, photos, SharedState};
+
+
+/// prefix of every encrypted value in the data file
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// the keys shared by all stores, replaced in place when the key file changes
+pub type SharedKeys = Arc<RwLock<Keys>>;
+
+
+/// a key encrypting the data keys, never used for the student data itself
+pub struct MasterKey {
+    pub id: String,
+    key: [u8; 32],
+}
+
+/// the master keys and the key for the blind indexes.
+///
+/// Every value is encrypted with its own random data key (AES-256-GCM), the data key is stored
+/// next to the value wrapped by the active master key. Older master keys are only kept to
+/// read values until they were encrypted again with the active one.
+pub struct Keys {
+    // oldest first, the last one is active
+    masters: Vec<MasterKey>,
+    // stays the same over rotations, so the blind indexes do not change
+    index_key: [u8; 32],
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io(io::Error),
+    // the key file or variable can not be used
+    Invalid(String),
+    // the value was encrypted with a master key which is not configured
+    UnknownKey(String),
+    // the value was changed or encrypted with a different key
+    Decrypt,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyError::Io(e) => write!(f, "could not read the master key: {}", e),
+            KeyError::Invalid(e) => write!(f, "invalid master key: {}", e),
+            KeyError::UnknownKey(id) => write!(f, "value is encrypted with the unknown master key {:?}", id),
+            KeyError::Decrypt => write!(f, "value could not be decrypted"),
+        }
+    }
+}
+
+impl From<io::Error> for KeyError {
+    fn from(e: io::Error) -> Self {
+        KeyError::Io(e)
+    }
+}
+
+
+/// content of the key file:
+/// `{ "keys": [{ "id": "2026-10", "key": "<base64 of 32 bytes>" }], "index_key": "<base64 of 32 bytes>" }`
+#[derive(Deserialize)]
+struct KeyFile {
+    keys: Vec<KeyEntry>,
+    index_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KeyEntry {
+    id: String,
+    key: String,
+}
+
+fn decode_key(value: &str) -> Result<[u8; 32], KeyError> {
+    let bytes = STANDARD.decode(value.trim()).map_err(|e| KeyError::Invalid(e.to_string()))?;
+    bytes.try_into().map_err(|_| KeyError::Invalid("a key must be 32 bytes".to_string()))
+}
+
+/// a new random key, base64 encoded like the key file and `MASTER_KEY` expect it
+pub fn generate_key() -> String {
+    STANDARD.encode(Aes256Gcm::generate_key(OsRng))
+}
+
+
+impl Keys {
+    /// the keys from the key file, or the single base64 `key`. `None` when neither is configured
+    pub fn configured(file: Option<&Path>, key: Option<&str>) -> Result<Option<Keys>, KeyError> {
+        match (file, key) {
+            (Some(file), _) => Keys::load(file).map(Some),
+            (None, Some(key)) => Keys::single(key).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn load(file: impl AsRef<Path>) -> Result<Keys, KeyError> {
+        let data = fs::read_to_string(file)?;
+        let file: KeyFile = serde_json::from_str(&data).map_err(|e| KeyError::Invalid(e.to_string()))?;
+        let masters = file
+            .keys
+            .into_iter()
+            .map(|entry| Ok(MasterKey { id: entry.id, key: decode_key(&entry.key)? }))
+            .collect::<Result<Vec<_>, KeyError>>()?;
+        let index_key = match file.index_key {
+            Some(key) => Some(decode_key(&key)?),
+            None => None,
+        };
+        Keys::new(masters, index_key)
+    }
+
+    /// one master key given as base64, its id is derived from the key
+    pub fn single(key: &str) -> Result<Keys, KeyError> {
+        let key = decode_key(key)?;
+        let id = hex::encode(&Sha256::digest(key)[..4]);
+        Keys::new(vec![MasterKey { id, key }], None)
+    }
+
+    /// without an explicit index key it is derived from the oldest master key, which must then stay in the key file
+    pub fn new(masters: Vec<MasterKey>, index_key: Option<[u8; 32]>) -> Result<Keys, KeyError> {
+        let first = masters.first().ok_or_else(|| KeyError::Invalid("no master key".to_string()))?;
+        if masters.iter().any(|m| m.id.is_empty() || m.id.contains(':')) {
+            return Err(KeyError::Invalid("key ids must not be empty or contain ':'".to_string()));
+        }
+        let index_key = index_key.unwrap_or_else(|| {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&first.key).expect("hmac accepts keys of any length");
+            mac.update(b"blind index");
+            mac.finalize().into_bytes().into()
+        });
+        Ok(Keys { masters, index_key })
+    }
+
+    pub fn active(&self) -> &MasterKey {
+        self.masters.last().expect("there is always a master key")
+    }
+
+    fn master(&self, id: &str) -> Result<&MasterKey, KeyError> {
+        self.masters.iter().find(|m| m.id == id).ok_or_else(|| KeyError::UnknownKey(id.to_string()))
+    }
+
+    /// `enc:v1:<master key id>:<wrapped data key>:<ciphertext>`, the field name is authenticated with the value
+    pub fn encrypt(&self, field: &str, plaintext: &str) -> String {
+        self.encrypt_bytes(field, plaintext.as_bytes())
+    }
+
+    /// like `encrypt` for binary content, the photo files are encrypted with it
+    pub fn encrypt_bytes(&self, field: &str, plaintext: &[u8]) -> String {
+        let master = self.active();
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let wrapped = seal(&master.key, &data_key, master.id.as_bytes());
+        let sealed = seal(&data_key.into(), plaintext, field.as_bytes());
+        format!("{}{}:{}:{}", ENCRYPTED_PREFIX, master.id, STANDARD.encode(wrapped), STANDARD.encode(sealed))
+    }
+
+    /// the plaintext of a value written by `encrypt`, values without the prefix are returned as they are
+    pub fn decrypt(&self, field: &str, value: &str) -> Result<String, KeyError> {
+        if !value.starts_with(ENCRYPTED_PREFIX) {
+            return Ok(value.to_string());
+        }
+        String::from_utf8(self.decrypt_bytes(field, value)?).map_err(|_| KeyError::Decrypt)
+    }
+
+    /// the plaintext of a value written by `encrypt_bytes`
+    pub fn decrypt_bytes(&self, field: &str, value: &str) -> Result<Vec<u8>, KeyError> {
+        let rest = value.strip_prefix(ENCRYPTED_PREFIX).ok_or(KeyError::Decrypt)?;
+        let mut parts = rest.splitn(3, ':');
+        let (Some(id), Some(wrapped), Some(sealed)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(KeyError::Decrypt);
+        };
+        let master = self.master(id)?;
+        let wrapped = STANDARD.decode(wrapped).map_err(|_| KeyError::Decrypt)?;
+        let data_key: [u8; 32] = open(&master.key, &wrapped, id.as_bytes())?.try_into().map_err(|_| KeyError::Decrypt)?;
+        let sealed = STANDARD.decode(sealed).map_err(|_| KeyError::Decrypt)?;
+        open(&data_key, &sealed, field.as_bytes())
+    }
+
+    /// whether a stored value is plaintext or still encrypted with an older master key
+    pub fn is_stale(&self, value: &str) -> bool {
+        match value.strip_prefix(ENCRYPTED_PREFIX) {
+            Some(rest) => !rest.starts_with(&format!("{}:", self.active().id)),
+            None => true,
+        }
+    }
+
+    /// keyed hash of a value which is the same for the same value, so it can be searched for without decrypting.
+    /// Emails are compared case insensitive.
+    pub fn blind_index(&self, field: &str, value: &str) -> String {
+        let value = value.trim();
+        let normalized = if field == "email" { value.to_lowercase() } else { value.to_string() };
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key).expect("hmac accepts keys of any length");
+        mac.update(field.as_bytes());
+        mac.update(&[0]);
+        mac.update(normalized.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+/// nonce followed by ciphertext
+fn seal(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(OsRng);
+    let ciphertext = cipher.encrypt(&nonce, Payload { msg: plaintext, aad }).expect("encrypting into a vec does not fail");
+    [nonce.as_slice(), &ciphertext].concat()
+}
+
+fn open(key: &[u8; 32], sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, KeyError> {
+    if sealed.len() < 12 {
+        return Err(KeyError::Decrypt);
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad }).map_err(|_| KeyError::Decrypt)
+}
+
+
+/// reload the key file when it changes and encrypt every store again whose file or photos hold
+/// plaintext or values of an older master key. `stores` is asked on every round, so new tenants are included.
+pub fn spawn_rotation(
+    keys: SharedKeys,
+    key_file: Option<PathBuf>,
+    stores: impl Fn() -> Vec<SharedState> + Send + 'static,
+    interval: Duration,
+) -> JoinHandle<()> {
+    let mut last = key_file.as_ref().and_then(|file| handler::read_file(file).ok().flatten()).map(|data| handler::fingerprint(&data));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Some(file) = &key_file {
+                let current = handler::read_file(file).ok().flatten().map(|data| handler::fingerprint(&data));
+                if current != last {
+                    last = current;
+                    match Keys::load(file) {
+                        Ok(loaded) => {
+                            println!("loaded master keys, active key is {}", loaded.active().id);
+                            *keys.write().unwrap() = loaded;
+                        }
+                        Err(e) => eprintln!("keeping the old master keys: {}", e),
+                    }
+                }
+            }
+            for state in stores() {
+                let mut students = state.students.lock().await;
+                match state.reencrypt(&mut students) {
+                    Ok(true) => println!("encrypted {} with the active master key", state.data_file.display()),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("could not encrypt {} again: {}", state.data_file.display(), e),
+                }
+                drop(students);
+                match photos::reencrypt(&state).await {
+                    Ok(0) => {}
+                    Ok(count) => println!("encrypted {} photo files of {} with the active master key", count, state.data_file.display()),
+                    Err(e) => eprintln!("could not encrypt the photos of {} again: {}", state.data_file.display(), e),
+                }
+            }
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(ids: &[&str]) -> Keys {
+        let masters = ids.iter().map(|id| MasterKey { id: id.to_string(), key: decode_key(&generate_key()).unwrap() }).collect();
+        Keys::new(masters, Some([7; 32])).unwrap()
+    }
+
+    #[test]
+    fn values_round_trip_and_are_bound_to_their_field() {
+        let keys = keys(&["k1"]);
+        let encrypted = keys.encrypt("email", "aman@example.com");
+        assert!(encrypted.starts_with("enc:v1:k1:"));
+        assert_ne!(encrypted, keys.encrypt("email", "aman@example.com"));
+        assert_eq!(keys.decrypt("email", &encrypted).unwrap(), "aman@example.com");
+        assert!(matches!(keys.decrypt("mobile", &encrypted), Err(KeyError::Decrypt)));
+        assert_eq!(keys.decrypt("email", "plain@example.com").unwrap(), "plain@example.com");
+    }
+
+    #[test]
+    fn older_master_keys_still_decrypt_but_are_stale() {
+        let old = keys(&["k1"]);
+        let encrypted = old.encrypt("mobile", "9876543210");
+        let rotated = Keys::new(
+            vec![MasterKey { id: "k1".to_string(), key: old.masters[0].key }, MasterKey { id: "k2".to_string(), key: [1; 32] }],
+            Some([7; 32]),
+        )
+        .unwrap();
+
+        assert_eq!(rotated.decrypt("mobile", &encrypted).unwrap(), "9876543210");
+        assert!(rotated.is_stale(&encrypted));
+        assert!(!rotated.is_stale(&rotated.encrypt("mobile", "9876543210")));
+        assert!(matches!(keys(&["k3"]).decrypt("mobile", &encrypted), Err(KeyError::UnknownKey(_))));
+    }
+
+    #[test]
+    fn blind_indexes_survive_rotation() {
+        let (a, b) = (keys(&["k1"]), keys(&["k1", "k2"]));
+        assert_eq!(a.blind_index("email", "Aman@Example.com"), b.blind_index("email", "aman@example.com "));
+        assert_ne!(a.blind_index("email", "aman@example.com"), a.blind_index("mobile", "aman@example.com"));
+    }
+
+    #[tokio::test]
+    async fn data_file_is_encrypted_searchable_and_rotated() {
+        use crate::{model::Student, state::AppState};
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        let k1 = MasterKey { id: "k1".to_string(), key: [1; 32] };
+        let shared: SharedKeys = Arc::new(RwLock::new(Keys::new(vec![k1], Some([7; 32])).unwrap()));
+        let state = AppState::open(&file, Some(shared.clone())).unwrap();
+
+        let aman = Student { id: "a".to_string(), name: "Aman".to_string(), email: "aman@example.com".to_string(), mobile: "9876543210".to_string(), ..Default::default() };
+        let mut students = state.students.lock().await;
+        students.push(aman.clone());
+        state.sync(&mut students).unwrap();
+
+        let data = fs::read(&file).unwrap();
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert!(!text.contains("aman@example.com") && !text.contains("9876543210"));
+        let index = shared.read().unwrap().blind_index("email", "AMAN@example.com");
+        assert_eq!(handler::find_by_index(&data, "email", &index), vec!["a".to_string()]);
+        assert_eq!(AppState::open(&file, Some(shared.clone())).unwrap().students.lock().await.clone(), vec![aman.clone()]);
+        assert!(handler::parse_students(&data, None).is_err());
+
+        // a second master key becomes active, the file is encrypted again and still reads the same
+        let rotated = Keys::new(vec![MasterKey { id: "k1".to_string(), key: [1; 32] }, MasterKey { id: "k2".to_string(), key: [2; 32] }], Some([7; 32]));
+        *shared.write().unwrap() = rotated.unwrap();
+        assert!(state.reencrypt(&mut students).unwrap());
+        assert!(!state.reencrypt(&mut students).unwrap());
+        let text = fs::read_to_string(&file).unwrap();
+        assert!(text.contains("enc:v1:k2:") && !text.contains("enc:v1:k1:"));
+        assert_eq!(handler::find_by_index(text.as_bytes(), "email", &index), vec!["a".to_string()]);
+        let new_keys = Keys::new(vec![MasterKey { id: "k2".to_string(), key: [2; 32] }], Some([7; 32])).unwrap();
+        assert_eq!(handler::parse_students(text.as_bytes(), Some(&new_keys)).unwrap(), vec![aman]);
+    }
+
+    #[tokio::test]
+    async fn a_wrong_key_is_an_error_not_an_empty_store() {
+        use crate::{model::Student, state::{AppState, StoreError}};
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        let keys = |id: &str, key: u8| -> SharedKeys { Arc::new(RwLock::new(Keys::new(vec![MasterKey { id: id.to_string(), key: [key; 32] }], Some([7; 32])).unwrap())) };
+        let right = keys("k1", 1);
+        let student = |id: &str| Student { id: id.to_string(), name: id.to_string(), email: format!("{}@example.com", id), mobile: "9876543210".to_string(), ..Default::default() };
+
+        let state = AppState::open(&file, Some(right.clone())).unwrap();
+        let mut students = state.students.lock().await;
+        students.push(student("aman"));
+        state.sync(&mut students).unwrap();
+        let written = fs::read(&file).unwrap();
+
+        // no key, a key which was rotated out and another key under the same id
+        for wrong in [None, Some(keys("k2", 2)), Some(keys("k1", 9))] {
+            assert!(matches!(AppState::open(&file, wrong), Err(StoreError::Invalid(_))));
+        }
+        assert_eq!(fs::read(&file).unwrap(), written);
+
+        // with the right key a write keeps what was there
+        let state = AppState::open(&file, Some(right.clone())).unwrap();
+        let mut students = state.students.lock().await;
+        students.push(student("bela"));
+        state.sync(&mut students).unwrap();
+        let reopened = AppState::open(&file, Some(right)).unwrap();
+        assert_eq!(reopened.students.lock().await.clone(), vec![student("aman"), student("bela")]);
+    }
+}
@@ -0,0 +1,142 @@
+use std::{fs, hash::{DefaultHasher, Hash, Hasher}, io, path::Path};
+use serde_json::Value;
+use crate::{crypto::{Keys, ENCRYPTED_PREFIX}, model::{assign_ids, Student}, state::StoreError};
+
+
+/// the personal fields which are encrypted when a master key is configured, each with a blind index
+pub const ENCRYPTED_FIELDS: [&str; 2] = ["email", "mobile"];
+
+
+/// get all students from the file, a missing file has none, with the fingerprint of the file.
+/// A file which can not be read, decrypted or parsed is an error - starting with no students
+/// instead would overwrite it with the next change. Students which got an id are written back.
+pub fn load_students(path: &Path, keys: Option<&Keys>) -> Result<(Vec<Student>, Option<u64>), StoreError> {
+    let Some(data) = read_file(path)? else { return Ok((vec![], None)) };
+    let (students, assigned) = read_students(&data, keys).map_err(StoreError::Invalid)?;
+    let fingerprint = if assigned { save_students(path, &students, keys)? } else { fingerprint(&data) };
+    Ok((students, Some(fingerprint)))
+}
+
+
+/// save the vector of students to the file, returns the fingerprint of the written content
+pub fn save_students(path: &Path, students: &[Student], keys: Option<&Keys>) -> io::Result<u64> {
+    let data = encode_students(students, keys)?;
+    fs::write(path, &data)?;
+    Ok(fingerprint(data.as_bytes()))
+}
+
+
+/// the file content for `students`. With keys the personal fields are encrypted and
+/// `<field>_index` holds their blind index, so students can be found without decrypting.
+pub fn encode_students(students: &[Student], keys: Option<&Keys>) -> io::Result<String> {
+    let Some(keys) = keys else { return serde_json::to_string_pretty(students).map_err(io::Error::other) };
+    let mut values = Vec::with_capacity(students.len());
+    for student in students {
+        let mut value = serde_json::to_value(student).map_err(io::Error::other)?;
+        let object = value.as_object_mut().expect("a student is a json object");
+        for field in ENCRYPTED_FIELDS {
+            if let Some(Value::String(plain)) = object.get(field).cloned() {
+                object.insert(format!("{}_index", field), Value::String(keys.blind_index(field, &plain)));
+                object.insert(field.to_string(), Value::String(keys.encrypt(field, &plain)));
+            }
+        }
+        for contact in object.get_mut("contacts").and_then(Value::as_array_mut).into_iter().flatten() {
+            if let Some(Value::String(plain)) = contact.get("value").cloned() {
+                contact["value"] = Value::String(keys.encrypt("contact", &plain));
+            }
+        }
+        values.push(value);
+    }
+    serde_json::to_string_pretty(&values).map_err(io::Error::other)
+}
+
+
+/// the students in the file content with their personal fields decrypted, without validation
+pub fn decode_students(data: &[u8], keys: Option<&Keys>) -> Result<Vec<Student>, String> {
+    let mut values: Vec<Value> = serde_json::from_slice(data).map_err(|e| format!("invalid json: {}", e))?;
+    for value in &mut values {
+        let Some(object) = value.as_object_mut() else { continue };
+        for field in ENCRYPTED_FIELDS {
+            object.remove(&format!("{}_index", field));
+            if let Some(Value::String(stored)) = object.get(field).cloned() {
+                object.insert(field.to_string(), Value::String(decrypt(keys, field, &stored)?));
+            }
+        }
+        for contact in object.get_mut("contacts").and_then(Value::as_array_mut).into_iter().flatten() {
+            if let Some(Value::String(stored)) = contact.get("value").cloned() {
+                contact["value"] = Value::String(decrypt(keys, "contact", &stored)?);
+            }
+        }
+    }
+    serde_json::from_value(Value::Array(values)).map_err(|e| format!("invalid json: {}", e))
+}
+
+fn decrypt(keys: Option<&Keys>, field: &str, stored: &str) -> Result<String, String> {
+    match keys {
+        Some(keys) => keys.decrypt(field, stored).map_err(|e| format!("{}: {}", field, e)),
+        None if stored.starts_with(ENCRYPTED_PREFIX) => Err(format!("{} is encrypted but no master key is configured", field)),
+        None => Ok(stored.to_string()),
+    }
+}
+
+
+/// whether the file content holds personal fields in plaintext or encrypted with an older master key
+pub fn needs_encryption(data: &[u8], keys: &Keys) -> bool {
+    let Ok(values) = serde_json::from_slice::<Vec<Value>>(data) else { return false };
+    values.iter().any(|value| {
+        let fields = ENCRYPTED_FIELDS.iter().filter_map(|field| value.get(*field));
+        let contacts = value.get("contacts").and_then(Value::as_array).into_iter().flatten().filter_map(|c| c.get("value"));
+        fields.chain(contacts).filter_map(Value::as_str).any(|stored| keys.is_stale(stored))
+    })
+}
+
+
+/// ids of the students in the file content whose `field` has the blind index `index`, see `Keys::blind_index`
+pub fn find_by_index(data: &[u8], field: &str, index: &str) -> Vec<String> {
+    let Ok(values) = serde_json::from_slice::<Vec<Value>>(data) else { return vec![] };
+    values
+        .iter()
+        .filter(|value| value.get(format!("{}_index", field)).and_then(Value::as_str) == Some(index))
+        .filter_map(|value| value.get("id").and_then(Value::as_str).map(str::to_string))
+        .collect()
+}
+
+
+/// read the raw content of the file, a missing file is reported as `None`
+pub fn read_file(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+
+/// parse the content of the file, students without a unique id get one
+pub fn parse_students(data: &[u8], keys: Option<&Keys>) -> Result<Vec<Student>, String> {
+    read_students(data, keys).map(|(students, _)| students)
+}
+
+/// like `parse_students`, also tells whether ids were given - the file should be written then
+pub fn read_students(data: &[u8], keys: Option<&Keys>) -> Result<(Vec<Student>, bool), String> {
+    let mut students = decode_students(data, keys)?;
+    let assigned = assign_ids(&mut students);
+    Ok((students, assigned))
+}
+
+
+/// hash of the file content, used to notice when somebody else changed the file
+pub fn fingerprint(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+
+/// check that the directory of the file takes new files, without touching the file itself
+pub fn check_writable(path: &Path) -> io::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let probe = dir.join(format!(".{}.probe", path.file_name().and_then(|name| name.to_str()).unwrap_or("students")));
+    fs::write(&probe, b"probe")?;
+    fs::remove_file(&probe)
+}
@@ -0,0 +1,20 @@
+//! the students of one store: the model, the data file with its encryption, the merge of
+//! edits made outside the server and the changes all api versions make.
+//!
+//! Shared by the REST api (`_16_1_axum`) and the gRPC service (`_17_grpc/_17_1_tonic`), which
+//! also share the schema in `proto/student.proto` - its `google.api.http` options are the
+//! student routes of both.
+
+pub mod bindings;
+pub mod crypto;
+pub mod handler;
+pub mod model;
+pub mod photos;
+pub mod state;
+pub mod students;
+pub mod watcher;
+
+use std::sync::Arc;
+use state::AppState;
+
+pub type SharedState = Arc<AppState>;
@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// a student as it is stored in the file, the api versions in `v1` and `v2` convert from and to it.
+///
+/// The fields added after the first version are left out of the file while they are empty, so
+/// files written by older releases can still be read and look the same.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Student {
+    // for ignore the id field on creating time
+    #[serde(default)]
+    pub id: String,
+    // full name for display
+    pub name: String,
+    // primary email and mobile
+    pub email: String,
+    pub mobile: String,
+    // given and family name, only known for students created or updated through v2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_parts: Option<NameParts>,
+    // further contacts besides the primary email and mobile
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contacts: Vec<Contact>,
+    // the uploaded photo, see `photo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub photo: Option<Photo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NameParts {
+    pub given: String,
+    pub family: Option<String>,
+}
+
+/// the files of a student's photo, every one is named by the sha-256 of its content
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Photo {
+    pub original: String,
+    pub small: String,
+    pub medium: String,
+}
+
+impl Photo {
+    /// the names of all files, the original first
+    pub fn files(&self) -> [&str; 3] {
+        [&self.original, &self.small, &self.medium]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactKind {
+    Email,
+    Mobile,
+    Phone,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub kind: ContactKind,
+    pub value: String,
+}
+
+impl Contact {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.kind {
+            ContactKind::Email if !self.value.contains('@') => Err(format!("email {:?} is not a valid email address", self.value)),
+            ContactKind::Mobile | ContactKind::Phone if !is_phone_number(&self.value) => {
+                Err(format!("number {:?} must contain only digits", self.value))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn is_phone_number(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+}
+
+impl Student {
+    /// check the fields of a single student, the id is not checked here.
+    /// Only students created or replaced through v2 and gRPC are held to this, v1 and the data file
+    /// take every student the first version took.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        Contact { kind: ContactKind::Email, value: self.email.clone() }.validate()?;
+        if !is_phone_number(&self.mobile) {
+            return Err(format!("mobile {:?} must contain only digits", self.mobile));
+        }
+        self.contacts.iter().try_for_each(Contact::validate)
+    }
+}
+
+impl Student {
+    /// replace everything which identifies the person after a request to be forgotten.
+    /// The id and creation time stay, so everything referring to the student still resolves.
+    pub fn pseudonymize(&mut self) {
+        let alias: String = self.id.chars().filter(char::is_ascii_alphanumeric).take(8).collect();
+        self.name = "Erased student".to_string();
+        self.email = format!("erased-{}@erased.invalid", alias);
+        self.mobile = "0000000000".to_string();
+        self.name_parts = None;
+        self.contacts.clear();
+        self.photo = None;
+        self.updated_at = Some(Utc::now());
+    }
+}
+
+/// check a whole list of students as it is stored in a backup - every student needs a unique id
+pub fn validate_students(students: &[Student]) -> Result<(), String> {
+    let mut ids = std::collections::HashSet::new();
+    for (index, student) in students.iter().enumerate() {
+        if student.id.is_empty() {
+            return Err(format!("student at index {} has no id", index));
+        }
+        if !ids.insert(student.id.as_str()) {
+            return Err(format!("duplicate student id {}", student.id));
+        }
+    }
+    Ok(())
+}
+
+/// give every student without an id, or with the id of a student before it, a new id.
+/// Data files of the first version can have those, returns whether any id was given.
+pub fn assign_ids(students: &mut [Student]) -> bool {
+    let mut ids = std::collections::HashSet::new();
+    let mut assigned = false;
+    for student in students {
+        if student.id.is_empty() || ids.contains(&student.id) {
+            student.id = uuid::Uuid::new_v4().to_string();
+            assigned = true;
+        }
+        ids.insert(student.id.clone());
+    }
+    assigned
+}
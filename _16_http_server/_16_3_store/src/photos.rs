@@ -0,0 +1,126 @@
+use std::{collections::HashSet, fs, io::{self, Read}, path::Path as FsPath};
+use sha2::{Digest, Sha256};
+use crate::{crypto::ENCRYPTED_PREFIX, model::Photo, state::AppState, SharedState};
+
+
+// authenticated with every encrypted photo file, like the field name of an encrypted field
+const ENCRYPTED_FIELD: &str = "photo";
+
+
+/// the name of a photo file, the sha-256 of its content
+pub fn hash(file: &[u8]) -> String {
+    hex::encode(Sha256::digest(file))
+}
+
+/// write the stored form of a file, a file of the same name has the same content already
+pub fn store(path: &FsPath, stored: &[u8]) -> io::Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    write(path, stored)
+}
+
+/// how a file is stored: encrypted with the active master key when the store has keys
+pub fn seal(state: &AppState, file: &[u8]) -> Vec<u8> {
+    match &state.keys {
+        Some(keys) => keys.read().unwrap().encrypt_bytes(ENCRYPTED_FIELD, file).into_bytes(),
+        None => file.to_vec(),
+    }
+}
+
+/// the content of a stored file. Files stored before the master key was configured are plaintext.
+pub fn unseal(state: &AppState, stored: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !stored.starts_with(ENCRYPTED_PREFIX.as_bytes()) {
+        return Ok(stored);
+    }
+    let keys = state.keys.as_ref().ok_or_else(|| io::Error::other("the photo is encrypted but no master key is configured"))?;
+    let stored = std::str::from_utf8(&stored).map_err(io::Error::other)?;
+    let file = keys.read().unwrap().decrypt_bytes(ENCRYPTED_FIELD, stored);
+    file.map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// encrypt the photo files of a store again which are plaintext or encrypted with an older
+/// master key, returns how many were written
+pub async fn reencrypt(state: &SharedState) -> io::Result<usize> {
+    let Some(keys) = &state.keys else { return Ok(0) };
+    let current = format!("{}{}:", ENCRYPTED_PREFIX, keys.read().unwrap().active().id);
+    // locked, so `prune` does not remove a file while it is written again
+    let students = state.students.lock().await;
+    let dir = state.photos_dir();
+    let mut written = 0;
+    for name in students.iter().filter_map(|s| s.photo.as_ref()).flat_map(Photo::files) {
+        let path = dir.join(name);
+        // the start of the file is enough to tell, the whole file is only read when it is written again
+        let mut start = Vec::new();
+        match fs::File::open(&path) {
+            Ok(file) => file.take(current.len() as u64).read_to_end(&mut start)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if start == current.as_bytes() {
+            continue;
+        }
+        let file = unseal(state, fs::read(&path)?)?;
+        write(&path, &seal(state, &file))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// written completely or not at all
+fn write(path: &FsPath, file: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, file)?;
+    fs::rename(temporary, path)
+}
+
+/// copy the files of `photo` from the directory `from` to `to` where `to` does not have them yet,
+/// backups keep the photos this way. Returns false when a file is in neither directory.
+pub fn copy_files(photo: &Photo, from: &FsPath, to: &FsPath) -> io::Result<bool> {
+    for name in photo.files() {
+        let target = to.join(name);
+        if target.exists() {
+            continue;
+        }
+        match fs::read(from.join(name)) {
+            Ok(file) => write(&target, &file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// remove the files of the photo directory no student of the store refers to, returns how many
+pub async fn prune(state: &SharedState) -> usize {
+    let students = state.students.lock().await;
+    let used: HashSet<&str> = students
+        .iter()
+        .filter_map(|s| s.photo.as_ref())
+        .flat_map(Photo::files)
+        .collect();
+    let entries = match fs::read_dir(state.photos_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return 0,
+        Err(e) => {
+            eprintln!("could not read the photo directory: {}", e);
+            return 0;
+        }
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        // a file being written by `write`, it is renamed to a used name right after
+        if name.to_str().is_some_and(|name| used.contains(name) || name.ends_with(".tmp")) {
+            continue;
+        }
+        match fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => eprintln!("could not remove the unused photo {}: {}", entry.path().display(), e),
+        }
+    }
+    removed
+}
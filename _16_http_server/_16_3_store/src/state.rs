@@ -0,0 +1,341 @@
+use std::{fmt, io, path::PathBuf, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Mutex as StdMutex}, time::{Duration, Instant}};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Mutex, MutexGuard};
+use crate::{crypto::{Keys, SharedKeys}, handler, model::Student, watcher::{diff, edited_from, merge, merge_stale, Merged}};
+
+
+/// what happened to a student
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// who made the change - a request to the api or somebody editing the file by hand
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    Api,
+    External,
+}
+
+/// event sent to every subscriber of `AppState::events` after a change was stored
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub id: String,
+    // the student after the change, `None` for deleted students
+    pub student: Option<Student>,
+    pub source: ChangeSource,
+}
+
+
+#[derive(Debug)]
+pub enum StoreError {
+    // the file on disk could not be read or written
+    Io(io::Error),
+    // the content of the file is not a valid student list or can not be decrypted with the configured keys
+    Invalid(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "could not access the data file: {}", e),
+            StoreError::Invalid(e) => write!(f, "the data file is invalid: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+
+/// the students of a store behind a lock which counts how often callers had to wait for it
+pub struct StudentsLock {
+    students: Mutex<Vec<Student>>,
+    acquired: AtomicU64,
+    contended: AtomicU64,
+    waited_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+/// numbers about `StudentsLock` since the start, shown in /admin/diagnostics
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockStats {
+    pub acquired: u64,
+    // acquisitions which found the lock taken and had to wait
+    pub contended: u64,
+    pub waited_micros: u64,
+    pub max_wait_micros: u64,
+}
+
+impl StudentsLock {
+    pub fn new(students: Vec<Student>) -> Self {
+        StudentsLock {
+            students: Mutex::new(students),
+            acquired: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            waited_micros: AtomicU64::new(0),
+            max_wait_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn lock(&self) -> MutexGuard<'_, Vec<Student>> {
+        self.acquired.fetch_add(1, Ordering::Relaxed);
+        if let Ok(guard) = self.students.try_lock() {
+            return guard;
+        }
+        let start = Instant::now();
+        let guard = self.students.lock().await;
+        let waited = start.elapsed().as_micros() as u64;
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        self.waited_micros.fetch_add(waited, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(waited, Ordering::Relaxed);
+        guard
+    }
+
+    pub fn stats(&self) -> LockStats {
+        LockStats {
+            acquired: self.acquired.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            waited_micros: self.waited_micros.load(Ordering::Relaxed),
+            max_wait_micros: self.max_wait_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+
+/// how writing the data file went lately
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Persistence {
+    // the last time the students and the data file were brought in line
+    pub last_success: Option<DateTime<Utc>>,
+    // the error of the last sync, `None` when it worked
+    pub error: Option<String>,
+}
+
+
+/// how long a directory found writable is taken as writable, so readiness polls do not
+/// create a probe file every time
+pub const WRITABLE_FOR: Duration = Duration::from_secs(30);
+
+
+/// how many earlier versions of the file are kept to notice a save from one of them
+const HISTORY: usize = 16;
+
+
+/// what the file looked like the last time we read or wrote it
+struct Synced {
+    base: Vec<Student>,
+    fingerprint: Option<u64>,
+    // the versions before `base`, oldest first
+    history: Vec<Vec<Student>>,
+}
+
+impl Synced {
+    /// merge the file content `theirs` into `ours`. A file saved from a version older than `base`,
+    /// like an editor buffer loaded before the last api write, is merged against that version.
+    fn merge(&self, ours: &[Student], theirs: &[Student]) -> Merged {
+        match edited_from(&self.history, &self.base, theirs) {
+            Some(older) => merge_stale(&self.history[older], ours, theirs),
+            None => merge(&self.base, ours, theirs),
+        }
+    }
+
+    /// the file holds `base` now
+    fn advance(&mut self, base: Vec<Student>, fingerprint: u64) {
+        if base != self.base {
+            self.history.push(std::mem::replace(&mut self.base, base));
+            if self.history.len() > HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.fingerprint = Some(fingerprint);
+    }
+}
+
+
+pub struct AppState {
+    pub students: StudentsLock,
+    pub data_file: PathBuf,
+    pub events: broadcast::Sender<ChangeEvent>,
+    // the most students this store may hold, `None` for no limit
+    pub max_students: Option<usize>,
+    // encrypt the personal fields in the data file, plaintext when `None`
+    pub keys: Option<SharedKeys>,
+    // set while a backup is being restored into this store
+    pub recovering: AtomicBool,
+    synced: StdMutex<Synced>,
+    persistence: StdMutex<Persistence>,
+    // when the directory of the data file was last found writable
+    writable: StdMutex<Option<Instant>>,
+}
+
+impl AppState {
+    /// load the students from the data file and remember its content for later change detection.
+    /// Fails when the file exists but can not be read, nothing may be written over it then.
+    pub fn new(data_file: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        AppState::open(data_file, None)
+    }
+
+    /// like `new`, with the personal fields of the data file encrypted by `keys`.
+    /// A missing or wrong master key is an error, not an empty store.
+    pub fn open(data_file: impl Into<PathBuf>, keys: Option<SharedKeys>) -> Result<Self, StoreError> {
+        let data_file = data_file.into();
+        let (students, fingerprint) = handler::load_students(&data_file, keys.as_ref().map(|k| k.read().unwrap()).as_deref())?;
+        let (events, _) = broadcast::channel(256);
+        Ok(AppState {
+            synced: StdMutex::new(Synced { base: students.clone(), fingerprint, history: Vec::new() }),
+            students: StudentsLock::new(students),
+            data_file,
+            events,
+            max_students: None,
+            keys,
+            recovering: AtomicBool::new(false),
+            persistence: StdMutex::new(Persistence::default()),
+            writable: StdMutex::new(None),
+        })
+    }
+
+    /// limit the number of students which can be added through the api
+    pub fn with_quota(mut self, max_students: Option<usize>) -> Self {
+        self.max_students = max_students;
+        self
+    }
+
+    /// where the photos of the students are stored, next to the data file.
+    /// `students.json` keeps its photos in `students.photos/`, every store has its own.
+    pub fn photos_dir(&self) -> PathBuf {
+        self.data_file.with_extension("photos")
+    }
+
+    /// send an event to all subscribers, it is fine if nobody is listening
+    pub fn notify(&self, event: ChangeEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// bring the in-memory students and the data file in line with each other.
+    ///
+    /// Must be called with the `students` lock held. External edits of the file are merged
+    /// into `students` (three-way, using the last synced content as base) and local changes
+    /// are written back. Returns the ids of students which were changed on both sides - for
+    /// those the version from the file is kept and the local change is dropped.
+    pub fn sync(&self, students: &mut Vec<Student>) -> Result<Vec<String>, StoreError> {
+        let result = self.sync_file(students);
+        let mut persistence = self.persistence.lock().unwrap();
+        match &result {
+            Ok(_) => *persistence = Persistence { last_success: Some(Utc::now()), error: None },
+            Err(e) => persistence.error = Some(e.to_string()),
+        }
+        result
+    }
+
+    /// the students `sync` would leave in memory, edits of the file merged in, without writing
+    /// anything or announcing the edits. Must be called with the `students` lock held.
+    pub fn peek(&self, students: &[Student]) -> Result<Vec<Student>, StoreError> {
+        let synced = self.synced.lock().unwrap();
+        match handler::read_file(&self.data_file)? {
+            Some(data) if Some(handler::fingerprint(&data)) != synced.fingerprint => {
+                let theirs = handler::parse_students(&data, self.read_keys().as_deref()).map_err(StoreError::Invalid)?;
+                Ok(synced.merge(students, &theirs).students)
+            }
+            _ => Ok(students.to_vec()),
+        }
+    }
+
+    /// what uniqueness checks and lookups compare for an email: the blind index with keys, else
+    /// the trimmed and lowercased email, so it is the same over key rotations and for any spelling
+    pub fn email_index(&self, email: &str) -> String {
+        match self.read_keys() {
+            Some(keys) => keys.blind_index("email", email),
+            None => email.trim().to_lowercase(),
+        }
+    }
+
+    fn read_keys(&self) -> Option<std::sync::RwLockReadGuard<'_, Keys>> {
+        self.keys.as_ref().map(|keys| keys.read().unwrap())
+    }
+
+    /// write the data file again when it holds personal fields in plaintext or encrypted with
+    /// an older master key, returns whether it was written. Must be called with the `students` lock held.
+    pub fn reencrypt(&self, students: &mut Vec<Student>) -> Result<bool, StoreError> {
+        if self.keys.is_none() {
+            return Ok(false);
+        }
+        self.sync(students)?;
+        let mut synced = self.synced.lock().unwrap();
+        let keys = self.read_keys();
+        let keys = keys.as_deref();
+        match handler::read_file(&self.data_file)? {
+            Some(data) if keys.is_some_and(|keys| handler::needs_encryption(&data, keys)) => {
+                let fingerprint = handler::save_students(&self.data_file, students, keys)?;
+                synced.advance(students.clone(), fingerprint);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// how the last syncs went, see `sync`
+    pub fn persistence(&self) -> Persistence {
+        self.persistence.lock().unwrap().clone()
+    }
+
+    /// check that the data file can be read and its directory written. A sync or probe which
+    /// wrote within `WRITABLE_FOR` counts as writable, only after that a probe file is written.
+    pub fn check_storage(&self) -> io::Result<()> {
+        handler::read_file(&self.data_file)?;
+        let mut writable = self.writable.lock().unwrap();
+        let synced = self.persistence().last_success
+            .and_then(|at| (Utc::now() - at).to_std().ok())
+            .is_some_and(|ago| ago < WRITABLE_FOR);
+        if synced || writable.is_some_and(|at| at.elapsed() < WRITABLE_FOR) {
+            return Ok(());
+        }
+        handler::check_writable(&self.data_file)?;
+        *writable = Some(Instant::now());
+        Ok(())
+    }
+
+    fn sync_file(&self, students: &mut Vec<Student>) -> Result<Vec<String>, StoreError> {
+        let mut synced = self.synced.lock().unwrap();
+        let disk = handler::read_file(&self.data_file)?;
+
+        // a missing file is not treated as an edit, editors may briefly remove it while saving
+        let (mut conflicts, mut stale, mut assigned) = (Vec::new(), false, false);
+        let on_disk = match disk {
+            Some(data) if Some(handler::fingerprint(&data)) != synced.fingerprint => {
+                let theirs;
+                (theirs, assigned) = handler::read_students(&data, self.read_keys().as_deref()).map_err(StoreError::Invalid)?;
+                let merged = synced.merge(students, &theirs);
+                (conflicts, stale) = (merged.conflicts, merged.stale);
+
+                for event in diff(students, &merged.students) {
+                    self.notify(ChangeEvent { source: ChangeSource::External, ..event });
+                }
+                *students = merged.students;
+                synced.advance(theirs.clone(), handler::fingerprint(&data));
+                theirs
+            }
+            _ => synced.base.clone(),
+        };
+
+        // students added to the file without an id got one, it has to be written for them to keep it
+        if assigned || *students != on_disk {
+            let fingerprint = handler::save_students(&self.data_file, students, self.read_keys().as_deref())?;
+            synced.advance(students.clone(), fingerprint);
+        }
+        // the conflicts are reported, an edit of what was written now is merged against it
+        if stale {
+            synced.history.clear();
+        }
+        Ok(conflicts)
+    }
+}
@@ -0,0 +1,161 @@
+use http::StatusCode;
+use uuid::Uuid;
+use crate::{model::Student, photos, state::{ChangeEvent, ChangeKind, ChangeSource, StoreError}, SharedState};
+
+
+/// store a new student under a new id, shared by all api versions. The fields are checked by
+/// the api version, v1 takes what it always took. An email another student has already is
+/// refused with 422 Unprocessable Entity.
+pub async fn create(state: &SharedState, mut student: Student) -> Result<Student, StatusCode> {
+    student.id = Uuid::new_v4().to_string();
+    let max_students = state.max_students;
+    commit(state, |students| {
+        // the quota of the store is used up
+        if max_students.is_some_and(|max| students.len() >= max) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        if email_taken(state, students, &student) {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        students.push(student.clone());
+        Ok(event(ChangeKind::Created, student))
+    })
+    .await
+    .map(|event| event.student.unwrap_or_default())
+}
+
+/// store several new students with a single write of the data file, all of them or none.
+/// Every student gets its own `Created` event.
+pub async fn create_many(state: &SharedState, mut new: Vec<Student>) -> Result<Vec<Student>, StatusCode> {
+    for student in &mut new {
+        student.id = Uuid::new_v4().to_string();
+    }
+    let mut students = state.students.lock().await;
+    state.sync(&mut students).map_err(store_error)?;
+    if state.max_students.is_some_and(|max| students.len() + new.len() > max) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    for (i, student) in new.iter().enumerate() {
+        if email_taken(state, &students, student) || email_taken(state, &new[..i], student) {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+    let mut changed = students.clone();
+    changed.extend(new.iter().cloned());
+    // the ids are new, nobody can have edited them in the file
+    state.sync(&mut changed).map_err(store_error)?;
+    *students = changed;
+    for student in &new {
+        state.notify(event(ChangeKind::Created, student.clone()));
+    }
+    Ok(new)
+}
+
+/// change a stored student with `change`, shared by all api versions
+pub async fn update(state: &SharedState, id: &str, change: impl FnOnce(&mut Student)) -> Result<Student, StatusCode> {
+    commit(state, |students| {
+        let index = students.iter().position(|s| s.id == id).ok_or(StatusCode::NOT_FOUND)?;
+        let mut changed = students[index].clone();
+        change(&mut changed);
+        if email_taken(state, students, &changed) {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        students[index] = changed;
+        Ok(event(ChangeKind::Updated, students[index].clone()))
+    })
+    .await
+    .map(|event| event.student.unwrap_or_default())
+}
+
+/// delete a stored student and its photo, shared by all api versions
+pub async fn remove(state: &SharedState, id: String) -> Result<(), StatusCode> {
+    commit(state, |students| {
+        if !students.iter().any(|s| s.id == id) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        students.retain(|s| s.id != id);
+        Ok(ChangeEvent { kind: ChangeKind::Deleted, id, student: None, source: ChangeSource::Api })
+    })
+    .await?;
+    photos::prune(state).await;
+    Ok(())
+}
+
+/// the students with `email`, compared by `AppState::email_index`
+pub fn find_by_email<'a>(state: &SharedState, students: &'a [Student], email: &str) -> Vec<&'a Student> {
+    let index = state.email_index(email);
+    students.iter().filter(|s| state.email_index(&s.email) == index).collect()
+}
+
+/// whether another student than `student` has its email, 422 Unprocessable Entity for the api
+fn email_taken(state: &SharedState, students: &[Student], student: &Student) -> bool {
+    find_by_email(state, students, &student.email).iter().any(|s| s.id != student.id)
+}
+
+fn event(kind: ChangeKind, student: Student) -> ChangeEvent {
+    ChangeEvent { kind, id: student.id.clone(), student: Some(student), source: ChangeSource::Api }
+}
+
+/// apply `change` to the students and store the result.
+///
+/// Edits of the data file made outside the server are merged in before and after the change,
+/// if the same student was edited in the file meanwhile the change is dropped with 409 Conflict.
+/// The change is made on a copy, the students stay as they were when it can not be written.
+pub async fn commit(state: &SharedState, change: impl FnOnce(&mut Vec<Student>) -> Result<ChangeEvent, StatusCode>) -> Result<ChangeEvent, StatusCode> {
+    let mut students = state.students.lock().await;
+    state.sync(&mut students).map_err(store_error)?;
+    let mut changed = students.clone();
+    let event = change(&mut changed)?;
+    let conflicts = state.sync(&mut changed).map_err(store_error)?;
+    *students = changed;
+    if conflicts.contains(&event.id) {
+        return Err(StatusCode::CONFLICT);
+    }
+    state.notify(event.clone());
+    Ok(event)
+}
+
+/// the status of a store which can not be read or written, the error is logged
+pub fn store_error(e: StoreError) -> StatusCode {
+    eprintln!("{}", e);
+    match e {
+        StoreError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        StoreError::Invalid(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use std::{fs, sync::Arc};
+
+    #[tokio::test]
+    async fn a_change_which_can_not_be_written_is_not_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        let state: SharedState = Arc::new(AppState::new(&file).unwrap());
+        let aman = Student { name: "Aman".into(), email: "aman@example.com".into(), mobile: "9876543210".into(), ..Default::default() };
+        let aman = create(&state, aman).await.unwrap();
+        let mut events = state.events.subscribe();
+
+        // the data file turns into a directory while the change is made
+        let failed = commit(&state, |students| {
+            fs::remove_file(&file).unwrap();
+            fs::create_dir(&file).unwrap();
+            students.clear();
+            Ok(ChangeEvent { kind: ChangeKind::Deleted, id: aman.id.clone(), student: None, source: ChangeSource::Api })
+        })
+        .await;
+        assert_eq!(failed.unwrap_err(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(*state.students.lock().await, vec![aman.clone()]);
+        assert!(events.try_recv().is_err());
+
+        // the next change which can be written is the only one
+        fs::remove_dir(&file).unwrap();
+        let bela = Student { name: "Bela".into(), email: "bela@example.com".into(), mobile: "9876543210".into(), ..Default::default() };
+        let bela = create(&state, bela).await.unwrap();
+        assert_eq!(AppState::new(&file).unwrap().students.lock().await.clone(), vec![aman, bela]);
+    }
+}
@@ -0,0 +1,280 @@
+use std::{collections::{HashMap, HashSet}, time::Duration};
+use tokio::task::JoinHandle;
+use crate::{model::Student, state::{ChangeEvent, ChangeKind, ChangeSource, StoreError}, SharedState};
+
+
+/// result of a three-way merge of two student lists
+pub struct Merged {
+    pub students: Vec<Student>,
+    // ids changed differently on both sides, the version of `theirs` was kept for them
+    pub conflicts: Vec<String>,
+    // `theirs` was saved from an older version than the last one, see `merge_stale`
+    pub stale: bool,
+}
+
+
+/// merge `ours` and `theirs`, which both started from `base`, student by student.
+///
+/// A student changed only on one side takes that side's version, a student changed on both
+/// sides to different values is a conflict. The order of `theirs` is kept and students only
+/// created on our side are appended at the end.
+pub fn merge(base: &[Student], ours: &[Student], theirs: &[Student]) -> Merged {
+    let base_by_id = by_id(base);
+    let ours_by_id = by_id(ours);
+    let theirs_by_id = by_id(theirs);
+
+    let mut students = Vec::new();
+    let mut conflicts = Vec::new();
+    let ids = theirs.iter().chain(ours).chain(base).map(|s| s.id.as_str());
+    let mut seen = HashSet::new();
+
+    for id in ids {
+        if !seen.insert(id) {
+            continue;
+        }
+        let (b, o, t) = (base_by_id.get(id).copied(), ours_by_id.get(id).copied(), theirs_by_id.get(id).copied());
+        let picked = if o == t || o == b {
+            t
+        } else if t == b {
+            o
+        } else {
+            conflicts.push(id.to_string());
+            t
+        };
+        if let Some(student) = picked {
+            students.push(student.clone());
+        }
+    }
+
+    Merged { students, conflicts, stale: false }
+}
+
+/// merge `theirs`, which was saved from the older version `older`, into `ours`.
+///
+/// Like `merge` with `older` as base, and students `theirs` still has as in `older` while ours
+/// changed since are conflicts as well - the file would put them back, ours are kept for them.
+pub fn merge_stale(older: &[Student], ours: &[Student], theirs: &[Student]) -> Merged {
+    let mut merged = merge(older, ours, theirs);
+    let (older_by_id, ours_by_id, theirs_by_id) = (by_id(older), by_id(ours), by_id(theirs));
+    let mut seen = HashSet::new();
+    for id in ours.iter().chain(older).map(|s| s.id.as_str()) {
+        let (b, o, t) = (older_by_id.get(id), ours_by_id.get(id), theirs_by_id.get(id));
+        if seen.insert(id) && t == b && o != b && !merged.conflicts.iter().any(|c| c == id) {
+            merged.conflicts.push(id.to_string());
+        }
+    }
+    merged.stale = true;
+    merged
+}
+
+/// the version of `history` (oldest first) the file content `theirs` was most likely saved from,
+/// the one with the fewest students different from it. `None` when that is `base`, the last version.
+pub fn edited_from(history: &[Vec<Student>], base: &[Student], theirs: &[Student]) -> Option<usize> {
+    let theirs_by_id = by_id(theirs);
+    let differences = |version: &[Student]| {
+        let version_by_id = by_id(version);
+        let ids: HashSet<&str> = version_by_id.keys().chain(theirs_by_id.keys()).copied().collect();
+        ids.into_iter().filter(|id| version_by_id.get(id) != theirs_by_id.get(id)).count()
+    };
+    // newest first, an older version has to be strictly closer
+    let mut closest = (differences(base), None);
+    for (i, version) in history.iter().enumerate().rev() {
+        let count = differences(version);
+        if count < closest.0 {
+            closest = (count, Some(i));
+        }
+    }
+    closest.1
+}
+
+
+/// the events which turn `before` into `after`
+pub fn diff(before: &[Student], after: &[Student]) -> Vec<ChangeEvent> {
+    let before_by_id = by_id(before);
+    let after_by_id = by_id(after);
+    let mut events = Vec::new();
+
+    for student in after {
+        let kind = match before_by_id.get(student.id.as_str()) {
+            None => ChangeKind::Created,
+            Some(old) if *old != student => ChangeKind::Updated,
+            Some(_) => continue,
+        };
+        events.push(ChangeEvent { kind, id: student.id.clone(), student: Some(student.clone()), source: ChangeSource::Api });
+    }
+    for student in before {
+        if !after_by_id.contains_key(student.id.as_str()) {
+            events.push(ChangeEvent { kind: ChangeKind::Deleted, id: student.id.clone(), student: None, source: ChangeSource::Api });
+        }
+    }
+    events
+}
+
+
+fn by_id(students: &[Student]) -> HashMap<&str, &Student> {
+    students.iter().map(|s| (s.id.as_str(), s)).collect()
+}
+
+
+/// check the data file for edits made outside the server every `interval` and merge them in
+pub fn spawn(state: SharedState, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // remember the last problem so a broken file is only reported once
+        let mut last_error = None;
+        loop {
+            ticker.tick().await;
+            let mut students = state.students.lock().await;
+            match state.sync(&mut students) {
+                Ok(conflicts) => {
+                    for id in conflicts {
+                        eprintln!("student {} was changed in the file and by the api at the same time, check the data file", id);
+                    }
+                    last_error = None;
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    if last_error.as_ref() != Some(&message) {
+                        match e {
+                            StoreError::Invalid(_) => eprintln!("ignoring edit of {}: {}", state.data_file.display(), message),
+                            StoreError::Io(_) => eprintln!("{}", message),
+                        }
+                        last_error = Some(message);
+                    }
+                }
+            }
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use std::sync::Arc;
+
+    fn student(id: &str, name: &str) -> Student {
+        Student { id: id.to_string(), name: name.to_string(), email: format!("{}@example.com", id), mobile: "1234567890".to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn merge_takes_changes_from_both_sides() {
+        let base = vec![student("a", "Alice"), student("b", "Bob")];
+        let ours = vec![student("a", "Alice"), student("b", "Bob"), student("c", "Carol")];
+        let theirs = vec![student("a", "Alicia")];
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.students, vec![student("a", "Alicia"), student("c", "Carol")]);
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn merge_reports_conflicts_and_keeps_theirs() {
+        let base = vec![student("a", "Alice")];
+        let ours = vec![student("a", "Alice Api")];
+        let theirs = vec![student("a", "Alice File")];
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.students, theirs);
+        assert_eq!(merged.conflicts, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn diff_lists_created_updated_and_deleted() {
+        let before = vec![student("a", "Alice"), student("b", "Bob")];
+        let after = vec![student("a", "Alicia"), student("c", "Carol")];
+
+        let kinds: Vec<_> = diff(&before, &after).into_iter().map(|e| (e.kind, e.id)).collect();
+        assert_eq!(kinds, vec![
+            (ChangeKind::Updated, "a".to_string()),
+            (ChangeKind::Created, "c".to_string()),
+            (ChangeKind::Deleted, "b".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn external_edit_is_merged_and_announced() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        crate::handler::save_students(&file, &[student("a", "Alice")], None).unwrap();
+        let state = Arc::new(AppState::new(&file).unwrap());
+        let mut events = state.events.subscribe();
+
+        std::fs::write(&file, serde_json::to_string(&[student("a", "Alicia")]).unwrap()).unwrap();
+        let mut students = state.students.lock().await;
+        assert!(state.sync(&mut students).unwrap().is_empty());
+
+        assert_eq!(*students, vec![student("a", "Alicia")]);
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.kind, event.source), (ChangeKind::Updated, ChangeSource::External));
+    }
+
+    #[tokio::test]
+    async fn invalid_edit_is_rejected_and_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        crate::handler::save_students(&file, &[student("a", "Alice")], None).unwrap();
+        let state = Arc::new(AppState::new(&file).unwrap());
+
+        std::fs::write(&file, "[{ \"id\": \"a\", \"name\": ").unwrap();
+        let mut students = state.students.lock().await;
+        students.push(student("b", "Bob"));
+        assert!(matches!(state.sync(&mut students), Err(StoreError::Invalid(_))));
+
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "[{ \"id\": \"a\", \"name\": ");
+    }
+
+    #[tokio::test]
+    async fn a_student_added_without_an_id_gets_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        crate::handler::save_students(&file, &[student("a", "Alice")], None).unwrap();
+        let state = Arc::new(AppState::new(&file).unwrap());
+
+        std::fs::write(&file, "[{ \"id\": \"a\", \"name\": \"Alice\", \"email\": \"\", \"mobile\": \"\" }, { \"name\": \"Bob\", \"email\": \"\", \"mobile\": \"\" }]").unwrap();
+        let mut students = state.students.lock().await;
+        assert!(state.sync(&mut students).unwrap().is_empty());
+
+        let on_disk = crate::handler::parse_students(&std::fs::read(&file).unwrap(), None).unwrap();
+        assert_eq!(*students, on_disk);
+        assert!(!students[1].id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn saving_a_file_loaded_before_an_api_write_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("students.json");
+        crate::handler::save_students(&file, &[student("a", "Alice"), student("b", "Bob")], None).unwrap();
+        let state = Arc::new(AppState::new(&file).unwrap());
+
+        // an editor loads the file, then the api changes Alice
+        let buffer = std::fs::read_to_string(&file).unwrap();
+        crate::students::update(&state, "a", |s| s.name = "Alice Api".to_string()).await.unwrap();
+
+        // the editor saves its buffer with Bob renamed
+        std::fs::write(&file, buffer.replace("\"Bob\"", "\"Bobby\"")).unwrap();
+        let mut students = state.students.lock().await;
+        assert_eq!(state.sync(&mut students).unwrap(), vec!["a".to_string()]);
+        assert_eq!(*students, vec![student("a", "Alice Api"), student("b", "Bobby")]);
+        let text = std::fs::read_to_string(&file).unwrap();
+        assert!(text.contains("Alice Api") && text.contains("Bobby"));
+
+        // the next edit of the file as it is now is merged as usual
+        std::fs::write(&file, text.replace("Alice Api", "Alice")).unwrap();
+        assert!(state.sync(&mut students).unwrap().is_empty());
+        assert_eq!(students[0].name, "Alice");
+    }
+
+    #[test]
+    fn the_closest_version_is_the_base_of_an_edit() {
+        let v0 = vec![student("a", "Alice"), student("b", "Bob")];
+        let v1 = vec![student("a", "Alice Api"), student("b", "Bob")];
+        // edited from the last version
+        assert_eq!(edited_from(std::slice::from_ref(&v0), &v1, &[student("a", "Alice Api"), student("b", "Bobby")]), None);
+        // edited from the one before
+        assert_eq!(edited_from(std::slice::from_ref(&v0), &v1, &[student("a", "Alice"), student("b", "Bobby")]), Some(0));
+        // as close to both, the last version wins
+        assert_eq!(edited_from(&[v0], &v1, &[student("a", "Alicia"), student("b", "Bob")]), None);
+    }
+}
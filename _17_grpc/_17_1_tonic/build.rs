@@ -0,0 +1,17 @@
+use std::{env, path::PathBuf};
+
+
+// Generate the tonic server and client from the student.proto of the store crate, which the REST
+// api builds its routes from too, with the protoc shipped in protoc-bin-vendored so no protoc
+// needs to be installed. The descriptor set is served by reflection.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // SAFETY: the build script sets it before starting any thread
+    unsafe { env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    let proto = PathBuf::from("../../_16_http_server/_16_3_store/proto");
+    let includes = [proto.clone(), protoc_bin_vendored::include_path()?];
+    let descriptors = PathBuf::from(env::var("OUT_DIR")?).join("student_descriptor.bin");
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(descriptors)
+        .compile_protos(&[proto.join("student.proto")], &includes)?;
+    Ok(())
+}
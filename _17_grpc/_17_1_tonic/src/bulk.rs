@@ -0,0 +1,53 @@
+use chrono::Utc;
+use studet_api::{api, model::Student, SharedState};
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+use crate::{convert, error, pb};
+
+
+/// how many students are stored with one write of the data file
+pub const CHUNK: usize = 100;
+
+
+/// store the students of `inputs` chunk by chunk.
+///
+/// The next input is only read after the current chunk is stored, so a fast client is slowed
+/// down by HTTP/2 flow control instead of piling up students in memory. A chunk which can not
+/// be stored (e.g. the quota is used up) is reported as an error for each of its students.
+pub async fn create(state: &SharedState, mut inputs: impl Stream<Item = Result<pb::StudentInput, Status>> + Unpin) -> Result<pb::BulkCreateResponse, Status> {
+    let mut response = pb::BulkCreateResponse::default();
+    let mut chunk = Vec::with_capacity(CHUNK);
+    let mut index = 0;
+    while let Some(input) = inputs.next().await {
+        match student(input?) {
+            Ok(student) => chunk.push((index, student)),
+            Err(message) => response.errors.push(pb::BulkError { index, message }),
+        }
+        index += 1;
+        if chunk.len() == CHUNK {
+            store(state, &mut chunk, &mut response).await;
+        }
+    }
+    store(state, &mut chunk, &mut response).await;
+    response.errors.sort_by_key(|e| e.index);
+    Ok(response)
+}
+
+fn student(input: pb::StudentInput) -> Result<Student, String> {
+    let student = convert::input(Some(input)).map_err(|e| e.message().to_string())?;
+    Ok(Student { created_at: Some(Utc::now()), ..student })
+}
+
+async fn store(state: &SharedState, chunk: &mut Vec<(u32, Student)>, response: &mut pb::BulkCreateResponse) {
+    if chunk.is_empty() {
+        return;
+    }
+    let (indices, students): (Vec<u32>, Vec<Student>) = chunk.drain(..).unzip();
+    match api::create_many(state, students).await {
+        Ok(stored) => response.students.extend(stored.iter().map(convert::student)),
+        Err(code) => {
+            let message = error::status(code).message().to_string();
+            response.errors.extend(indices.into_iter().map(|index| pb::BulkError { index, message: message.clone() }));
+        }
+    }
+}
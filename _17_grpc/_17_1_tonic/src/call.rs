@@ -0,0 +1,207 @@
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::time::{timeout_at, Instant};
+use tonic::{metadata::MetadataMap, service::Interceptor, Code, Request, Response, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+use uuid::Uuid;
+use crate::error;
+
+
+/// metadata carrying the id of a call, taken from the client or generated, and sent back
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// deadline of unary calls when the client sends none
+pub const DEFAULT_DEADLINE: Duration = Duration::from_secs(10);
+/// the longest deadline a unary call may have, longer ones are cut
+pub const MAX_DEADLINE: Duration = Duration::from_secs(60);
+// a call gives up this much before the client does, so the client still gets the answer
+const MARGIN: Duration = Duration::from_millis(5);
+
+
+/// what the interceptor found out about a call, the handlers take it from the request extensions
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub request_id: String,
+    // when a unary call has to be answered by
+    pub deadline: Instant,
+    // the deadline the client sent, streams are not limited without one
+    pub client_deadline: Option<Instant>,
+}
+
+
+/// interceptor for the student service: checks the bearer token, gives every call a request id
+/// and works out its deadline from the `grpc-timeout` the client sent.
+#[derive(Clone)]
+pub struct Guard {
+    tokens: Arc<[String]>,
+    default_deadline: Duration,
+    max_deadline: Duration,
+}
+
+impl Guard {
+    /// accept calls with one of `tokens` as bearer token, every call when there are none
+    pub fn new(tokens: Vec<String>) -> Self {
+        Guard { tokens: tokens.into(), default_deadline: DEFAULT_DEADLINE, max_deadline: MAX_DEADLINE }
+    }
+
+    /// deadline of unary calls without one and the longest one a client may ask for
+    pub fn with_deadlines(mut self, default: Duration, max: Duration) -> Self {
+        self.default_deadline = default;
+        self.max_deadline = max;
+        self
+    }
+
+    fn inspect(&self, metadata: &MetadataMap) -> Result<Call, Status> {
+        let received = Instant::now();
+        let request_id = metadata
+            .get(REQUEST_ID)
+            .and_then(|id| id.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= 128)
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let mut call = Call { request_id, deadline: received + self.default_deadline.min(self.max_deadline), client_deadline: None };
+        match grpc_timeout(metadata) {
+            Ok(None) => {}
+            Ok(Some(timeout)) => {
+                call.deadline = received + timeout.saturating_sub(MARGIN).min(self.max_deadline);
+                call.client_deadline = Some(received + timeout);
+            }
+            Err(()) => return Err(call.error(error::invalid("grpc-timeout", "grpc-timeout is not a valid timeout"))),
+        }
+        Ok(call)
+    }
+}
+
+impl Interceptor for Guard {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let call = self.inspect(request.metadata())?;
+        if !self.tokens.is_empty() {
+            let token = request.metadata().get("authorization").and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer "));
+            match token {
+                None => return Err(call.error(error::unauthenticated("MISSING_TOKEN"))),
+                Some(token) if !self.tokens.iter().any(|t| t == token) => return Err(call.error(error::unauthenticated("INVALID_TOKEN"))),
+                Some(_) => {}
+            }
+        }
+        request.extensions_mut().insert(call);
+        Ok(request)
+    }
+}
+
+
+impl Call {
+    /// the call of a request, a new one with the default deadline when no `Guard` looked at it
+    pub fn of<T>(request: &Request<T>) -> Call {
+        request.extensions().get::<Call>().cloned().unwrap_or_else(|| Call {
+            request_id: Uuid::new_v4().to_string(),
+            deadline: Instant::now() + DEFAULT_DEADLINE,
+            client_deadline: None,
+        })
+    }
+
+    /// run a unary call until its deadline. Work which is cut off has not changed anything, the
+    /// store functions only wait while acquiring the lock.
+    pub async fn unary<T>(&self, work: impl Future<Output = Result<T, Status>>) -> Result<Response<T>, Status> {
+        self.respond(timeout_at(self.deadline, work).await.unwrap_or_else(|_| Err(error::deadline_exceeded())))
+    }
+
+    /// like `unary` for calls which may take long, only a deadline of the client limits them
+    pub async fn long<T>(&self, work: impl Future<Output = Result<T, Status>>) -> Result<Response<T>, Status> {
+        let Some(deadline) = self.client_deadline else { return self.respond(work.await) };
+        let deadline = deadline.checked_sub(MARGIN).unwrap_or(deadline);
+        self.respond(timeout_at(deadline, work).await.unwrap_or_else(|_| Err(error::deadline_exceeded())))
+    }
+
+    /// the response for `result`, tagged with the request id
+    pub fn respond<T>(&self, result: Result<T, Status>) -> Result<Response<T>, Status> {
+        let mut response = Response::new(result.map_err(|status| self.error(status))?);
+        if let Ok(id) = self.request_id.parse() {
+            response.metadata_mut().insert(REQUEST_ID, id);
+        }
+        Ok(response)
+    }
+
+    /// add the request id to an error, as metadata and as `RequestInfo` detail
+    pub fn error(&self, status: Status) -> Status {
+        if status.code() == Code::Internal {
+            eprintln!("request {}: {}", self.request_id, status.message());
+        }
+        let mut details: ErrorDetails = status.get_error_details();
+        details.set_request_info(self.request_id.clone(), "");
+        let mut status = Status::with_error_details(status.code(), status.message(), details);
+        if let Ok(id) = self.request_id.parse() {
+            status.metadata_mut().insert(REQUEST_ID, id);
+        }
+        status
+    }
+}
+
+
+/// the `grpc-timeout` of a call, see https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md
+fn grpc_timeout(metadata: &MetadataMap) -> Result<Option<Duration>, ()> {
+    let Some(value) = metadata.get("grpc-timeout") else { return Ok(None) };
+    let value = value.to_str().map_err(|_| ())?;
+    if value.len() < 2 || value.len() > 9 {
+        return Err(());
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: u64 = amount.parse().map_err(|_| ())?;
+    let timeout = match unit {
+        "H" => Duration::from_secs(amount * 3600),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return Err(()),
+    };
+    Ok(Some(timeout))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(metadata: &[(&'static str, &str)]) -> Request<()> {
+        let mut request = Request::new(());
+        for (key, value) in metadata {
+            request.metadata_mut().insert(*key, value.parse().unwrap());
+        }
+        request
+    }
+
+    #[test]
+    fn tokens() {
+        let mut guard = Guard::new(vec!["secret".into()]);
+        let missing = guard.call(request(&[])).unwrap_err();
+        assert_eq!(missing.code(), Code::Unauthenticated);
+        assert_eq!(missing.get_error_details().error_info().unwrap().reason, "MISSING_TOKEN");
+        let wrong = guard.call(request(&[("authorization", "Bearer guess"), (REQUEST_ID, "abc")])).unwrap_err();
+        assert_eq!(wrong.get_error_details().request_info().unwrap().request_id, "abc");
+        assert_eq!(wrong.metadata().get(REQUEST_ID).unwrap(), "abc");
+        assert!(guard.call(request(&[("authorization", "Bearer secret")])).is_ok());
+
+        let mut open = Guard::new(Vec::new());
+        assert!(open.call(request(&[])).is_ok());
+    }
+
+    #[test]
+    fn deadlines() {
+        let mut guard = Guard::new(Vec::new()).with_deadlines(Duration::from_secs(5), Duration::from_secs(20));
+        let call = |request: Request<()>| Call::of(&request);
+
+        let start = Instant::now();
+        let default = call(guard.call(request(&[])).unwrap());
+        assert!(default.client_deadline.is_none());
+        assert!(default.deadline >= start + Duration::from_secs(5) && default.deadline < start + Duration::from_secs(6));
+
+        let short = call(guard.call(request(&[("grpc-timeout", "200m")])).unwrap());
+        assert!(short.deadline < start + Duration::from_millis(200));
+        let long = call(guard.call(request(&[("grpc-timeout", "2H")])).unwrap());
+        assert!(long.deadline < start + Duration::from_secs(21));
+        assert!(long.client_deadline.unwrap() > start + Duration::from_secs(7000));
+
+        assert_eq!(guard.call(request(&[("grpc-timeout", "12x")])).unwrap_err().code(), Code::InvalidArgument);
+        assert!(!call(guard.call(request(&[])).unwrap()).request_id.is_empty());
+    }
+}
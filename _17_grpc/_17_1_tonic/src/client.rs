@@ -0,0 +1,365 @@
+use std::{
+    future::Future,
+    error::Error,
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Weak},
+    time::Duration,
+};
+use tokio::{task::JoinSet, time::{sleep, timeout}};
+use tonic::{transport::{Channel, Endpoint}, Code, Request, Response, Status};
+use tonic_health::pb::{health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest};
+use tonic_types::StatusExt;
+use crate::{call::REQUEST_ID, pb::{self, student_service_client::StudentServiceClient}};
+
+
+/// how the next server is picked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// one after the other
+    RoundRobin,
+    /// the one with the fewest calls running, one after the other when they are even
+    LeastLoaded,
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub policy: Policy,
+    /// tries of an idempotent call, the first one included
+    pub attempts: u32,
+    /// wait before the second try, doubled for every further one
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// start another try of a read on another server when the first is not answered in time
+    pub hedge_after: Option<Duration>,
+    /// time for one try
+    pub timeout: Duration,
+    /// how often every server is asked for its health
+    pub health_interval: Duration,
+    /// sent as `authorization: Bearer {token}`
+    pub token: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            policy: Policy::RoundRobin,
+            attempts: 3,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            hedge_after: None,
+            timeout: Duration::from_secs(10),
+            health_interval: Duration::from_secs(1),
+            token: None,
+        }
+    }
+}
+
+
+/// a client of the student service on several servers with the same students.
+///
+/// Servers which are not serving by the health service, or failed a call as unavailable, are
+/// left out until their health check passes again. Reads, updates and deletes are tried again
+/// on another server when a server is unavailable or too slow; creates only when the server
+/// refused the connection, as a second try of a create the server got could add the student twice.
+#[derive(Clone)]
+pub struct StudentClient {
+    backends: Arc<[Arc<Backend>]>,
+    settings: Arc<Settings>,
+    next: Arc<AtomicUsize>,
+}
+
+struct Backend {
+    url: String,
+    client: StudentServiceClient<Channel>,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+// the local guard of a try waits this much longer than the deadline sent with the request
+const DEADLINE_MARGIN: Duration = Duration::from_millis(100);
+
+// how often a call may be sent
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    // only when the request did not reach the server
+    Unsent,
+    Idempotent,
+    // idempotent, NOT_FOUND after a try which may have deleted already counts as deleted
+    Delete,
+    Hedged,
+}
+
+
+impl StudentClient {
+    /// a client of the servers at `urls`, connections are made on first use.
+    ///
+    /// Must be called inside a tokio runtime, the health checks run until the client is dropped.
+    /// Panics without any url.
+    pub fn new(urls: &[String], settings: Settings) -> Result<Self, tonic::transport::Error> {
+        assert!(!urls.is_empty(), "a client needs at least one server");
+        let mut backends = Vec::with_capacity(urls.len());
+        for url in urls {
+            let channel = Endpoint::from_shared(url.clone())?.connect_timeout(settings.timeout).connect_lazy();
+            let backend = Arc::new(Backend {
+                url: url.clone(),
+                client: StudentServiceClient::new(channel.clone()),
+                healthy: AtomicBool::new(true),
+                in_flight: AtomicUsize::new(0),
+            });
+            tokio::spawn(check_health(Arc::downgrade(&backend), HealthClient::new(channel), settings.health_interval));
+            backends.push(backend);
+        }
+        Ok(StudentClient { backends: backends.into(), settings: Arc::new(settings), next: Arc::new(AtomicUsize::new(0)) })
+    }
+
+    /// the servers which are used
+    pub fn healthy(&self) -> Vec<String> {
+        self.backends.iter().filter(|b| b.healthy.load(Ordering::Relaxed)).map(|b| b.url.clone()).collect()
+    }
+
+    pub async fn get_student(&self, message: pb::GetStudentRequest) -> Result<Response<pb::Student>, Status> {
+        self.call(Retry::Hedged, message, |mut c, r| async move { c.get_student(r).await }).await
+    }
+
+    pub async fn list_students(&self, message: pb::ListStudentsRequest) -> Result<Response<pb::ListStudentsResponse>, Status> {
+        self.call(Retry::Hedged, message, |mut c, r| async move { c.list_students(r).await }).await
+    }
+
+    pub async fn create_student(&self, message: pb::CreateStudentRequest) -> Result<Response<pb::Student>, Status> {
+        self.call(Retry::Unsent, message, |mut c, r| async move { c.create_student(r).await }).await
+    }
+
+    /// replaces the student, so sending it twice does no harm
+    pub async fn update_student(&self, message: pb::UpdateStudentRequest) -> Result<Response<pb::Student>, Status> {
+        self.call(Retry::Idempotent, message, |mut c, r| async move { c.update_student(r).await }).await
+    }
+
+    /// a second try after the first one deleted the student answers NOT_FOUND, which is taken as
+    /// done when the earlier try got to a server
+    pub async fn delete_student(&self, message: pb::DeleteStudentRequest) -> Result<Response<pb::DeleteStudentResponse>, Status> {
+        self.call(Retry::Delete, message, |mut c, r| async move { c.delete_student(r).await }).await
+    }
+
+    /// the client of the next server, for the streaming calls which are not tried again
+    pub fn pick(&self) -> StudentServiceClient<Channel> {
+        self.backends[self.choose(&[])].client.clone()
+    }
+
+    /// send `message` with `send`, to other servers as far as `retry` allows. All tries carry
+    /// the same request id.
+    async fn call<T, R, F, Fut>(&self, retry: Retry, message: T, send: F) -> Result<Response<R>, Status>
+    where
+        T: Clone + Send + 'static,
+        R: Default + Send + 'static,
+        F: Fn(StudentServiceClient<Channel>, Request<T>) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = Result<Response<R>, Status>> + Send,
+    {
+        let attempts = self.settings.attempts.max(1);
+        let hedge_after = self.settings.hedge_after.filter(|_| retry == Retry::Hedged);
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let mut running = JoinSet::new();
+        let (mut started, mut tried, mut last_error) = (0, Vec::new(), None);
+        // a failed try which got to a server may still have been carried out
+        let mut maybe_done = false;
+        loop {
+            if running.is_empty() {
+                if started == attempts {
+                    return Err(last_error.expect("every try failed"));
+                }
+                if let Some(error) = &last_error {
+                    sleep(self.backoff(started, error)).await;
+                }
+                self.start(&mut running, &mut tried, &request_id, message.clone(), send.clone());
+                started += 1;
+            }
+
+            let hedge = async {
+                match hedge_after {
+                    Some(after) if started < attempts => sleep(after).await,
+                    _ => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                Some(done) = running.join_next() => {
+                    let (index, result, sent) = done.map_err(|e| Status::internal(format!("a try did not finish: {}", e)))?;
+                    match result {
+                        Ok(response) => return Ok(response),
+                        Err(status) => {
+                            if matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded) {
+                                self.backends[index].healthy.store(false, Ordering::Relaxed);
+                            }
+                            if retry == Retry::Delete && maybe_done && status.code() == Code::NotFound {
+                                return Ok(Response::new(R::default()));
+                            }
+                            if !retryable(&status) || (retry == Retry::Unsent && sent) {
+                                return Err(status);
+                            }
+                            // ABORTED is the service turning the call down
+                            maybe_done |= sent && status.code() != Code::Aborted;
+                            last_error = Some(status);
+                        }
+                    }
+                }
+                _ = hedge => {
+                    self.start(&mut running, &mut tried, &request_id, message.clone(), send.clone());
+                    started += 1;
+                }
+            }
+        }
+    }
+
+    /// start a try on the next server
+    fn start<T, R, F, Fut>(&self, running: &mut Tries<R>, tried: &mut Vec<usize>, request_id: &str, message: T, send: F)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(StudentServiceClient<Channel>, Request<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Response<R>, Status>> + Send,
+    {
+        let index = self.choose(tried);
+        tried.push(index);
+        let backend = self.backends[index].clone();
+        let mut request = Request::new(message);
+        request.set_timeout(self.settings.timeout);
+        request.metadata_mut().insert(REQUEST_ID, request_id.parse().expect("a uuid is a valid header"));
+        if let Some(Ok(value)) = self.settings.token.as_ref().map(|token| format!("Bearer {}", token).parse()) {
+            request.metadata_mut().insert("authorization", value);
+        }
+        // a little longer than the deadline of the request, so tonic usually ends the try first
+        let limit = self.settings.timeout + DEADLINE_MARGIN;
+        running.spawn(async move {
+            backend.in_flight.fetch_add(1, Ordering::Relaxed);
+            // also counts down when a hedged try is cancelled
+            let _running = InFlight(&backend.in_flight);
+            // the server ends the call at the deadline, one which does not answer at all is left here
+            let late = || Status::deadline_exceeded(format!("{} did not answer in time", backend.url));
+            let result = match timeout(limit, send(backend.client.clone(), request)).await {
+                Ok(Err(status)) if caused_by(&status, |e: &std::io::Error| e.kind() == std::io::ErrorKind::ConnectionRefused) => {
+                    let unreached = Status::unavailable(format!("{} refused the connection: {}", backend.url, status.message()));
+                    return (index, Err(unreached), false);
+                }
+                // the deadline of the request passed, tonic reports it as cancelled
+                Ok(Err(status)) if caused_by(&status, |_: &tonic::TimeoutExpired| true) => Err(late()),
+                // tonic reports some failures to connect as cancelled
+                Ok(Err(status)) if status.source().is_some_and(|e| e.is::<tonic::transport::Error>()) => {
+                    Err(Status::unavailable(format!("{} can not be reached: {}", backend.url, status.message())))
+                }
+                Ok(result) => result,
+                Err(_) => Err(late()),
+            };
+            (index, result, true)
+        });
+    }
+
+    /// the server for the next try: a healthy one not tried yet, else one not tried yet, else any
+    fn choose(&self, tried: &[usize]) -> usize {
+        let count = self.backends.len();
+        let first = self.next.fetch_add(1, Ordering::Relaxed);
+        let order = (0..count).map(|i| (first + i) % count);
+        let healthy = |i: &usize| self.backends[*i].healthy.load(Ordering::Relaxed);
+        let fresh = |i: &usize| !tried.contains(i);
+        let candidates: Vec<usize> = match order.clone().filter(|i| healthy(i) && fresh(i)).collect::<Vec<_>>() {
+            healthy if !healthy.is_empty() => healthy,
+            _ => match order.clone().filter(fresh).collect::<Vec<_>>() {
+                fresh if !fresh.is_empty() => fresh,
+                _ => order.collect(),
+            },
+        };
+        match self.settings.policy {
+            Policy::RoundRobin => candidates[0],
+            // min_by_key keeps the first of equals, so even loads go round robin
+            Policy::LeastLoaded => *candidates.iter().min_by_key(|i| self.backends[**i].in_flight.load(Ordering::Relaxed)).expect("there is a server"),
+        }
+    }
+
+    /// the wait before try `started + 1`, as long as the server asks for if it says so
+    fn backoff(&self, started: u32, error: &Status) -> Duration {
+        let doubled = self.settings.backoff.saturating_mul(2u32.saturating_pow(started.saturating_sub(1)));
+        let asked = error.get_error_details().retry_info().and_then(|info| info.retry_delay);
+        asked.unwrap_or(doubled).min(self.settings.max_backoff)
+    }
+}
+
+// the running tries of a call: the server, the result and whether the request was sent
+type Tries<R> = JoinSet<(usize, Result<Response<R>, Status>, bool)>;
+
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// errors where another try may succeed, on another server or a bit later
+fn retryable(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded | Code::Aborted)
+}
+
+/// whether the failure of a call comes from an error of type `E` which passes `is`
+fn caused_by<E: Error + 'static>(status: &Status, is: impl Fn(&E) -> bool) -> bool {
+    let mut source = status.source();
+    while let Some(error) = source {
+        if error.downcast_ref::<E>().is_some_and(&is) {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// ask the health service of the server every `interval` until the client is dropped
+async fn check_health(backend: Weak<Backend>, mut health: HealthClient<Channel>, interval: Duration) {
+    let request = || HealthCheckRequest { service: crate::pb::student_service_server::SERVICE_NAME.to_string() };
+    loop {
+        let serving = matches!(
+            timeout(interval, health.check(request())).await,
+            Ok(Ok(response)) if response.get_ref().status() == ServingStatus::Serving
+        );
+        let Some(backend) = backend.upgrade() else { return };
+        backend.healthy.store(serving, Ordering::Relaxed);
+        drop(backend);
+        sleep(interval).await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(urls: usize, policy: Policy) -> StudentClient {
+        let urls: Vec<String> = (0..urls).map(|i| format!("http://127.0.0.1:{}", 1 + i)).collect();
+        StudentClient::new(&urls, Settings { policy, health_interval: Duration::from_secs(3600), ..Default::default() }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn choosing() {
+        let round_robin = client(3, Policy::RoundRobin);
+        let picked: Vec<usize> = (0..4).map(|_| round_robin.choose(&[])).collect();
+        assert_eq!(picked, [0, 1, 2, 0]);
+        // unhealthy and tried servers come last
+        round_robin.backends[2].healthy.store(false, Ordering::Relaxed);
+        let picked: Vec<usize> = (0..3).map(|_| round_robin.choose(&[1])).collect();
+        assert_eq!(picked, [0, 0, 0]);
+        assert_eq!(round_robin.choose(&[0, 1]), 2);
+        assert!(round_robin.choose(&[0, 1, 2]) < 3);
+
+        let least_loaded = client(3, Policy::LeastLoaded);
+        least_loaded.backends[0].in_flight.store(2, Ordering::Relaxed);
+        least_loaded.backends[1].in_flight.store(1, Ordering::Relaxed);
+        assert_eq!(least_loaded.choose(&[]), 2);
+        assert_eq!(least_loaded.choose(&[2]), 1);
+    }
+
+    #[tokio::test]
+    async fn backoff() {
+        let client = client(1, Policy::RoundRobin);
+        let unavailable = Status::unavailable("down");
+        let waits: Vec<Duration> = (1..=6).map(|started| client.backoff(started, &unavailable)).collect();
+        assert_eq!(waits.iter().map(Duration::as_millis).collect::<Vec<_>>(), [50, 100, 200, 400, 800, 1000]);
+        // the 1s of the RetryInfo the service sends
+        assert_eq!(client.backoff(1, &crate::error::status(axum::http::StatusCode::CONFLICT)), Duration::from_secs(1));
+        assert!(retryable(&unavailable));
+        assert!(!retryable(&Status::not_found("nobody")));
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use studet_api::{
+    model::{ContactKind, Student},
+    state::{ChangeEvent, ChangeKind},
+    v2::{ContactV2, NameInputV2, StudentInputV2, StudentV2},
+};
+use tonic::Status;
+use crate::{error, pb};
+
+
+/// a stored student as the proto shows it, the same way v2 of the REST api does
+pub fn student(student: &Student) -> pb::Student {
+    let student = StudentV2::from(student);
+    pb::Student {
+        id: student.id,
+        name: Some(pb::Name { given: student.name.given, family: student.name.family, display: student.name.display }),
+        contacts: student.contacts.into_iter().map(contact).collect(),
+        created_at: student.created_at.map(timestamp),
+        updated_at: student.updated_at.map(timestamp),
+    }
+}
+
+/// a change of a student as watchers get it
+pub fn event(event: &ChangeEvent) -> pb::StudentEvent {
+    let kind = match event.kind {
+        ChangeKind::Created => pb::ChangeKind::Created,
+        ChangeKind::Updated => pb::ChangeKind::Updated,
+        ChangeKind::Deleted => pb::ChangeKind::Deleted,
+    };
+    pb::StudentEvent { kind: kind.into(), id: event.id.clone(), student: event.student.as_ref().map(student) }
+}
+
+/// the student to store for an input, without id and timestamps
+pub fn input(input: Option<pb::StudentInput>) -> Result<Student, Status> {
+    let input = input.ok_or_else(|| error::invalid("student", "student is required"))?;
+    let name = input.name.ok_or_else(|| error::invalid("student.name", "student.name is required"))?;
+    let contacts = input
+        .contacts
+        .into_iter()
+        .map(|c| {
+            let kind = match pb::ContactKind::try_from(c.kind) {
+                Ok(pb::ContactKind::Email) => ContactKind::Email,
+                Ok(pb::ContactKind::Mobile) => ContactKind::Mobile,
+                Ok(pb::ContactKind::Phone) => ContactKind::Phone,
+                _ => return Err(error::invalid("student.contacts", format!("unknown contact kind {}", c.kind))),
+            };
+            Ok(ContactV2 { kind, value: c.value, primary: c.primary })
+        })
+        .collect::<Result<_, _>>()?;
+    StudentInputV2 { name: NameInputV2 { given: name.given, family: name.family }, contacts }
+        .into_student()
+        .map_err(|e| error::invalid("student.contacts", e))
+}
+
+fn contact(contact: ContactV2) -> pb::Contact {
+    let kind = match contact.kind {
+        ContactKind::Email => pb::ContactKind::Email,
+        ContactKind::Mobile => pb::ContactKind::Mobile,
+        ContactKind::Phone => pb::ContactKind::Phone,
+    };
+    pb::Contact { kind: kind.into(), value: contact.value, primary: contact.primary }
+}
+
+fn timestamp(at: DateTime<Utc>) -> Timestamp {
+    Timestamp { seconds: at.timestamp(), nanos: at.timestamp_subsec_nanos() as i32 }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(kind: pb::ContactKind, value: &str) -> pb::Contact {
+        pb::Contact { kind: kind.into(), value: value.into(), primary: false }
+    }
+
+    #[test]
+    fn input_and_back() {
+        let stored = input(Some(pb::StudentInput {
+            name: Some(pb::NameInput { given: "Aman".into(), family: Some("Verasia".into()) }),
+            contacts: vec![
+                contact(pb::ContactKind::Phone, "0201234567"),
+                contact(pb::ContactKind::Email, "aman@example.com"),
+                contact(pb::ContactKind::Mobile, "9876543210"),
+            ],
+        }))
+        .unwrap();
+        assert_eq!((stored.name.as_str(), stored.email.as_str(), stored.mobile.as_str()), ("Aman Verasia", "aman@example.com", "9876543210"));
+
+        let shown = student(&Student { created_at: DateTime::from_timestamp(1_700_000_000, 5), ..stored });
+        assert_eq!(shown.name.unwrap().display, "Aman Verasia");
+        assert_eq!(shown.contacts.len(), 3);
+        assert!(shown.contacts[0].primary && shown.contacts[0].kind == pb::ContactKind::Email as i32);
+        assert_eq!(shown.created_at, Some(Timestamp { seconds: 1_700_000_000, nanos: 5 }));
+        assert_eq!(shown.updated_at, None);
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(input(None).unwrap_err().code(), tonic::Code::InvalidArgument);
+        let unspecified = pb::StudentInput {
+            name: Some(pb::NameInput { given: "Aman".into(), family: None }),
+            contacts: vec![contact(pb::ContactKind::Unspecified, "aman@example.com")],
+        };
+        assert_eq!(input(Some(unspecified)).unwrap_err().code(), tonic::Code::InvalidArgument);
+        let no_mobile = pb::StudentInput {
+            name: Some(pb::NameInput { given: "Aman".into(), family: None }),
+            contacts: vec![contact(pb::ContactKind::Email, "aman@example.com")],
+        };
+        assert_eq!(input(Some(no_mobile)).unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
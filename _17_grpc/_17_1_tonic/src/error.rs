@@ -0,0 +1,97 @@
+use std::{collections::HashMap, time::Duration};
+use axum::http::StatusCode;
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+
+
+/// domain of the `ErrorInfo` detail of every error
+pub const DOMAIN: &str = "student.v1";
+
+
+/// the gRPC status for an error of the shared store functions, with details a client can act on
+pub fn status(code: StatusCode) -> Status {
+    let (code, message, mut details) = match code {
+        StatusCode::BAD_REQUEST => (Code::InvalidArgument, "the student is not valid", info("INVALID_STUDENT")),
+        StatusCode::NOT_FOUND => (Code::NotFound, "no such student", info("STUDENT_NOT_FOUND")),
+        // the quota of the store is used up
+        StatusCode::FORBIDDEN => {
+            let mut details = info("QUOTA_EXCEEDED");
+            details.add_quota_failure_violation("students", "the store holds the maximum number of students");
+            (Code::ResourceExhausted, "the store holds the maximum number of students", details)
+        }
+        // the data file was edited meanwhile, the client may try again
+        StatusCode::CONFLICT => (Code::Aborted, "the student was changed in the data file meanwhile", info("EDIT_CONFLICT")),
+        StatusCode::SERVICE_UNAVAILABLE => (Code::Unavailable, "the store is not available", info("STORE_UNAVAILABLE")),
+        StatusCode::UNPROCESSABLE_ENTITY => (Code::AlreadyExists, "another student has this email", info("EMAIL_TAKEN")),
+        _ => (Code::Internal, "could not store the change", info("STORE_FAILED")),
+    };
+    if matches!(code, Code::Aborted | Code::Unavailable) {
+        details.set_retry_info(Some(Duration::from_secs(1)));
+    }
+    Status::with_error_details(code, message, details)
+}
+
+/// a field of the request which is missing or wrong
+pub fn invalid(field: &str, message: impl Into<String>) -> Status {
+    let message = message.into();
+    let mut details = info("INVALID_STUDENT");
+    details.add_bad_request_violation(field, message.clone());
+    Status::with_error_details(Code::InvalidArgument, message, details)
+}
+
+pub fn not_found(id: &str) -> Status {
+    let mut details = info("STUDENT_NOT_FOUND");
+    details.set_resource_info("student.v1.Student", id, "", "no student with this id");
+    Status::with_error_details(Code::NotFound, format!("no student with id {}", id), details)
+}
+
+/// `reason` is MISSING_TOKEN or INVALID_TOKEN
+pub fn unauthenticated(reason: &str) -> Status {
+    let message = match reason {
+        "MISSING_TOKEN" => "send a token as `authorization: Bearer {token}`",
+        _ => "the token is not valid",
+    };
+    Status::with_error_details(Code::Unauthenticated, message, info(reason))
+}
+
+pub fn deadline_exceeded() -> Status {
+    Status::with_error_details(Code::DeadlineExceeded, "the deadline of the call has passed", info("DEADLINE_EXCEEDED"))
+}
+
+fn info(reason: &str) -> ErrorDetails {
+    ErrorDetails::with_error_info(reason, DOMAIN, HashMap::new())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_codes() {
+        let codes = [
+            (StatusCode::BAD_REQUEST, Code::InvalidArgument),
+            (StatusCode::NOT_FOUND, Code::NotFound),
+            (StatusCode::FORBIDDEN, Code::ResourceExhausted),
+            (StatusCode::CONFLICT, Code::Aborted),
+            (StatusCode::SERVICE_UNAVAILABLE, Code::Unavailable),
+            (StatusCode::UNPROCESSABLE_ENTITY, Code::AlreadyExists),
+            (StatusCode::INTERNAL_SERVER_ERROR, Code::Internal),
+        ];
+        for (http, grpc) in codes {
+            assert_eq!(status(http).code(), grpc);
+        }
+    }
+
+    #[test]
+    fn details() {
+        let quota = status(StatusCode::FORBIDDEN).get_error_details();
+        assert_eq!(quota.error_info().unwrap().reason, "QUOTA_EXCEEDED");
+        assert_eq!(quota.quota_failure().unwrap().violations[0].subject, "students");
+        assert!(status(StatusCode::CONFLICT).get_error_details().retry_info().is_some());
+
+        let invalid = invalid("student.name", "a name is required").get_error_details();
+        let violation = &invalid.bad_request().unwrap().field_violations[0];
+        assert_eq!((violation.field.as_str(), violation.description.as_str()), ("student.name", "a name is required"));
+    }
+}
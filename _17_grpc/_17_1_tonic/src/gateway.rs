@@ -0,0 +1,294 @@
+use std::{collections::HashMap, sync::{Arc, LazyLock}};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query},
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{on, MethodFilter, MethodRouter},
+    Json, Router,
+};
+use http_body_util::BodyExt;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, Kind, MessageDescriptor};
+use serde_json::{json, Map, Value as JsonValue};
+use tonic::{service::Routes, Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+use tower::ServiceExt;
+use crate::{call::REQUEST_ID, pb};
+
+// the routes of the `google.api.http` options, the REST api builds its routes from them too
+pub use student_store::bindings::Binding;
+
+
+/// the proto files of the service, with the `google.api.http` options of every method
+static POOL: LazyLock<DescriptorPool> = LazyLock::new(|| DescriptorPool::decode(pb::FILE_DESCRIPTOR_SET).expect("the descriptor set is generated by build.rs"));
+
+// headers passed on to the gRPC service as metadata
+const FORWARDED: [&str; 3] = ["authorization", REQUEST_ID, "grpc-timeout"];
+
+
+/// the bindings of all unary methods of the student service, from the descriptors tonic serves
+pub fn bindings() -> Vec<Binding> {
+    student_store::bindings::bindings(&POOL)
+}
+
+
+/// the HTTP/JSON routes of all bindings, forwarding to the gRPC services in `grpc`
+pub fn router(grpc: Routes) -> Router {
+    let mut paths: HashMap<String, MethodRouter> = HashMap::new();
+    for binding in bindings() {
+        let filter = MethodFilter::try_from(binding.method.clone()).expect("google.api.http only has methods axum knows");
+        let path = binding.path.clone();
+        let (binding, grpc) = (Arc::new(binding), grpc.clone());
+        let handler = on(filter, move |params: Option<Path<HashMap<String, String>>>, Query(query): Query<Vec<(String, String)>>, headers: HeaderMap, body: Bytes| {
+            forward(binding, grpc, params.map(|Path(p)| p).unwrap_or_default(), query, headers, body)
+        });
+        let route = match paths.remove(&path) {
+            Some(existing) => existing.merge(handler),
+            None => handler,
+        };
+        paths.insert(path, route);
+    }
+    paths.into_iter().fold(Router::new(), |router, (path, route)| router.route(&path, route))
+}
+
+/// `grpc` with the gateway routes added, so one port serves gRPC and HTTP/JSON
+pub fn add(grpc: Routes) -> Routes {
+    let gateway = router(grpc.clone());
+    Routes::from(grpc.into_axum_router().merge(gateway))
+}
+
+
+/// call the gRPC method of `binding` with the request built from path, query and body
+async fn forward(binding: Arc<Binding>, grpc: Routes, params: HashMap<String, String>, query: Vec<(String, String)>, headers: HeaderMap, body: Bytes) -> Response {
+    let message = match request_message(&binding, params, query, &body) {
+        Ok(message) => message,
+        Err(e) => return error_response(&Status::invalid_argument(e), None),
+    };
+
+    // a gRPC message is framed with a compression flag and its length
+    let message = message.encode_to_vec();
+    let mut frame = Vec::with_capacity(message.len() + 5);
+    frame.push(0);
+    frame.extend((message.len() as u32).to_be_bytes());
+    frame.extend(message);
+    let mut request = Request::post(&binding.rpc)
+        .header(header::CONTENT_TYPE, "application/grpc")
+        .header(header::TE, "trailers")
+        .body(Body::from(frame))
+        .expect("the rpc path is a valid uri");
+    for name in FORWARDED {
+        if let Some(value) = headers.get(name) {
+            request.headers_mut().insert(name, value.clone());
+        }
+    }
+
+    let Ok(response) = grpc.oneshot(request).await;
+    let (parts, body) = response.into_parts();
+    let (trailers, data) = match body.collect().await {
+        Ok(collected) => (collected.trailers().cloned(), collected.to_bytes()),
+        Err(e) => return error_response(&Status::internal(format!("the gRPC call failed: {}", e)), None),
+    };
+    let request_id = parts.headers.get(REQUEST_ID).or_else(|| trailers.as_ref().and_then(|t| t.get(REQUEST_ID))).cloned();
+    // a call which fails right away has the status in the headers
+    let status = Status::from_header_map(&parts.headers).or_else(|| trailers.as_ref().and_then(Status::from_header_map));
+    match status {
+        Some(status) if status.code() != Code::Ok => error_response(&status, request_id),
+        _ => match decode(&binding.output, &data) {
+            Ok(message) => with_request_id(Json(message).into_response(), request_id),
+            Err(e) => error_response(&Status::internal(e), request_id),
+        },
+    }
+}
+
+/// the request message, parsed with the proto3 JSON mapping from one object of body, path and query
+fn request_message(binding: &Binding, params: HashMap<String, String>, query: Vec<(String, String)>, body: &[u8]) -> Result<DynamicMessage, String> {
+    let mut fields = Map::new();
+    if let Some(field) = &binding.body {
+        let body: JsonValue = if body.is_empty() { json!({}) } else { serde_json::from_slice(body).map_err(|e| format!("the body is not valid json: {}", e))? };
+        if field == "*" {
+            let JsonValue::Object(body) = body else { return Err("the body must be a json object".to_string()) };
+            fields = body;
+        } else {
+            fields.insert(field.clone(), body);
+        }
+    }
+    for (name, value) in params.into_iter().chain(query) {
+        set(&binding.input, &mut fields, &name, value)?;
+    }
+    DynamicMessage::deserialize(binding.input.clone(), JsonValue::Object(fields)).map_err(|e| e.to_string())
+}
+
+/// set the field at `path` ("a.b" for nested fields) of the json object to a value from the url
+fn set(message: &MessageDescriptor, fields: &mut Map<String, JsonValue>, path: &str, value: String) -> Result<(), String> {
+    let (name, rest) = match path.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    };
+    let field = message
+        .get_field_by_name(name)
+        .or_else(|| message.get_field_by_json_name(name))
+        .ok_or_else(|| format!("{} has no field {}", message.full_name(), name))?;
+    match (rest, field.kind()) {
+        (Some(rest), Kind::Message(inner)) => {
+            let entry = fields.entry(field.name().to_string()).or_insert_with(|| json!({}));
+            let JsonValue::Object(nested) = entry else { return Err(format!("{} is set twice", name)) };
+            set(&inner, nested, rest, value)
+        }
+        (Some(_), _) => Err(format!("{} has no fields", name)),
+        (None, kind) => {
+            // numbers may be strings in proto3 json, booleans may not
+            let value = match kind {
+                Kind::Bool => JsonValue::Bool(value.parse().map_err(|_| format!("{} must be true or false", name))?),
+                _ => JsonValue::String(value),
+            };
+            if field.is_list() {
+                let entry = fields.entry(field.name().to_string()).or_insert_with(|| json!([]));
+                entry.as_array_mut().ok_or_else(|| format!("{} is set twice", name))?.push(value);
+            } else {
+                fields.insert(field.name().to_string(), value);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// the response message of a gRPC response body
+fn decode(output: &MessageDescriptor, data: &[u8]) -> Result<DynamicMessage, String> {
+    match data {
+        [0, length @ ..] if length.len() >= 4 => {
+            let size = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+            let message = length.get(4..4 + size).ok_or("the gRPC response is cut off")?;
+            DynamicMessage::decode(output.clone(), message).map_err(|e| e.to_string())
+        }
+        _ => Err("the gRPC response holds no message".to_string()),
+    }
+}
+
+
+/// the error as `google.rpc.Status` in json, with the HTTP status of its code
+fn error_response(status: &Status, request_id: Option<HeaderValue>) -> Response {
+    let body = json!({
+        "code": status.code() as i32,
+        "message": status.message(),
+        "details": details(&status.get_error_details()),
+    });
+    with_request_id((http_status(status.code()), Json(body)).into_response(), request_id)
+}
+
+fn with_request_id(mut response: Response, request_id: Option<HeaderValue>) -> Response {
+    if let Some(id) = request_id {
+        response.headers_mut().insert(REQUEST_ID, id);
+    }
+    response
+}
+
+/// the HTTP status for a gRPC code, as grpc-gateway maps them
+pub fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::Cancelled => StatusCode::from_u16(499).expect("499 is a valid status"),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// the error details the service sends, in the json mapping of their `google.rpc` messages
+fn details(details: &ErrorDetails) -> Vec<JsonValue> {
+    let kind = |name: &str| format!("type.googleapis.com/google.rpc.{}", name);
+    let mut list = Vec::new();
+    if let Some(info) = details.error_info() {
+        list.push(json!({ "@type": kind("ErrorInfo"), "reason": info.reason, "domain": info.domain, "metadata": info.metadata }));
+    }
+    if let Some(bad_request) = details.bad_request() {
+        let violations: Vec<JsonValue> = bad_request.field_violations.iter().map(|v| json!({ "field": v.field, "description": v.description })).collect();
+        list.push(json!({ "@type": kind("BadRequest"), "fieldViolations": violations }));
+    }
+    if let Some(quota) = details.quota_failure() {
+        let violations: Vec<JsonValue> = quota.violations.iter().map(|v| json!({ "subject": v.subject, "description": v.description })).collect();
+        list.push(json!({ "@type": kind("QuotaFailure"), "violations": violations }));
+    }
+    if let Some(retry) = details.retry_info().and_then(|retry| retry.retry_delay) {
+        list.push(json!({ "@type": kind("RetryInfo"), "retryDelay": format!("{}s", retry.as_secs_f64()) }));
+    }
+    if let Some(resource) = details.resource_info() {
+        list.push(json!({
+            "@type": kind("ResourceInfo"),
+            "resourceType": resource.resource_type,
+            "resourceName": resource.resource_name,
+            "owner": resource.owner,
+            "description": resource.description,
+        }));
+    }
+    if let Some(request) = details.request_info() {
+        list.push(json!({ "@type": kind("RequestInfo"), "requestId": request.request_id, "servingData": request.serving_data }));
+    }
+    list
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+
+    fn binding(rpc: &str) -> Binding {
+        bindings().into_iter().find(|b| b.rpc.ends_with(rpc)).unwrap()
+    }
+
+    #[test]
+    fn bindings_come_from_the_proto() {
+        let routes: Vec<(Method, String, Option<String>)> = bindings().into_iter().map(|b| (b.method, b.path, b.body)).collect();
+        assert_eq!(routes.len(), 5, "{:?}", routes);
+        assert!(routes.contains(&(Method::GET, "/v1/students/{id}".into(), None)));
+        assert!(routes.contains(&(Method::POST, "/v1/students".into(), Some("student".into()))));
+        assert!(routes.contains(&(Method::PUT, "/v1/students/{id}".into(), Some("student".into()))));
+        assert_eq!(binding("/UpdateStudent").rpc, "/student.v1.StudentService/UpdateStudent");
+    }
+
+    #[test]
+    fn request_from_path_and_body() {
+        let update = binding("/UpdateStudent");
+        let body = br#"{ "name": { "given": "Aman" }, "contacts": [{ "kind": "CONTACT_KIND_EMAIL", "value": "aman@example.com", "primary": true }] }"#;
+        let message = request_message(&update, HashMap::from([("id".into(), "42".into())]), Vec::new(), body).unwrap();
+        let request = pb::UpdateStudentRequest::decode(message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(request.id, "42");
+        let student = request.student.unwrap();
+        assert_eq!(student.name.unwrap().given, "Aman");
+        assert_eq!(student.contacts[0].kind(), pb::ContactKind::Email);
+
+        // unknown fields and wrong types are rejected
+        assert!(request_message(&update, HashMap::new(), Vec::new(), br#"{ "nickname": "A" }"#).is_err());
+        assert!(request_message(&update, HashMap::new(), vec![("unknown".into(), "1".into())], b"").is_err());
+        assert!(request_message(&binding("/CreateStudent"), HashMap::new(), Vec::new(), b"{ not json").is_err());
+    }
+
+    #[test]
+    fn responses_use_the_canonical_json_mapping() {
+        let student = pb::Student {
+            id: "42".into(),
+            name: Some(pb::Name { given: "Aman".into(), family: None, display: "Aman".into() }),
+            contacts: vec![pb::Contact { kind: pb::ContactKind::Mobile.into(), value: "9876543210".into(), primary: true }],
+            created_at: Some(prost_types::Timestamp { seconds: 1_700_000_000, nanos: 0 }),
+            updated_at: None,
+        };
+        let mut frame = vec![0];
+        frame.extend((student.encoded_len() as u32).to_be_bytes());
+        frame.extend(student.encode_to_vec());
+        let json = serde_json::to_value(decode(&binding("/GetStudent").output, &frame).unwrap()).unwrap();
+        assert_eq!(json, json!({
+            "id": "42",
+            "name": { "given": "Aman", "display": "Aman" },
+            "contacts": [{ "kind": "CONTACT_KIND_MOBILE", "value": "9876543210", "primary": true }],
+            "createdAt": "2023-11-14T22:13:20Z",
+        }));
+    }
+}
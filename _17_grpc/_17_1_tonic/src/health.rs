@@ -0,0 +1,23 @@
+use std::time::Duration;
+use studet_api::{health::readiness, SharedState};
+use tonic::server::NamedService;
+use tonic_health::{server::HealthReporter, ServingStatus};
+use crate::{pb::student_service_server::StudentServiceServer, Students};
+
+
+/// keep the `grpc.health.v1` status of the student service and of the whole server ("") in line
+/// with `/readyz` of the REST api, checked every `interval`
+pub fn spawn(state: SharedState, reporter: HealthReporter, interval: Duration) {
+    tokio::spawn(async move {
+        let mut last = None;
+        loop {
+            let status = if readiness(&state).ready { ServingStatus::Serving } else { ServingStatus::NotServing };
+            if last != Some(status) {
+                reporter.set_service_status(<StudentServiceServer<Students> as NamedService>::NAME, status).await;
+                reporter.set_service_status("", status).await;
+                last = Some(status);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
@@ -0,0 +1,140 @@
+pub mod bulk;
+pub mod call;
+pub mod client;
+pub mod convert;
+pub mod error;
+pub mod gateway;
+pub mod health;
+pub mod watch;
+
+use std::time::Duration;
+use chrono::Utc;
+use studet_api::{api, model::Student, v2, SharedState};
+use tonic::{service::{interceptor::InterceptedService, Routes}, Request, Response, Status, Streaming};
+use call::{Call, Guard};
+use pb::student_service_server::{StudentService, StudentServiceServer};
+
+
+/// the messages and the generated server and client of student.proto in the store crate
+pub mod pb {
+    tonic::include_proto!("student.v1");
+
+    /// the compiled proto files, served by reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("student_descriptor");
+}
+
+
+/// the gRPC StudentService on a store of the REST api. Writes go through the same functions
+/// as the REST handlers, so validation, quota, persistence and change events are the same.
+pub struct Students {
+    state: SharedState,
+}
+
+/// the service to add to a `tonic::transport::Server`, without a `Guard` every call is accepted
+pub fn service(state: SharedState) -> StudentServiceServer<Students> {
+    StudentServiceServer::new(Students { state })
+}
+
+/// the student service behind `guard`, health and reflection. Health and reflection need no
+/// token, so standard tooling like grpcurl and grpc_health_probe works without one.
+///
+/// Must be called inside a tokio runtime, the health status is updated every `interval`.
+pub fn routes(state: SharedState, guard: Guard, interval: Duration) -> Routes {
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    health::spawn(state.clone(), reporter, interval);
+
+    let reflection = || {
+        tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(pb::FILE_DESCRIPTOR_SET)
+            .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+    };
+    // grpcurl and most other tools still ask v1alpha
+    let reflection_v1 = reflection().build_v1().expect("the descriptor set is generated by build.rs");
+    let reflection_v1alpha = reflection().build_v1alpha().expect("the descriptor set is generated by build.rs");
+
+    Routes::new(InterceptedService::new(service(state), guard))
+        .add_service(health_service)
+        .add_service(reflection_v1)
+        .add_service(reflection_v1alpha)
+}
+
+
+#[tonic::async_trait]
+impl StudentService for Students {
+    async fn get_student(&self, request: Request<pb::GetStudentRequest>) -> Result<Response<pb::Student>, Status> {
+        let call = Call::of(&request);
+        let id = request.into_inner().id;
+        call.unary(async {
+            let students = self.state.students.lock().await;
+            let student = students.iter().find(|s| s.id == id).ok_or_else(|| error::not_found(&id))?;
+            Ok(convert::student(student))
+        })
+        .await
+    }
+
+    async fn list_students(&self, request: Request<pb::ListStudentsRequest>) -> Result<Response<pb::ListStudentsResponse>, Status> {
+        Call::of(&request)
+            .unary(async {
+                let students = self.state.students.lock().await;
+                Ok(pb::ListStudentsResponse { students: students.iter().map(convert::student).collect() })
+            })
+            .await
+    }
+
+    async fn create_student(&self, request: Request<pb::CreateStudentRequest>) -> Result<Response<pb::Student>, Status> {
+        let call = Call::of(&request);
+        call.unary(async {
+            let student = convert::input(request.into_inner().student)?;
+            let student = api::create(&self.state, Student { created_at: Some(Utc::now()), ..student }).await.map_err(error::status)?;
+            Ok(convert::student(&student))
+        })
+        .await
+    }
+
+    async fn update_student(&self, request: Request<pb::UpdateStudentRequest>) -> Result<Response<pb::Student>, Status> {
+        let call = Call::of(&request);
+        let request = request.into_inner();
+        call.unary(async {
+            let changed = convert::input(request.student)?;
+            let student = api::update(&self.state, &request.id, |student| v2::replace(student, changed)).await.map_err(|code| store_error(code, &request.id))?;
+            Ok(convert::student(&student))
+        })
+        .await
+    }
+
+    async fn delete_student(&self, request: Request<pb::DeleteStudentRequest>) -> Result<Response<pb::DeleteStudentResponse>, Status> {
+        let call = Call::of(&request);
+        let id = request.into_inner().id;
+        call.unary(async {
+            api::remove(&self.state, id.clone()).await.map_err(|code| store_error(code, &id))?;
+            Ok(pb::DeleteStudentResponse {})
+        })
+        .await
+    }
+
+    type WatchStudentsStream = watch::Events;
+
+    async fn watch_students(&self, request: Request<pb::WatchStudentsRequest>) -> Result<Response<Self::WatchStudentsStream>, Status> {
+        let call = Call::of(&request);
+        let events = watch::events(&self.state, request.into_inner());
+        let events = match call.client_deadline {
+            Some(deadline) => watch::until(events, deadline, call.error(error::deadline_exceeded())),
+            None => events,
+        };
+        call.respond(Ok(events))
+    }
+
+    async fn bulk_create(&self, request: Request<Streaming<pb::StudentInput>>) -> Result<Response<pb::BulkCreateResponse>, Status> {
+        let call = Call::of(&request);
+        call.long(bulk::create(&self.state, request.into_inner())).await
+    }
+}
+
+
+/// like `error::status`, naming the student which was not found
+fn store_error(code: axum::http::StatusCode, id: &str) -> Status {
+    match code {
+        axum::http::StatusCode::NOT_FOUND => error::not_found(id),
+        code => error::status(code),
+    }
+}
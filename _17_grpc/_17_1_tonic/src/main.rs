@@ -0,0 +1,54 @@
+use std::{env, net::SocketAddr};
+use student_grpc::{call::Guard, gateway};
+use studet_api::{config::Config, server};
+use tonic::transport::Server;
+
+
+// The REST api of _16_1_axum and the gRPC StudentService in one process, on two ports.
+// Both use the same store, so a student created over gRPC is served by /v2/students right away.
+//
+// grpcurl -plaintext 127.0.0.1:50051 list
+// grpcurl -plaintext 127.0.0.1:50051 grpc.health.v1.Health/Check
+// grpcurl -plaintext -H "authorization: Bearer {token}" 127.0.0.1:50051 student.v1.StudentService/ListStudents
+// grpcurl -plaintext -H "authorization: Bearer {token}" -d '{ "student": { "name": { "given": "Aman" }, "contacts": [{ "kind": "CONTACT_KIND_EMAIL", "value": "aman@example.com" }, { "kind": "CONTACT_KIND_MOBILE", "value": "9876543210" }] } }' 127.0.0.1:50051 student.v1.StudentService/CreateStudent
+//
+// The gRPC port also serves HTTP/JSON, with the routes of the google.api.http options in student.proto:
+// curl -H "authorization: Bearer {token}" http://127.0.0.1:50051/v1/students
+// curl -H "authorization: Bearer {token}" -X POST -d '{ "name": { "given": "Aman" }, "contacts": [{ "kind": "CONTACT_KIND_EMAIL", "value": "aman@example.com" }, { "kind": "CONTACT_KIND_MOBILE", "value": "9876543210" }] }' http://127.0.0.1:50051/v1/students
+// curl -H "authorization: Bearer {token}" -X DELETE http://127.0.0.1:50051/v1/students/{id}
+#[tokio::main]
+async fn main() {
+    let config = Config::from_env();
+    // GRPC_ADDR
+    let grpc_addr = env::var("GRPC_ADDR")
+        .ok()
+        .and_then(|addr| addr.parse().ok())
+        .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 50051)));
+    // GRPC_TOKENS - comma separated bearer tokens for the student service, the admin key works as well
+    let tokens: Vec<String> = env::var("GRPC_TOKENS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .chain(config.admin_key.clone())
+        .collect();
+    if tokens.is_empty() {
+        println!("GRPC_TOKENS and ADMIN_API_KEY are not set, the gRPC student service is open to everybody");
+    }
+
+    let server = server::build(&config);
+
+    let routes = gateway::add(student_grpc::routes(server.state.clone(), Guard::new(tokens), config.watch_interval));
+    tokio::spawn(async move {
+        println!("gRPC running at http://{}", grpc_addr);
+        Server::builder()
+            .accept_http1(true)
+            .add_routes(routes)
+            .serve(grpc_addr)
+            .await
+            .unwrap();
+    });
+
+    server::serve(config, server.app).await;
+}
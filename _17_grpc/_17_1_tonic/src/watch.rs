@@ -0,0 +1,69 @@
+use std::pin::Pin;
+use studet_api::{state::ChangeEvent, SharedState};
+use tokio::time::{sleep_until, Instant};
+use tokio_stream::{wrappers::{errors::BroadcastStreamRecvError, BroadcastStream}, Stream, StreamExt};
+use tonic::Status;
+use crate::{convert, pb};
+
+
+pub type Events = Pin<Box<dyn Stream<Item = Result<pb::StudentEvent, Status>> + Send>>;
+
+
+/// the changes of the store matching `filter`.
+///
+/// tonic only polls the stream when the client has room for more, so a slow client holds the
+/// events in the channel of the store until it overflows. The client gets a resync event then.
+pub fn events(state: &SharedState, filter: pb::WatchStudentsRequest) -> Events {
+    let search = filter.search.to_lowercase();
+    let events = BroadcastStream::new(state.events.subscribe()).filter_map(move |event| match event {
+        Ok(event) => matches(&filter.ids, &search, &event).then(|| Ok(convert::event(&event))),
+        Err(BroadcastStreamRecvError::Lagged(_)) => Some(Ok(pb::StudentEvent { kind: pb::ChangeKind::Resync.into(), ..Default::default() })),
+    });
+    Box::pin(events)
+}
+
+/// end `events` with `error` at `deadline`, tonic closes the call with the first error
+pub fn until(events: Events, deadline: Instant, error: Status) -> Events {
+    let expired = tokio_stream::once(()).then(move |_| {
+        let error = error.clone();
+        async move {
+            sleep_until(deadline).await;
+            Err(error)
+        }
+    });
+    Box::pin(events.merge(expired))
+}
+
+fn matches(ids: &[String], search: &str, event: &ChangeEvent) -> bool {
+    if !ids.is_empty() && !ids.contains(&event.id) {
+        return false;
+    }
+    match &event.student {
+        Some(student) => student.name.to_lowercase().contains(search),
+        None => true,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use studet_api::{model::Student, state::{ChangeKind, ChangeSource}};
+
+    fn event(kind: ChangeKind, id: &str, name: Option<&str>) -> ChangeEvent {
+        let student = name.map(|name| Student { id: id.into(), name: name.into(), ..Default::default() });
+        ChangeEvent { kind, id: id.into(), student, source: ChangeSource::Api }
+    }
+
+    #[test]
+    fn filter() {
+        let ellis = event(ChangeKind::Updated, "1", Some("Ellis Tarmaster"));
+        assert!(matches(&[], "", &ellis));
+        assert!(matches(&[], "tarm", &ellis));
+        assert!(!matches(&[], "aman", &ellis));
+        assert!(matches(&["1".into()], "", &ellis));
+        assert!(!matches(&["2".into()], "", &ellis));
+        assert!(matches(&[], "aman", &event(ChangeKind::Deleted, "1", None)));
+        assert!(!matches(&["2".into()], "", &event(ChangeKind::Deleted, "1", None)));
+    }
+}
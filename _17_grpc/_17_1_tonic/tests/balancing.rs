@@ -0,0 +1,172 @@
+// The balancing client against several servers, some of them dead or hanging.
+
+use std::{sync::Arc, time::Duration};
+use student_grpc::{client::{Policy, Settings, StudentClient}, pb};
+use studet_api::{state::AppState, SharedState};
+use tokio::time::{timeout, Instant};
+use tonic::Code;
+
+mod common;
+
+use common::{black_hole, input, nobody, replica};
+
+
+fn store(dir: &tempfile::TempDir, name: &str) -> SharedState {
+    Arc::new(AppState::new(dir.path().join(name)).unwrap())
+}
+
+fn create(given: &str) -> pb::CreateStudentRequest {
+    pb::CreateStudentRequest { student: Some(input(given, &format!("{}@example.com", given.to_lowercase()))) }
+}
+
+/// settings which never notice a change of health by themselves
+fn settled() -> Settings {
+    Settings { health_interval: Duration::from_secs(3600), ..Default::default() }
+}
+
+
+#[tokio::test]
+async fn round_robin_spreads_the_calls() {
+    let dir = tempfile::tempdir().unwrap();
+    let stores: Vec<SharedState> = (0..3).map(|i| store(&dir, &format!("{}.json", i))).collect();
+    let mut urls = Vec::new();
+    for state in &stores {
+        urls.push(replica(state.clone()).await.url);
+    }
+    let client = StudentClient::new(&urls, Settings::default()).unwrap();
+    for given in ["Aman", "Bela", "Carla", "Dev", "Ema", "Farid"] {
+        client.create_student(create(given)).await.unwrap();
+    }
+    for state in &stores {
+        assert_eq!(state.students.lock().await.len(), 2);
+    }
+}
+
+#[tokio::test]
+async fn a_killed_server_is_left_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = store(&dir, "students.json");
+    let mut replicas = Vec::new();
+    for _ in 0..3 {
+        replicas.push(replica(state.clone()).await);
+    }
+    let urls: Vec<String> = replicas.iter().map(|r| r.url.clone()).collect();
+    let settings = Settings { health_interval: Duration::from_millis(50), ..Default::default() };
+    let client = StudentClient::new(&urls, settings).unwrap();
+    let id = client.create_student(create("Aman")).await.unwrap().into_inner().id;
+
+    // reads keep going while a server goes away, the ones it does not answer are tried again
+    let reader = {
+        let client = client.clone();
+        let id = id.clone();
+        tokio::spawn(async move {
+            for _ in 0..200 {
+                client.get_student(pb::GetStudentRequest { id: id.clone() }).await.unwrap();
+            }
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    replicas[1].kill();
+    timeout(Duration::from_secs(10), reader).await.unwrap().unwrap();
+
+    let left_out = timeout(Duration::from_secs(2), async {
+        while client.healthy().contains(&urls[1]) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+    left_out.await.expect("the killed server is still used");
+    assert_eq!(client.healthy(), [urls[0].clone(), urls[2].clone()]);
+    // the dead server is left out, so the creates all get to the store once
+    for given in ["Bela", "Carla", "Dev", "Ema"] {
+        client.create_student(create(given)).await.unwrap();
+    }
+    assert_eq!(state.students.lock().await.len(), 5);
+}
+
+#[tokio::test]
+async fn failed_reads_are_tried_again_creates_only_when_not_sent() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = store(&dir, "students.json");
+    let urls = vec![nobody().await, replica(state.clone()).await.url];
+
+    // the read goes to the dead server first
+    let client = StudentClient::new(&urls, settled()).unwrap();
+    assert!(client.list_students(pb::ListStudentsRequest {}).await.is_ok());
+    assert_eq!(client.healthy(), [urls[1].clone()]);
+
+    // the dead server refused the connection, so the create is sent again
+    let client = StudentClient::new(&urls, settled()).unwrap();
+    client.create_student(create("Aman")).await.unwrap();
+    assert_eq!(client.healthy(), [urls[1].clone()]);
+    client.create_student(create("Bela")).await.unwrap();
+    assert_eq!(state.students.lock().await.len(), 2);
+
+    // a server which does not answer might have got the create, so it is not sent again
+    let urls = vec![black_hole().await, urls[1].clone()];
+    let client = StudentClient::new(&urls, Settings { timeout: Duration::from_millis(200), ..settled() }).unwrap();
+    let failed = client.create_student(create("Carla")).await.unwrap_err();
+    assert_eq!(failed.code(), Code::DeadlineExceeded);
+    assert_eq!(state.students.lock().await.len(), 2);
+
+    // an answer of the service is final
+    let missing = client.get_student(pb::GetStudentRequest { id: "nobody".into() }).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn a_delete_which_may_have_been_done_is_not_found_as_done() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = store(&dir, "students.json");
+    let urls = vec![black_hole().await, replica(state.clone()).await.url];
+    let settings = Settings { timeout: Duration::from_millis(200), ..settled() };
+
+    // the first try is not answered, it might have deleted the student before the second one
+    let client = StudentClient::new(&urls, settings.clone()).unwrap();
+    client.delete_student(pb::DeleteStudentRequest { id: "nobody".into() }).await.unwrap();
+
+    // without an earlier try NOT_FOUND stays an error
+    let client = StudentClient::new(&urls[1..], settings).unwrap();
+    let missing = client.delete_student(pb::DeleteStudentRequest { id: "nobody".into() }).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn hedged_reads_do_not_wait_for_a_hanging_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = store(&dir, "students.json");
+    let urls = vec![black_hole().await, replica(state.clone()).await.url];
+
+    let hedged = StudentClient::new(&urls, Settings { hedge_after: Some(Duration::from_millis(50)), ..settled() }).unwrap();
+    let started = Instant::now();
+    hedged.list_students(pb::ListStudentsRequest {}).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2), "waited {:?}", started.elapsed());
+
+    // without hedging the try has to time out first
+    let patient = StudentClient::new(&urls, Settings { timeout: Duration::from_millis(300), ..settled() }).unwrap();
+    let started = Instant::now();
+    patient.list_students(pb::ListStudentsRequest {}).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn least_loaded_avoids_a_busy_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = store(&dir, "students.json");
+    let urls = vec![black_hole().await, replica(state.clone()).await.url];
+    let settings = Settings { policy: Policy::LeastLoaded, attempts: 1, timeout: Duration::from_secs(5), ..settled() };
+    let client = StudentClient::new(&urls, settings).unwrap();
+
+    // stuck at the black hole
+    let stuck = {
+        let client = client.clone();
+        tokio::spawn(async move { client.list_students(pb::ListStudentsRequest {}).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let started = Instant::now();
+    for _ in 0..5 {
+        client.list_students(pb::ListStudentsRequest {}).await.unwrap();
+    }
+    assert!(started.elapsed() < Duration::from_secs(2), "waited {:?}", started.elapsed());
+    assert!(!stuck.is_finished());
+    stuck.abort();
+}
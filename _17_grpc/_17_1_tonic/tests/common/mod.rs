@@ -0,0 +1,117 @@
+// not every test file uses every helper
+#![allow(dead_code)]
+
+use std::{sync::Arc, time::Duration};
+use student_grpc::{call::Guard, gateway, pb::{self, student_service_client::StudentServiceClient}};
+use studet_api::{app, state::AppState, SharedState};
+use tokio::{net::TcpListener, sync::oneshot};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Endpoint};
+
+
+pub struct Servers {
+    pub rest: String,
+    pub grpc: StudentServiceClient<Channel>,
+    // address of the gRPC server, for clients with other settings
+    pub grpc_url: String,
+    pub state: SharedState,
+    pub _dir: tempfile::TempDir,
+}
+
+/// the REST api and the gRPC service on free ports of localhost, with one empty store
+pub async fn start() -> Servers {
+    start_with(Guard::new(Vec::new())).await
+}
+
+/// like `start` with the student service behind `guard`, the health status is checked every 20ms.
+/// The gRPC port serves the HTTP/JSON gateway as well.
+pub async fn start_with(guard: Guard) -> Servers {
+    let dir = tempfile::tempdir().unwrap();
+    let state: SharedState = Arc::new(AppState::new(dir.path().join("students.json")).unwrap());
+
+    let rest = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let rest_addr = rest.local_addr().unwrap();
+    tokio::spawn(axum::serve(rest, app(state.clone())).into_future());
+
+    let grpc = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let grpc_url = format!("http://{}", grpc.local_addr().unwrap());
+    let routes = gateway::add(student_grpc::routes(state.clone(), guard, Duration::from_millis(20)));
+    let server = tonic::transport::Server::builder().accept_http1(true).add_routes(routes);
+    tokio::spawn(server.serve_with_incoming(TcpListenerStream::new(grpc)));
+
+    let client = StudentServiceClient::connect(grpc_url.clone()).await.unwrap();
+    Servers { rest: format!("http://{}", rest_addr), grpc: client, grpc_url, state, _dir: dir }
+}
+
+/// one more gRPC server on `state`, like another instance behind a load balancer
+pub struct Replica {
+    pub url: String,
+    stop: Option<oneshot::Sender<()>>,
+}
+
+pub async fn replica(state: SharedState) -> Replica {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (stop, stopped) = oneshot::channel::<()>();
+    let routes = student_grpc::routes(state, Guard::new(Vec::new()), Duration::from_millis(20));
+    let server = tonic::transport::Server::builder().add_routes(routes);
+    tokio::spawn(server.serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+        // a replica which is dropped keeps running
+        if stopped.await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }));
+    Replica { url, stop: Some(stop) }
+}
+
+impl Replica {
+    /// stop the server, its connections are closed and its port refuses new ones
+    pub fn kill(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+/// a server which accepts connections and never answers
+pub async fn black_hole() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((socket, _)) = listener.accept().await {
+            held.push(socket);
+        }
+    });
+    url
+}
+
+/// an address nobody listens on
+pub async fn nobody() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+pub async fn channel(url: &str) -> Channel {
+    Endpoint::from_shared(url.to_string()).unwrap().connect().await.unwrap()
+}
+
+/// a client which lets the server send no more than 64 KiB ahead of what it has read
+pub async fn small_window_client(url: &str) -> StudentServiceClient<Channel> {
+    let channel = Endpoint::from_shared(url.to_string())
+        .unwrap()
+        .initial_stream_window_size(65_535)
+        .initial_connection_window_size(65_535)
+        .connect()
+        .await
+        .unwrap();
+    StudentServiceClient::new(channel)
+}
+
+pub fn input(given: &str, email: &str) -> pb::StudentInput {
+    let contact = |kind: pb::ContactKind, value: &str| pb::Contact { kind: kind.into(), value: value.into(), primary: true };
+    pb::StudentInput {
+        name: Some(pb::NameInput { given: given.into(), family: None }),
+        contacts: vec![contact(pb::ContactKind::Email, email), contact(pb::ContactKind::Mobile, "9876543210")],
+    }
+}
@@ -0,0 +1,102 @@
+// The HTTP/JSON gateway on the gRPC port, with the routes of the google.api.http options.
+
+use serde_json::{json, Value};
+use student_grpc::{call::{Guard, REQUEST_ID}, pb};
+
+mod common;
+
+use common::{start, start_with};
+
+
+fn aman(email: &str) -> Value {
+    json!({
+        "name": { "given": "Aman" },
+        "contacts": [
+            { "kind": "CONTACT_KIND_EMAIL", "value": email, "primary": true },
+            { "kind": "CONTACT_KIND_MOBILE", "value": "9876543210" },
+        ],
+    })
+}
+
+
+#[tokio::test]
+async fn crud_over_json() {
+    let mut servers = start().await;
+    let http = reqwest::Client::new();
+    let students = format!("{}/v1/students", servers.grpc_url);
+
+    let created = http.post(&students).json(&aman("aman@example.com")).send().await.unwrap();
+    assert_eq!(created.status(), 200);
+    let created: Value = created.json().await.unwrap();
+    let id = created["id"].as_str().unwrap().to_string();
+    // lowerCamelCase names, enum names, RFC 3339 timestamps and no default values
+    assert!(created["createdAt"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(created["contacts"][1], json!({ "kind": "CONTACT_KIND_MOBILE", "value": "9876543210", "primary": true }));
+    assert_eq!(created["name"], json!({ "given": "Aman", "display": "Aman" }));
+    assert!(created.get("updatedAt").is_none());
+
+    // the same student over gRPC and the REST api
+    let grpc = servers.grpc.get_student(pb::GetStudentRequest { id: id.clone() }).await.unwrap().into_inner();
+    assert_eq!(grpc.name.unwrap().given, "Aman");
+    let rest: Value = http.get(format!("{}/v2/students/{}", servers.rest, id)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(rest["contacts"][0]["value"], "aman@example.com");
+
+    let updated: Value = http.put(format!("{}/{}", students, id)).json(&aman("verasia@example.com")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(updated["contacts"][0]["value"], "verasia@example.com");
+    assert!(updated.get("updatedAt").is_some());
+
+    let list: Value = http.get(&students).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["students"].as_array().unwrap().len(), 1);
+
+    let deleted = http.delete(format!("{}/{}", students, id)).send().await.unwrap();
+    assert_eq!(deleted.status(), 200);
+    assert_eq!(deleted.json::<Value>().await.unwrap(), json!({}));
+    assert_eq!(http.get(format!("{}/{}", students, id)).send().await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn errors_as_json() {
+    let servers = start().await;
+    let http = reqwest::Client::new();
+    let students = format!("{}/v1/students", servers.grpc_url);
+
+    let missing = http.get(format!("{}/nobody", students)).header(REQUEST_ID, "trace-9").send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+    assert_eq!(missing.headers()[REQUEST_ID], "trace-9");
+    let missing: Value = missing.json().await.unwrap();
+    assert_eq!(missing["code"], 5);
+    let details = missing["details"].as_array().unwrap();
+    let detail = |kind: &str| details.iter().find(|d| d["@type"] == format!("type.googleapis.com/google.rpc.{}", kind)).unwrap().clone();
+    assert_eq!(detail("ErrorInfo")["reason"], "STUDENT_NOT_FOUND");
+    assert_eq!(detail("ResourceInfo")["resourceName"], "nobody");
+    assert_eq!(detail("RequestInfo")["requestId"], "trace-9");
+
+    // rejected by the service
+    let invalid = http.post(&students).json(&json!({ "name": { "given": "" } })).send().await.unwrap();
+    assert_eq!(invalid.status(), 400);
+    let invalid: Value = invalid.json().await.unwrap();
+    assert_eq!(invalid["details"][0]["@type"], "type.googleapis.com/google.rpc.ErrorInfo");
+
+    // rejected by the gateway, the json does not fit the message
+    let unknown = http.post(&students).json(&json!({ "nickname": "A" })).send().await.unwrap();
+    assert_eq!(unknown.status(), 400);
+    assert_eq!(unknown.json::<Value>().await.unwrap()["code"], 3);
+    let wrong_type = http.post(&students).json(&json!({ "contacts": [{ "kind": "CONTACT_KIND_FAX" }] })).send().await.unwrap();
+    assert_eq!(wrong_type.status(), 400);
+}
+
+#[tokio::test]
+async fn bearer_tokens_are_passed_on() {
+    let servers = start_with(Guard::new(vec!["secret".into()])).await;
+    let http = reqwest::Client::new();
+    let students = format!("{}/v1/students", servers.grpc_url);
+
+    let missing = http.get(&students).send().await.unwrap();
+    assert_eq!(missing.status(), 401);
+    assert_eq!(missing.json::<Value>().await.unwrap()["details"][0]["reason"], "MISSING_TOKEN");
+
+    let allowed = http.get(&students).bearer_auth("secret").send().await.unwrap();
+    assert_eq!(allowed.status(), 200);
+    // a made up request id comes back as well
+    assert!(!allowed.headers()[REQUEST_ID].is_empty());
+}
@@ -0,0 +1,135 @@
+// What standard tooling relies on: health, reflection, bearer tokens, deadlines, request ids
+// and error details.
+
+use std::{sync::atomic::Ordering, time::Duration};
+use student_grpc::{call::{Guard, REQUEST_ID}, pb};
+use tokio::time::{timeout, Instant};
+use tonic::{Code, Request};
+use tonic_health::pb::{health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest};
+use tonic_reflection::pb::v1::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest,
+};
+use tonic_types::StatusExt;
+
+mod common;
+
+use common::{channel, start, start_with};
+
+
+fn with_token<T>(message: T, token: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+    request
+}
+
+async fn health(client: &mut HealthClient<tonic::transport::Channel>, service: &str) -> ServingStatus {
+    let request = HealthCheckRequest { service: service.into() };
+    client.check(request).await.unwrap().into_inner().status()
+}
+
+
+#[tokio::test]
+async fn health_follows_readiness() {
+    let servers = start_with(Guard::new(vec!["secret".into()])).await;
+    // health needs no token
+    let mut client = HealthClient::new(channel(&servers.grpc_url).await);
+    assert_eq!(health(&mut client, "").await, ServingStatus::Serving);
+    assert_eq!(health(&mut client, "student.v1.StudentService").await, ServingStatus::Serving);
+
+    servers.state.recovering.store(true, Ordering::Relaxed);
+    let not_serving = timeout(Duration::from_secs(2), async {
+        while health(&mut client, "student.v1.StudentService").await != ServingStatus::NotServing {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+    not_serving.await.expect("still serving while a backup is restored");
+    assert_eq!(health(&mut client, "").await, ServingStatus::NotServing);
+}
+
+#[tokio::test]
+async fn reflection_lists_the_services() {
+    let servers = start().await;
+    let mut client = ServerReflectionClient::new(channel(&servers.grpc_url).await);
+    let request = ServerReflectionRequest { host: String::new(), message_request: Some(MessageRequest::ListServices(String::new())) };
+    let mut responses = client.server_reflection_info(tokio_stream::iter([request])).await.unwrap().into_inner();
+    let Some(MessageResponse::ListServicesResponse(list)) = responses.message().await.unwrap().unwrap().message_response else {
+        panic!("not a list of services");
+    };
+    let names: Vec<String> = list.service.into_iter().map(|s| s.name).collect();
+    for name in ["student.v1.StudentService", "grpc.health.v1.Health"] {
+        assert!(names.iter().any(|n| n == name), "{} missing in {:?}", name, names);
+    }
+}
+
+#[tokio::test]
+async fn bearer_tokens_and_request_ids() {
+    let mut servers = start_with(Guard::new(vec!["secret".into()])).await;
+
+    let missing = servers.grpc.list_students(pb::ListStudentsRequest {}).await.unwrap_err();
+    assert_eq!(missing.code(), Code::Unauthenticated);
+    assert_eq!(missing.get_error_details().error_info().unwrap().reason, "MISSING_TOKEN");
+    // a request id is made up when the client sends none
+    assert!(!missing.get_error_details().request_info().unwrap().request_id.is_empty());
+
+    let wrong = servers.grpc.list_students(with_token(pb::ListStudentsRequest {}, "guess")).await.unwrap_err();
+    assert_eq!(wrong.get_error_details().error_info().unwrap().reason, "INVALID_TOKEN");
+
+    let mut request = with_token(pb::ListStudentsRequest {}, "secret");
+    request.metadata_mut().insert(REQUEST_ID, "trace-42".parse().unwrap());
+    let response = servers.grpc.list_students(request).await.unwrap();
+    assert_eq!(response.metadata().get(REQUEST_ID).unwrap(), "trace-42");
+}
+
+#[tokio::test]
+async fn errors_carry_details() {
+    let mut servers = start().await;
+
+    let mut request = Request::new(pb::GetStudentRequest { id: "nobody".into() });
+    request.metadata_mut().insert(REQUEST_ID, "trace-7".parse().unwrap());
+    let missing = servers.grpc.get_student(request).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+    let details = missing.get_error_details();
+    assert_eq!(details.error_info().unwrap().reason, "STUDENT_NOT_FOUND");
+    assert_eq!(details.resource_info().unwrap().resource_name, "nobody");
+    assert_eq!(details.request_info().unwrap().request_id, "trace-7");
+
+    let invalid = servers.grpc.create_student(pb::CreateStudentRequest { student: None }).await.unwrap_err();
+    assert_eq!(invalid.code(), Code::InvalidArgument);
+    assert_eq!(invalid.get_error_details().bad_request().unwrap().field_violations[0].field, "student");
+}
+
+#[tokio::test]
+async fn deadlines_are_enforced() {
+    let guard = Guard::new(Vec::new()).with_deadlines(Duration::from_millis(100), Duration::from_secs(1));
+    let mut servers = start_with(guard).await;
+    // nothing can be read while somebody else holds the store
+    let held = servers.state.students.lock().await;
+
+    let mut request = Request::new(pb::ListStudentsRequest {});
+    request.set_timeout(Duration::from_millis(300));
+    let started = Instant::now();
+    let expired = servers.grpc.list_students(request).await.unwrap_err();
+    // answered by the server just before the client gives up
+    assert_eq!(expired.code(), Code::DeadlineExceeded);
+    assert!(started.elapsed() >= Duration::from_millis(250));
+
+    // without a deadline from the client the default one applies
+    let started = Instant::now();
+    let expired = servers.grpc.list_students(pb::ListStudentsRequest {}).await.unwrap_err();
+    assert_eq!(expired.code(), Code::DeadlineExceeded);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    drop(held);
+    assert!(servers.grpc.list_students(pb::ListStudentsRequest {}).await.is_ok());
+}
+
+#[tokio::test]
+async fn watch_ends_at_the_client_deadline() {
+    let mut servers = start().await;
+    let mut request = Request::new(pb::WatchStudentsRequest::default());
+    request.set_timeout(Duration::from_millis(200));
+    let mut events = servers.grpc.watch_students(request).await.unwrap().into_inner();
+    let ended = timeout(Duration::from_secs(2), events.message()).await.expect("the watch did not end");
+    assert_eq!(ended.unwrap_err().code(), Code::DeadlineExceeded);
+}
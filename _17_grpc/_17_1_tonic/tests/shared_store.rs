@@ -0,0 +1,63 @@
+// The REST api and the gRPC service on one store, each sees what the other one writes.
+
+use serde_json::{json, Value};
+use student_grpc::pb;
+use tonic::Code;
+
+mod common;
+
+use common::{input, start};
+
+
+#[tokio::test]
+async fn grpc_writes_are_served_by_rest() {
+    let mut servers = start().await;
+    let http = reqwest::Client::new();
+
+    let created = servers.grpc.create_student(pb::CreateStudentRequest { student: Some(input("Aman", "aman@example.com")) }).await.unwrap().into_inner();
+    assert!(created.created_at.is_some());
+    let url = format!("{}/v2/students/{}", servers.rest, created.id);
+    let shown: Value = http.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(shown["name"]["display"], "Aman");
+    assert_eq!(shown["contacts"][0], json!({ "kind": "email", "value": "aman@example.com", "primary": true }));
+
+    let update = pb::UpdateStudentRequest { id: created.id.clone(), student: Some(input("Aman", "verasia@example.com")) };
+    servers.grpc.update_student(update).await.unwrap();
+    let shown: Value = http.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(shown["contacts"][0]["value"], "verasia@example.com");
+
+    servers.grpc.delete_student(pb::DeleteStudentRequest { id: created.id }).await.unwrap();
+    assert_eq!(http.get(&url).send().await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn rest_writes_are_served_by_grpc() {
+    let mut servers = start().await;
+    let http = reqwest::Client::new();
+
+    let body = json!({ "name": "Ellis Tarmaster", "email": "ellis@example.com", "mobile": "1234567890" });
+    let response = http.post(format!("{}/students", servers.rest)).json(&body).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    let students = servers.grpc.list_students(pb::ListStudentsRequest {}).await.unwrap().into_inner().students;
+    assert_eq!(students.len(), 1);
+    let student = servers.grpc.get_student(pb::GetStudentRequest { id: students[0].id.clone() }).await.unwrap().into_inner();
+    assert_eq!(student.name.unwrap().display, "Ellis Tarmaster");
+
+    let response = http.delete(format!("{}/v2/students/{}", servers.rest, student.id)).send().await.unwrap();
+    assert!(response.status().is_success());
+    let missing = servers.grpc.get_student(pb::GetStudentRequest { id: student.id }).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn errors_map_to_status_codes() {
+    let mut servers = start().await;
+
+    let invalid = servers.grpc.create_student(pb::CreateStudentRequest { student: Some(input("Aman", "not an email")) }).await.unwrap_err();
+    assert_eq!(invalid.code(), Code::InvalidArgument);
+    let missing = pb::UpdateStudentRequest { id: "nobody".into(), student: Some(input("Aman", "aman@example.com")) };
+    assert_eq!(servers.grpc.update_student(missing).await.unwrap_err().code(), Code::NotFound);
+    let missing = servers.grpc.delete_student(pb::DeleteStudentRequest { id: "nobody".into() }).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
@@ -0,0 +1,122 @@
+// WatchStudents and BulkCreate over loopback: events arrive in order, bulk creates are stored
+// while the client is still sending and a watcher which does not keep up is told to resync.
+
+use std::time::Duration;
+use student_grpc::{bulk::CHUNK, pb};
+use tokio::{sync::mpsc, time::timeout};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Streaming;
+
+mod common;
+
+use common::{input, small_window_client, start};
+
+
+async fn next(events: &mut Streaming<pb::StudentEvent>) -> pb::StudentEvent {
+    timeout(Duration::from_secs(5), events.message()).await.expect("no event within 5s").unwrap().unwrap()
+}
+
+fn numbered(i: usize) -> pb::StudentInput {
+    input(&format!("Student {:05}", i), &format!("student{}@example.com", i))
+}
+
+
+#[tokio::test]
+async fn changes_arrive_in_order() {
+    let mut servers = start().await;
+    let mut all = servers.grpc.watch_students(pb::WatchStudentsRequest::default()).await.unwrap().into_inner();
+    let search = pb::WatchStudentsRequest { search: "ELLIS".into(), ..Default::default() };
+    let mut ellis = servers.grpc.watch_students(search).await.unwrap().into_inner();
+
+    let aman = servers.grpc.create_student(pb::CreateStudentRequest { student: Some(input("Aman", "aman@example.com")) }).await.unwrap().into_inner();
+    let update = pb::UpdateStudentRequest { id: aman.id.clone(), student: Some(input("Aman", "verasia@example.com")) };
+    servers.grpc.update_student(update).await.unwrap();
+    let other = servers.grpc.create_student(pb::CreateStudentRequest { student: Some(input("Ellis", "ellis@example.com")) }).await.unwrap().into_inner();
+    servers.grpc.delete_student(pb::DeleteStudentRequest { id: aman.id.clone() }).await.unwrap();
+
+    let expected = [
+        (pb::ChangeKind::Created, &aman.id),
+        (pb::ChangeKind::Updated, &aman.id),
+        (pb::ChangeKind::Created, &other.id),
+        (pb::ChangeKind::Deleted, &aman.id),
+    ];
+    for (kind, id) in expected {
+        let event = next(&mut all).await;
+        assert_eq!((event.kind(), &event.id), (kind, id));
+        assert_eq!(event.student.is_some(), kind != pb::ChangeKind::Deleted);
+    }
+
+    // only Ellis matches the search, deletions always come through
+    let event = next(&mut ellis).await;
+    assert_eq!((event.kind(), event.student.unwrap().name.unwrap().display.as_str()), (pb::ChangeKind::Created, "Ellis"));
+    assert_eq!(next(&mut ellis).await.kind(), pb::ChangeKind::Deleted);
+}
+
+#[tokio::test]
+async fn bulk_create_stores_chunks_while_sending() {
+    let servers = start().await;
+    let mut grpc = servers.grpc.clone();
+    let (tx, rx) = mpsc::channel(8);
+    let call = tokio::spawn(async move { grpc.bulk_create(ReceiverStream::new(rx)).await });
+
+    for i in 0..CHUNK + 20 {
+        tx.send(numbered(i)).await.unwrap();
+    }
+    tx.send(input("Broken", "not an email")).await.unwrap();
+
+    // the first chunk is stored before the stream ends
+    let mut grpc = servers.grpc.clone();
+    let stored = timeout(Duration::from_secs(5), async {
+        loop {
+            let students = grpc.list_students(pb::ListStudentsRequest {}).await.unwrap().into_inner().students;
+            if !students.is_empty() {
+                return students.len();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("nothing stored while the stream was open");
+    assert_eq!(stored, CHUNK);
+
+    tx.send(numbered(CHUNK + 20)).await.unwrap();
+    drop(tx);
+    let response = call.await.unwrap().unwrap().into_inner();
+
+    let names: Vec<String> = response.students.iter().map(|s| s.name.clone().unwrap().display).collect();
+    let expected: Vec<String> = (0..=CHUNK + 20).map(|i| format!("Student {:05}", i)).collect();
+    assert_eq!(names, expected);
+    assert_eq!(response.errors.len(), 1);
+    assert_eq!(response.errors[0].index as usize, CHUNK + 20);
+    assert!(response.errors[0].message.contains("not a valid email"), "{}", response.errors[0].message);
+}
+
+#[tokio::test]
+async fn slow_watcher_is_told_to_resync() {
+    let mut servers = start().await;
+    let mut slow = small_window_client(&servers.grpc_url).await;
+    let mut events = slow.watch_students(pb::WatchStudentsRequest::default()).await.unwrap().into_inner();
+
+    // far more changes than fit into the flow control window and the event channel of the store
+    let total = 5_000;
+    let response = servers.grpc.bulk_create(tokio_stream::iter((0..total).map(numbered))).await.unwrap().into_inner();
+    assert_eq!(response.students.len(), total);
+
+    let last = format!("Student {:05}", total - 1);
+    let (mut resyncs, mut previous) = (0, String::new());
+    loop {
+        let event = next(&mut events).await;
+        if event.kind() == pb::ChangeKind::Resync {
+            resyncs += 1;
+            continue;
+        }
+        // events which were delivered keep their order
+        let name = event.student.unwrap().name.unwrap().display;
+        assert!(name > previous, "{} after {}", name, previous);
+        if name == last {
+            break;
+        }
+        previous = name;
+    }
+    assert!(resyncs >= 1);
+}
@@ -0,0 +1,12 @@
+use std::{env, path::PathBuf};
+
+
+// Generate the tonic server and client from proto/classroom.proto, with the protoc shipped in
+// protoc-bin-vendored so no protoc needs to be installed.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // SAFETY: the build script sets it before starting any thread
+    unsafe { env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    let includes = [PathBuf::from("proto"), protoc_bin_vendored::include_path()?];
+    tonic_prost_build::configure().compile_protos(&[PathBuf::from("proto/classroom.proto")], &includes)?;
+    Ok(())
+}
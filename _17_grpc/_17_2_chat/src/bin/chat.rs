@@ -0,0 +1,87 @@
+use std::process::ExitCode;
+use chrono::{DateTime, Local};
+use clap::Parser;
+use classroom_chat::pb::{classroom_client::ClassroomClient, ChatMessage, Kind};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+
+
+/// chat in a room of the classroom. Every line typed is sent to the room, /quit or the end of
+/// the input leaves.
+///
+/// chat --room rust --user aman
+/// chat --server http://classroom.example.com:50052 --room rust --user bela
+#[derive(Parser)]
+#[command(name = "chat", version, verbatim_doc_comment)]
+struct Cli {
+    /// url of the classroom server
+    #[arg(long, env = "CHAT_SERVER", default_value = "http://127.0.0.1:50052")]
+    server: String,
+    #[arg(long)]
+    room: String,
+    /// the name the others see
+    #[arg(long, env = "USER")]
+    user: String,
+}
+
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut client = match ClassroomClient::connect(cli.server.clone()).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("can not reach {}: {}", cli.server, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let (outbox, mut messages) = match classroom_chat::join(&mut client, &cli.room, &cli.user).await {
+        Ok(joined) => joined,
+        Err(e) => {
+            eprintln!("can not join {}: {}", cli.room, e.message());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match line.trim() {
+                "/quit" => break,
+                "" => continue,
+                text => {
+                    if outbox.send(classroom_chat::text(text)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        // dropping the sender ends the stream, which leaves the room
+    });
+
+    loop {
+        match messages.message().await {
+            Ok(Some(message)) => println!("{}", line(&message)),
+            Ok(None) => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("disconnected: {}", e.message());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+}
+
+/// one message as a line of the terminal
+fn line(message: &ChatMessage) -> String {
+    let time = message
+        .sent_at
+        .and_then(|t| DateTime::from_timestamp(t.seconds, t.nanos as u32))
+        .map(|t| t.with_timezone(&Local).format("%H:%M").to_string())
+        .unwrap_or_default();
+    match message.kind() {
+        Kind::Text if message.replay => format!("{} {}: {} (earlier)", time, message.user, message.text),
+        Kind::Text => format!("{} {}: {}", time, message.user, message.text),
+        Kind::Join => format!("{} * {} joined, here: {}", time, message.user, message.members.join(", ")),
+        Kind::Leave => format!("{} * {} {}, here: {}", time, message.user, message.text, message.members.join(", ")),
+        Kind::Unspecified => format!("{} ?", time),
+    }
+}
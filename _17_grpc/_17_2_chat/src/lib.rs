@@ -0,0 +1,75 @@
+pub mod room;
+
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Channel, Request, Response, Status, Streaming};
+use pb::{classroom_client::ClassroomClient, classroom_server::{self, ClassroomServer}, ChatMessage, Kind};
+use room::{Messages, Rooms};
+
+
+/// the messages and the generated server and client of proto/classroom.proto
+pub mod pb {
+    tonic::include_proto!("classroom.v1");
+}
+
+
+/// the Classroom service on `rooms`
+pub struct Classroom {
+    rooms: Arc<Rooms>,
+}
+
+/// the service to add to a `tonic::transport::Server`
+pub fn service(rooms: Arc<Rooms>) -> ClassroomServer<Classroom> {
+    ClassroomServer::new(Classroom { rooms })
+}
+
+/// join `room` as `user`. Texts sent on the returned sender go to the room, dropping it leaves.
+pub async fn join(client: &mut ClassroomClient<Channel>, room: &str, user: &str) -> Result<(mpsc::Sender<ChatMessage>, Streaming<ChatMessage>), Status> {
+    let (outbox, inbox) = mpsc::channel(16);
+    let join = ChatMessage { kind: Kind::Join.into(), room: room.to_string(), user: user.to_string(), ..Default::default() };
+    outbox.send(join).await.expect("the receiver is still here");
+    let messages = client.chat(ReceiverStream::new(inbox)).await?.into_inner();
+    Ok((outbox, messages))
+}
+
+/// a message with `text` for the room
+pub fn text(text: &str) -> ChatMessage {
+    ChatMessage { kind: Kind::Text.into(), text: text.to_string(), ..Default::default() }
+}
+
+
+#[tonic::async_trait]
+impl classroom_server::Classroom for Classroom {
+    type ChatStream = Messages;
+
+    /// grpcurl -plaintext -import-path proto -proto classroom.proto -d @ 127.0.0.1:50052 classroom.v1.Classroom/Chat
+    /// { "kind": "KIND_JOIN", "room": "rust", "user": "aman" }
+    /// { "kind": "KIND_TEXT", "text": "hello" }
+    async fn chat(&self, request: Request<Streaming<ChatMessage>>) -> Result<Response<Messages>, Status> {
+        let mut inbound = request.into_inner();
+        let join = inbound.message().await?.ok_or_else(|| Status::invalid_argument("the stream ended before joining a room"))?;
+        if join.kind() != Kind::Join {
+            return Err(Status::invalid_argument("the first message must join a room"));
+        }
+        let (member, messages) = self.rooms.join(&join.room, &join.user)?;
+
+        // the room is left when the client ends its stream, goes away or sends something wrong
+        let rooms = self.rooms.clone();
+        tokio::spawn(async move {
+            let error = loop {
+                match inbound.message().await {
+                    Ok(Some(message)) if message.kind() == Kind::Text => {
+                        if let Err(e) = rooms.say(&member, message.text) {
+                            break Some(e);
+                        }
+                    }
+                    Ok(Some(_)) => break Some(Status::invalid_argument("only text can be sent after joining")),
+                    Ok(None) | Err(_) => break None,
+                }
+            };
+            rooms.leave(&member, error);
+        });
+        Ok(Response::new(messages))
+    }
+}
@@ -0,0 +1,32 @@
+use std::{env, net::SocketAddr, sync::Arc};
+use classroom_chat::room::Rooms;
+use tonic::transport::Server;
+
+
+// The Classroom chat server, talk to it with the chat client of src/bin/chat.rs:
+//
+// cargo run --bin chat -- --room rust --user aman
+#[tokio::main]
+async fn main() {
+    // CHAT_ADDR
+    let addr = env::var("CHAT_ADDR")
+        .ok()
+        .and_then(|addr| addr.parse().ok())
+        .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 50052)));
+    // CHAT_HISTORY - messages replayed to everybody who joins a room
+    let history = number("CHAT_HISTORY", 50);
+    // CHAT_QUEUE - messages a client may fall behind before it is dropped
+    let queue = number("CHAT_QUEUE", 256);
+
+    let rooms = Arc::new(Rooms::new(history, queue));
+    println!("classroom chat running at http://{}", addr);
+    Server::builder()
+        .add_service(classroom_chat::service(rooms))
+        .serve(addr)
+        .await
+        .unwrap();
+}
+
+fn number(name: &str, default: usize) -> usize {
+    env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
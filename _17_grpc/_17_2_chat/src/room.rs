@@ -0,0 +1,272 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    pin::Pin,
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex},
+    time::SystemTime,
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tonic::Status;
+use crate::pb::{ChatMessage, Kind};
+
+
+/// longest room and user name
+pub const MAX_NAME: usize = 64;
+/// longest text, in characters
+pub const MAX_TEXT: usize = 2000;
+
+
+/// what a member gets from its room, ends with an error when the member was dropped
+pub type Messages = Pin<Box<dyn Stream<Item = Result<ChatMessage, Status>> + Send>>;
+
+
+/// all rooms of the classroom. A room is made by the first join and keeps its history when
+/// everybody left.
+pub struct Rooms {
+    rooms: Mutex<HashMap<String, Room>>,
+    // messages replayed on join
+    history: usize,
+    // messages a member may fall behind before it is dropped
+    queue: usize,
+    next_id: AtomicU64,
+}
+
+/// somebody in a room, returned by `Rooms::join` to say something or leave
+pub struct Member {
+    pub room: String,
+    pub user: String,
+    id: u64,
+}
+
+struct Room {
+    name: String,
+    members: BTreeMap<String, Seat>,
+    history: VecDeque<ChatMessage>,
+    seq: u64,
+}
+
+// the sending side of a member's messages, dropping it ends them
+struct Seat {
+    id: u64,
+    outbox: mpsc::Sender<ChatMessage>,
+    // the error the messages end with
+    end: Arc<Mutex<Option<Status>>>,
+}
+
+
+impl Rooms {
+    pub fn new(history: usize, queue: usize) -> Self {
+        Rooms { rooms: Mutex::new(HashMap::new()), history, queue: queue.max(1), next_id: AtomicU64::new(1) }
+    }
+
+    /// add `user` to `room`. The messages start with the history of the room and the join of
+    /// `user`, which everybody in the room gets as well.
+    pub fn join(&self, room: &str, user: &str) -> Result<(Member, Messages), Status> {
+        name("room", room)?;
+        name("user", user)?;
+        let mut rooms = self.rooms.lock().unwrap();
+        let chat = rooms.entry(room.to_string()).or_insert_with(|| Room::new(room));
+        if chat.members.contains_key(user) {
+            return Err(Status::already_exists(format!("{} is already in {}", user, room)));
+        }
+
+        // room for the whole history, so the replay never counts as falling behind
+        let (outbox, inbox) = mpsc::channel(self.history + self.queue);
+        for message in &chat.history {
+            let _ = outbox.try_send(ChatMessage { replay: true, ..message.clone() });
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let end = Arc::new(Mutex::new(None));
+        chat.members.insert(user.to_string(), Seat { id, outbox, end: end.clone() });
+        let joined = chat.presence(Kind::Join, user, "");
+        chat.announce(joined, self.history);
+
+        let ended = tokio_stream::once(()).filter_map(move |_| end.lock().unwrap().take().map(Err));
+        let messages = ReceiverStream::new(inbox).map(Ok).chain(ended);
+        Ok((Member { room: room.to_string(), user: user.to_string(), id }, Box::pin(messages)))
+    }
+
+    /// send `text` from `member` to everybody in its room. Ignored when the member was dropped.
+    pub fn say(&self, member: &Member, text: String) -> Result<(), Status> {
+        if text.trim().is_empty() {
+            return Err(Status::invalid_argument("text must not be empty"));
+        }
+        if text.chars().count() > MAX_TEXT {
+            return Err(Status::invalid_argument(format!("text must not be longer than {} characters", MAX_TEXT)));
+        }
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(chat) = rooms.get_mut(&member.room).filter(|chat| chat.seated(member)) else { return Ok(()) };
+        let message = ChatMessage { kind: Kind::Text.into(), room: member.room.clone(), user: member.user.clone(), text, ..Default::default() };
+        chat.announce(message, self.history);
+        Ok(())
+    }
+
+    /// remove `member` from its room, its messages end with `error` if given
+    pub fn leave(&self, member: &Member, error: Option<Status>) {
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(chat) = rooms.get_mut(&member.room).filter(|chat| chat.seated(member)) else { return };
+        let seat = chat.members.remove(&member.user).expect("seated members have a seat");
+        *seat.end.lock().unwrap() = error;
+        let left = chat.presence(Kind::Leave, &member.user, "left");
+        chat.announce(left, self.history);
+    }
+
+    /// the users in `room`
+    pub fn members(&self, room: &str) -> Vec<String> {
+        let rooms = self.rooms.lock().unwrap();
+        rooms.get(room).map(|chat| chat.members.keys().cloned().collect()).unwrap_or_default()
+    }
+}
+
+impl Room {
+    fn new(name: &str) -> Self {
+        Room { name: name.to_string(), members: BTreeMap::new(), history: VecDeque::new(), seq: 0 }
+    }
+
+    fn seated(&self, member: &Member) -> bool {
+        self.members.get(&member.user).is_some_and(|seat| seat.id == member.id)
+    }
+
+    fn presence(&self, kind: Kind, user: &str, text: &str) -> ChatMessage {
+        ChatMessage {
+            kind: kind.into(),
+            room: self.name.clone(),
+            user: user.to_string(),
+            text: text.to_string(),
+            members: self.members.keys().cloned().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// send `message` to every member without waiting for anybody. A member whose queue is full
+    /// is dropped and the others are told it left, so one slow reader never holds up the room.
+    fn announce(&mut self, message: ChatMessage, history: usize) {
+        let mut pending = VecDeque::from([message]);
+        while let Some(mut message) = pending.pop_front() {
+            self.seq += 1;
+            message.seq = self.seq;
+            message.sent_at = Some(SystemTime::now().into());
+            if message.kind() == Kind::Text && history > 0 {
+                if self.history.len() == history {
+                    self.history.pop_front();
+                }
+                self.history.push_back(message.clone());
+            }
+
+            let mut gone = Vec::new();
+            for (user, seat) in &self.members {
+                match seat.outbox.try_send(message.clone()) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => gone.push((user.clone(), true)),
+                    Err(TrySendError::Closed(_)) => gone.push((user.clone(), false)),
+                }
+            }
+            for (user, slow) in gone {
+                let seat = self.members.remove(&user).expect("the user was just found");
+                let reason = if slow {
+                    *seat.end.lock().unwrap() = Some(Status::resource_exhausted("too slow to keep up with the room, join again to catch up"));
+                    "too slow"
+                } else {
+                    "disconnected"
+                };
+                pending.push_back(self.presence(Kind::Leave, &user, reason));
+            }
+        }
+    }
+}
+
+fn name(what: &str, name: &str) -> Result<(), Status> {
+    if name.trim().is_empty() {
+        return Err(Status::invalid_argument(format!("{} must not be empty", what)));
+    }
+    if name.chars().count() > MAX_NAME {
+        return Err(Status::invalid_argument(format!("{} must not be longer than {} characters", what, MAX_NAME)));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn next(messages: &mut Messages) -> ChatMessage {
+        // everything is queued before `join`, `say` and `leave` return
+        let waker = std::task::Waker::noop();
+        match messages.as_mut().poll_next(&mut std::task::Context::from_waker(waker)) {
+            std::task::Poll::Ready(Some(Ok(message))) => message,
+            other => panic!("no message: {:?}", other.map(|m| m.map(|m| m.map_err(|e| e.code())))),
+        }
+    }
+
+    #[test]
+    fn presence_and_history() {
+        let rooms = Rooms::new(2, 8);
+        let (aman, mut aman_messages) = rooms.join("rust", "aman").unwrap();
+        let joined = next(&mut aman_messages);
+        assert_eq!((joined.kind(), joined.members.clone()), (Kind::Join, vec!["aman".to_string()]));
+
+        for text in ["one", "two", "three"] {
+            rooms.say(&aman, text.into()).unwrap();
+        }
+        let (bela, mut bela_messages) = rooms.join("rust", "bela").unwrap();
+        // the last two messages, then the join
+        let replayed: Vec<(String, bool)> = (0..2).map(|_| next(&mut bela_messages)).map(|m| (m.text, m.replay)).collect();
+        assert_eq!(replayed, [("two".to_string(), true), ("three".to_string(), true)]);
+        assert_eq!(next(&mut bela_messages).members, ["aman", "bela"]);
+
+        rooms.leave(&bela, None);
+        for _ in 0..3 {
+            next(&mut aman_messages);
+        }
+        let joined = next(&mut aman_messages);
+        assert_eq!((joined.kind(), joined.user.as_str()), (Kind::Join, "bela"));
+        let left = next(&mut aman_messages);
+        assert_eq!((left.kind(), left.members.clone(), left.seq), (Kind::Leave, vec!["aman".to_string()], 6));
+        assert_eq!(rooms.members("rust"), ["aman"]);
+    }
+
+    #[test]
+    fn names_and_text_are_checked() {
+        let rooms = Rooms::new(0, 8);
+        assert_eq!(rooms.join("", "aman").err().unwrap().code(), Code::InvalidArgument);
+        assert_eq!(rooms.join("rust", &"a".repeat(MAX_NAME + 1)).err().unwrap().code(), Code::InvalidArgument);
+        let (aman, _messages) = rooms.join("rust", "aman").unwrap();
+        assert_eq!(rooms.join("rust", "aman").err().unwrap().code(), Code::AlreadyExists);
+        assert!(rooms.join("go", "aman").is_ok());
+        assert_eq!(rooms.say(&aman, " ".into()).unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(rooms.say(&aman, "a".repeat(MAX_TEXT + 1)).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn slow_members_are_dropped() {
+        let rooms = Rooms::new(0, 4);
+        let (aman, mut aman_messages) = rooms.join("rust", "aman").unwrap();
+        let (_bela, mut bela_messages) = rooms.join("rust", "bela").unwrap();
+        // aman's queue is full with two joins and two texts, bela keeps reading
+        for text in ["one", "two"] {
+            rooms.say(&aman, text.into()).unwrap();
+        }
+        for _ in 0..3 {
+            next(&mut bela_messages);
+        }
+        rooms.say(&aman, "three".into()).unwrap();
+        assert_eq!(rooms.members("rust"), ["bela"]);
+        assert_eq!(next(&mut bela_messages).text, "three");
+        let left = next(&mut bela_messages);
+        assert_eq!((left.kind(), left.user.as_str(), left.text.as_str()), (Kind::Leave, "aman", "too slow"));
+
+        // aman gets what was queued and then the reason, saying anything more is ignored
+        for _ in 0..4 {
+            next(&mut aman_messages);
+        }
+        let waker = std::task::Waker::noop();
+        match aman_messages.as_mut().poll_next(&mut std::task::Context::from_waker(waker)) {
+            std::task::Poll::Ready(Some(Err(status))) => assert_eq!(status.code(), Code::ResourceExhausted),
+            _ => panic!("the messages of a dropped member end with an error"),
+        }
+        rooms.say(&aman, "four".into()).unwrap();
+        assert_eq!(rooms.members("rust"), ["bela"]);
+    }
+}
@@ -0,0 +1,157 @@
+// Several chat clients against one server on localhost.
+
+use std::{sync::Arc, time::Duration};
+use classroom_chat::{join, pb::{classroom_client::ClassroomClient, ChatMessage, Kind}, room::Rooms, text};
+use tokio::{net::TcpListener, time::timeout};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::{Channel, Endpoint}, Code, Streaming};
+
+
+/// a server replaying 2 messages, dropping clients 8 messages behind
+async fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = classroom_chat::service(Arc::new(Rooms::new(2, 8)));
+    tokio::spawn(tonic::transport::Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    url
+}
+
+async fn client(url: &str) -> ClassroomClient<Channel> {
+    ClassroomClient::connect(url.to_string()).await.unwrap()
+}
+
+async fn next(messages: &mut Streaming<ChatMessage>) -> ChatMessage {
+    timeout(Duration::from_secs(5), messages.message()).await.expect("nothing arrived").unwrap().expect("the chat ended")
+}
+
+/// the next text, skipping joins and leaves
+async fn next_text(messages: &mut Streaming<ChatMessage>) -> ChatMessage {
+    loop {
+        let message = next(messages).await;
+        if message.kind() == Kind::Text {
+            return message;
+        }
+    }
+}
+
+
+#[tokio::test]
+async fn rooms_hear_only_their_own_texts() {
+    let url = start().await;
+    let (aman, mut aman_messages) = join(&mut client(&url).await, "rust", "aman").await.unwrap();
+    let (_bela, mut bela_messages) = join(&mut client(&url).await, "rust", "bela").await.unwrap();
+    let (carla, mut carla_messages) = join(&mut client(&url).await, "go", "carla").await.unwrap();
+
+    aman.send(text("hello rust")).await.unwrap();
+    carla.send(text("hello go")).await.unwrap();
+    for messages in [&mut aman_messages, &mut bela_messages] {
+        let message = next_text(messages).await;
+        assert_eq!((message.room.as_str(), message.user.as_str(), message.text.as_str()), ("rust", "aman", "hello rust"));
+    }
+    assert_eq!(next_text(&mut carla_messages).await.text, "hello go");
+}
+
+#[tokio::test]
+async fn presence_and_history() {
+    let url = start().await;
+    let (aman, mut aman_messages) = join(&mut client(&url).await, "rust", "aman").await.unwrap();
+    assert_eq!(next(&mut aman_messages).await.members, ["aman"]);
+    for line in ["one", "two", "three"] {
+        aman.send(text(line)).await.unwrap();
+        next(&mut aman_messages).await;
+    }
+
+    // the last two texts, then the own join
+    let (bela, mut bela_messages) = join(&mut client(&url).await, "rust", "bela").await.unwrap();
+    for line in ["two", "three"] {
+        let replayed = next(&mut bela_messages).await;
+        assert_eq!((replayed.text.as_str(), replayed.replay), (line, true));
+    }
+    let joined = next(&mut bela_messages).await;
+    assert_eq!((joined.kind(), joined.members.clone()), (Kind::Join, vec!["aman".to_string(), "bela".to_string()]));
+    assert_eq!(next(&mut aman_messages).await.seq, joined.seq);
+
+    // ending the stream leaves, and so does going away without a word
+    drop(bela);
+    let left = next(&mut aman_messages).await;
+    assert_eq!((left.kind(), left.user.as_str(), left.members.clone()), (Kind::Leave, "bela", vec!["aman".to_string()]));
+    assert!(timeout(Duration::from_secs(5), bela_messages.message()).await.unwrap().unwrap().is_none());
+
+    let (carla, carla_messages) = join(&mut client(&url).await, "rust", "carla").await.unwrap();
+    assert_eq!(next(&mut aman_messages).await.user, "carla");
+    drop((carla, carla_messages));
+    let left = next(&mut aman_messages).await;
+    assert_eq!((left.kind(), left.user.as_str()), (Kind::Leave, "carla"));
+}
+
+#[tokio::test]
+async fn joining_is_checked() {
+    let url = start().await;
+    let mut client = client(&url).await;
+
+    let (outbox, inbox) = tokio::sync::mpsc::channel(1);
+    outbox.send(text("hello")).await.unwrap();
+    let status = client.chat(tokio_stream::wrappers::ReceiverStream::new(inbox)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let (_aman, _messages) = join(&mut client, "rust", "aman").await.unwrap();
+    assert_eq!(join(&mut client, "rust", "aman").await.unwrap_err().code(), Code::AlreadyExists);
+    assert_eq!(join(&mut client, "rust", "").await.unwrap_err().code(), Code::InvalidArgument);
+
+    // a bad text ends the chat of its sender
+    let (bela, mut bela_messages) = join(&mut client, "rust", "bela").await.unwrap();
+    bela.send(text(" ")).await.unwrap();
+    let ended = loop {
+        match timeout(Duration::from_secs(5), bela_messages.message()).await.unwrap() {
+            Ok(Some(_)) => continue,
+            other => break other,
+        }
+    };
+    assert_eq!(ended.unwrap_err().code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn slow_readers_are_dropped() {
+    let url = start().await;
+    // lets the server send no more than 64 KiB ahead of what the client has read
+    let channel = Endpoint::from_shared(url.clone())
+        .unwrap()
+        .initial_stream_window_size(65_535)
+        .initial_connection_window_size(65_535)
+        .connect()
+        .await
+        .unwrap();
+    let (_slow, mut slow_messages) = join(&mut ClassroomClient::new(channel), "rust", "slow").await.unwrap();
+    let (aman, mut aman_messages) = join(&mut client(&url).await, "rust", "aman").await.unwrap();
+
+    // aman reads each text back before sending the next, so only the slow reader falls behind
+    // once its window, the send buffer of the server and its queue in the room are full
+    let line = "x".repeat(1900);
+    let mut left = None;
+    for _ in 0..600 {
+        aman.send(text(&line)).await.unwrap();
+        loop {
+            let message = next(&mut aman_messages).await;
+            match message.kind() {
+                Kind::Text => break,
+                Kind::Leave => left = Some(message),
+                _ => {}
+            }
+        }
+    }
+    let left = left.expect("the slow reader was not dropped");
+    assert_eq!((left.user.as_str(), left.text.as_str(), left.members.clone()), ("slow", "too slow", vec!["aman".to_string()]));
+
+    // the slow reader gets what was sent before and then why it was dropped
+    let ended = loop {
+        match timeout(Duration::from_secs(5), slow_messages.message()).await.unwrap() {
+            Ok(Some(_)) => continue,
+            other => break other,
+        }
+    };
+    assert_eq!(ended.unwrap_err().code(), Code::ResourceExhausted);
+
+    // and can join again, with the history
+    let (_slow, mut slow_messages) = join(&mut client(&url).await, "rust", "slow").await.unwrap();
+    assert!(next(&mut slow_messages).await.replay);
+}
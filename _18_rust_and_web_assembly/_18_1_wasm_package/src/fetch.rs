@@ -0,0 +1,44 @@
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, Response};
+use crate::roster::Transport;
+
+
+/// requests with the browser's fetch to the api at `base`, an empty `base` is the origin of the page
+pub struct Fetch {
+    base: String,
+}
+
+impl Fetch {
+    pub fn new(base: impl Into<String>) -> Self {
+        Fetch { base: base.into().trim_end_matches('/').to_string() }
+    }
+}
+
+impl Transport for Fetch {
+    async fn send(&self, method: &str, path: &str, body: Option<String>) -> Result<(u16, String), String> {
+        let init = RequestInit::new();
+        init.set_method(method);
+        if let Some(body) = &body {
+            init.set_body(&JsValue::from_str(body));
+        }
+        let request = Request::new_with_str_and_init(&format!("{}{}", self.base, path), &init).map_err(describe)?;
+        request.headers().set("Accept", "application/json").map_err(describe)?;
+        if body.is_some() {
+            request.headers().set("Content-Type", "application/json").map_err(describe)?;
+        }
+
+        let window = web_sys::window().ok_or("there is no window to fetch from")?;
+        let response: Response = JsFuture::from(window.fetch_with_request(&request)).await.map_err(describe)?.dyn_into().map_err(describe)?;
+        let text = JsFuture::from(response.text().map_err(describe)?).await.map_err(describe)?;
+        Ok((response.status(), text.as_string().unwrap_or_default()))
+    }
+}
+
+// fetch rejects with a TypeError like "NetworkError when attempting to fetch resource."
+fn describe(error: JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => error.message().into(),
+        None => error.as_string().unwrap_or_else(|| format!("{:?}", error)),
+    }
+}
@@ -1,5 +1,12 @@
+pub use imaging;
+pub mod roster;
+pub mod fetch;
+pub mod view;
+
+use image::DynamicImage;
 use wasm_bindgen::prelude::*;
-use web_sys::{window, Document, Element, HtmlElement};
+use web_sys::{window, Document, Element};
+use imaging::Format;
 
 // Expose function to JavaScript
 #[wasm_bindgen]
@@ -13,33 +20,216 @@ pub struct Student {
     age: i32,
     is_active: bool,
     grades: Vec<i32>,
+    // where the student was rendered last, the setters render it there again
+    container: Option<Element>,
 }
 
 #[wasm_bindgen]
 impl Student {
     #[wasm_bindgen(constructor)]
     pub fn new(name: String, age: i32, is_active: bool, grades: Vec<i32>) -> Student {
-        Student { name, age, is_active, grades }
+        Student { name, age, is_active, grades, container: None }
     }
 
+    /// show the student in `container`, replacing what it showed before.
+    ///
+    /// The values are set as text, so a name like `<img src=x onerror=alert(1)>` is shown as it
+    /// is instead of being run. Rendering into the same container again updates it in place.
     #[wasm_bindgen]
-    pub fn render(&self) {
-        let document: Document = window().unwrap().document().unwrap();
-
-        // Explicitly cast to HtmlElement to get `body()`
-        let body: HtmlElement = document.body().expect("document should have a body");
-
-        let student_info = format!(
-            "<h2>Student Details</h2>
-             <p><strong>Name:</strong> {}</p>
-             <p><strong>Age:</strong> {}</p>
-             <p><strong>Active:</strong> {}</p>
-             <p><strong>Grades:</strong> {:?}</p>",
-            self.name, self.age, self.is_active, self.grades
-        );
-
-        let div: Element = document.create_element("div").unwrap();
-        div.set_inner_html(&student_info);
-        body.append_child(&div).unwrap();
-    }
-}
\ No newline at end of file
+    pub fn render(&mut self, container: &Element) -> Result<(), JsValue> {
+        let document: Document = container
+            .owner_document()
+            .or_else(|| window().and_then(|w| w.document()))
+            .ok_or_else(|| JsValue::from_str("the container is not part of a document"))?;
+
+        let card = document.create_element("div")?;
+        card.set_class_name("student");
+        let title = document.create_element("h2")?;
+        title.set_text_content(Some("Student Details"));
+        card.append_child(&title)?;
+        for (label, value) in self.fields() {
+            let line = document.create_element("p")?;
+            let strong = document.create_element("strong")?;
+            strong.set_text_content(Some(&format!("{}:", label)));
+            line.append_child(&strong)?;
+            line.append_child(&document.create_text_node(&format!(" {}", value)))?;
+            card.append_child(&line)?;
+        }
+
+        container.set_text_content(None);
+        container.append_child(&card)?;
+        self.container = Some(container.clone());
+        Ok(())
+    }
+
+    // the setters show the change right away when the student was rendered before
+
+    #[wasm_bindgen(js_name = setName)]
+    pub fn set_name(&mut self, name: String) -> Result<(), JsValue> {
+        self.name = name;
+        self.rerender()
+    }
+
+    #[wasm_bindgen(js_name = setAge)]
+    pub fn set_age(&mut self, age: i32) -> Result<(), JsValue> {
+        self.age = age;
+        self.rerender()
+    }
+
+    #[wasm_bindgen(js_name = setActive)]
+    pub fn set_active(&mut self, is_active: bool) -> Result<(), JsValue> {
+        self.is_active = is_active;
+        self.rerender()
+    }
+
+    #[wasm_bindgen(js_name = setGrades)]
+    pub fn set_grades(&mut self, grades: Vec<i32>) -> Result<(), JsValue> {
+        self.grades = grades;
+        self.rerender()
+    }
+}
+
+impl Student {
+    /// the labels and values shown by `render`
+    fn fields(&self) -> [(&'static str, String); 4] {
+        [
+            ("Name", self.name.clone()),
+            ("Age", self.age.to_string()),
+            ("Active", self.is_active.to_string()),
+            ("Grades", format!("{:?}", self.grades)),
+        ]
+    }
+
+    fn rerender(&mut self) -> Result<(), JsValue> {
+        match self.container.clone() {
+            Some(container) => self.render(&container),
+            None => Ok(()),
+        }
+    }
+}
+
+
+/// an image decoded from PNG or JPEG bytes. Every change gives a new picture, so calls chain:
+///
+/// const bytes = new Uint8Array(await file.arrayBuffer());
+/// const thumbnail = decodeImage(bytes).fit(200, 200, "lanczos3").grayscale().toJpeg(80);
+///
+/// Wrong input throws an Error with the reason.
+#[wasm_bindgen]
+pub struct Picture {
+    image: DynamicImage,
+}
+
+#[wasm_bindgen(js_name = decodeImage)]
+pub fn decode_image(bytes: &[u8]) -> Result<Picture, JsError> {
+    Ok(Picture { image: imaging::decode(bytes).map_err(thrown)? })
+}
+
+#[wasm_bindgen]
+impl Picture {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    /// exactly `width`x`height`, `filter` is nearest, triangle, catmullrom, gaussian or lanczos3
+    pub fn resize(self, width: u32, height: u32, filter: &str) -> Result<Picture, JsError> {
+        self.change(|image| imaging::resize(image, width, height, filter.parse()?))
+    }
+
+    /// as large as fits into `width`x`height`, keeping the aspect ratio
+    pub fn fit(self, width: u32, height: u32, filter: &str) -> Result<Picture, JsError> {
+        self.change(|image| imaging::fit(image, width, height, filter.parse()?))
+    }
+
+    /// scaled and cut to fill `width`x`height`
+    pub fn cover(self, width: u32, height: u32, filter: &str) -> Result<Picture, JsError> {
+        self.change(|image| imaging::cover(image, width, height, filter.parse()?))
+    }
+
+    pub fn crop(self, x: u32, y: u32, width: u32, height: u32) -> Result<Picture, JsError> {
+        self.change(|image| imaging::crop(image, x, y, width, height))
+    }
+
+    /// clockwise by a multiple of 90 degrees
+    pub fn rotate(self, degrees: i32) -> Result<Picture, JsError> {
+        self.change(|image| imaging::rotate(image, degrees))
+    }
+
+    pub fn grayscale(self) -> Picture {
+        Picture { image: imaging::grayscale(&self.image) }
+    }
+
+    pub fn blur(self, sigma: f32) -> Result<Picture, JsError> {
+        self.change(|image| imaging::blur(image, sigma))
+    }
+
+    /// -255 to 255
+    pub fn brightness(self, value: i32) -> Result<Picture, JsError> {
+        self.change(|image| imaging::brightness(image, value))
+    }
+
+    /// -100 to 100
+    pub fn contrast(self, percent: f32) -> Result<Picture, JsError> {
+        self.change(|image| imaging::contrast(image, percent))
+    }
+
+    #[wasm_bindgen(js_name = toPng)]
+    pub fn to_png(&self) -> Result<Vec<u8>, JsError> {
+        imaging::encode(&self.image, Format::Png, 0).map_err(thrown)
+    }
+
+    /// `quality` from 1 to 100
+    #[wasm_bindgen(js_name = toJpeg)]
+    pub fn to_jpeg(&self, quality: u8) -> Result<Vec<u8>, JsError> {
+        imaging::encode(&self.image, Format::Jpeg, quality).map_err(thrown)
+    }
+}
+
+impl Picture {
+    fn change(self, change: impl FnOnce(&DynamicImage) -> Result<DynamicImage, imaging::Error>) -> Result<Picture, JsError> {
+        Ok(Picture { image: change(&self.image).map_err(thrown)? })
+    }
+}
+
+// an Error thrown in JavaScript
+fn thrown(error: imaging::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let mut student = Student::new("<img src=x onerror=alert(1)>".into(), 22, true, vec![90, 85, 88]);
+        assert_eq!(student.fields(), [
+            ("Name", "<img src=x onerror=alert(1)>".to_string()),
+            ("Age", "22".to_string()),
+            ("Active", "true".to_string()),
+            ("Grades", "[90, 85, 88]".to_string()),
+        ]);
+        // nothing to update before the first render
+        assert!(student.set_age(23).is_ok());
+        assert_eq!(student.fields()[1].1, "23");
+    }
+
+    // only what succeeds can run outside of wasm, a JsError needs JavaScript
+    #[test]
+    fn pictures_chain() {
+        let image = DynamicImage::new_rgb8(40, 20);
+        let png = imaging::encode(&image, Format::Png, 0).unwrap();
+        let picture = decode_image(&png).ok().unwrap();
+        let picture = picture.fit(20, 20, "lanczos3").ok().unwrap().rotate(90).ok().unwrap().grayscale();
+        assert_eq!((picture.width(), picture.height()), (10, 20));
+        let jpeg = picture.to_jpeg(80).ok().unwrap();
+        assert_eq!(imaging::probe(&jpeg).unwrap(), (Format::Jpeg, 10, 20));
+    }
+}
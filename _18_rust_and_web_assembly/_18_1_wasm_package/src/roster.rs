@@ -0,0 +1,553 @@
+use std::{cell::RefCell, fmt, future::Future, str::FromStr};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+
+/// the v2 students of the axum server (`_16_http_server/_16_1_axum`)
+pub const STUDENTS: &str = "/v2/students";
+
+
+/// one http exchange, the browser's fetch in the page and a fake in the tests
+pub trait Transport {
+    /// send `body` as json, gives the status and the body of the answer or why no answer came
+    fn send(&self, method: &str, path: &str, body: Option<String>) -> impl Future<Output = Result<(u16, String), String>>;
+}
+
+
+#[derive(Debug, PartialEq)]
+pub enum ApiError {
+    /// no answer, the server is down or the browser blocked the request
+    Network(String),
+    /// an answer with an error status
+    Status(u16),
+    /// an answer the roster does not understand
+    Invalid(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Network(e) => write!(f, "could not reach the server: {}", e),
+            ApiError::Status(400) => write!(f, "the server did not accept the student"),
+            ApiError::Status(401) | ApiError::Status(403) => write!(f, "the server did not allow the change"),
+            ApiError::Status(404) => write!(f, "the student does not exist any more"),
+            ApiError::Status(409) => write!(f, "the student was changed at the same time, reload and try again"),
+            ApiError::Status(status) => write!(f, "the server answered with status {}", status),
+            ApiError::Invalid(e) => write!(f, "the answer of the server can not be read: {}", e),
+        }
+    }
+}
+
+
+/// a contact as the v2 api sends it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub kind: String,
+    pub value: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+#[derive(Deserialize)]
+struct StudentV2 {
+    id: String,
+    name: NameV2,
+    contacts: Vec<Contact>,
+}
+
+#[derive(Deserialize)]
+struct NameV2 {
+    given: String,
+    family: Option<String>,
+    display: String,
+}
+
+/// a line of the roster
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Row {
+    pub id: String,
+    pub given: String,
+    pub family: String,
+    // the full name, shown in the table
+    pub name: String,
+    pub email: String,
+    pub mobile: String,
+    // the contacts besides the primary email and mobile, the roster does not show them but keeps them on updates
+    pub others: Vec<Contact>,
+}
+
+impl From<StudentV2> for Row {
+    fn from(student: StudentV2) -> Self {
+        let mut contacts = student.contacts;
+        let mut take_primary = |kind: &str| {
+            let index = contacts
+                .iter()
+                .position(|c| c.kind == kind && c.primary)
+                .or_else(|| contacts.iter().position(|c| c.kind == kind));
+            index.map(|index| contacts.remove(index).value).unwrap_or_default()
+        };
+        let email = take_primary("email");
+        let mobile = take_primary("mobile");
+        Row {
+            id: student.id,
+            given: student.name.given,
+            family: student.name.family.unwrap_or_default(),
+            name: student.name.display,
+            email,
+            mobile,
+            others: contacts.into_iter().map(|c| Contact { primary: false, ..c }).collect(),
+        }
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(body).map_err(|e| ApiError::Invalid(e.to_string()))
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Given,
+    Family,
+    Email,
+    Mobile,
+}
+
+impl Field {
+    pub const ALL: [Field; 4] = [Field::Given, Field::Family, Field::Email, Field::Mobile];
+
+    /// the name of the form input
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Given => "given",
+            Field::Family => "family",
+            Field::Email => "email",
+            Field::Mobile => "mobile",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::Given => "Given name",
+            Field::Family => "Family name",
+            Field::Email => "Email",
+            Field::Mobile => "Mobile",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldError {
+    pub field: Field,
+    pub message: String,
+}
+
+/// what the create and edit form holds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Draft {
+    pub given: String,
+    pub family: String,
+    pub email: String,
+    pub mobile: String,
+}
+
+impl Draft {
+    pub fn from_row(row: &Row) -> Self {
+        Draft { given: row.given.clone(), family: row.family.clone(), email: row.email.clone(), mobile: row.mobile.clone() }
+    }
+
+    pub fn get(&self, field: Field) -> &str {
+        match field {
+            Field::Given => &self.given,
+            Field::Family => &self.family,
+            Field::Email => &self.email,
+            Field::Mobile => &self.mobile,
+        }
+    }
+
+    pub fn set(&mut self, field: Field, value: String) {
+        match field {
+            Field::Given => self.given = value,
+            Field::Family => self.family = value,
+            Field::Email => self.email = value,
+            Field::Mobile => self.mobile = value,
+        }
+    }
+
+    /// the checks the server makes too, so mistakes show up before anything is sent
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut error = |field, message: &str| errors.push(FieldError { field, message: message.to_string() });
+        if self.given.trim().is_empty() {
+            error(Field::Given, "a given name is required");
+        }
+        let email = self.email.trim();
+        if email.is_empty() {
+            error(Field::Email, "an email address is required");
+        } else if !email.contains('@') {
+            error(Field::Email, "not a valid email address");
+        }
+        let mobile = self.mobile.trim();
+        if mobile.is_empty() {
+            error(Field::Mobile, "a mobile number is required");
+        } else if !mobile.chars().all(|c| c.is_ascii_digit()) {
+            error(Field::Mobile, "only digits please");
+        }
+        errors
+    }
+
+    /// the body of `POST /v2/students` and `PUT /v2/students/{id}`
+    fn input(&self, others: &[Contact]) -> String {
+        let family = Some(self.family.trim()).filter(|f| !f.is_empty());
+        let primary = [("email", &self.email), ("mobile", &self.mobile)]
+            .into_iter()
+            .map(|(kind, value)| Contact { kind: kind.to_string(), value: value.trim().to_string(), primary: true });
+        let contacts: Vec<Contact> = primary.chain(others.iter().cloned()).collect();
+        json!({ "name": { "given": self.given.trim(), "family": family }, "contacts": contacts }).to_string()
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    Email,
+    Mobile,
+}
+
+impl Column {
+    pub const ALL: [Column; 3] = [Column::Name, Column::Email, Column::Mobile];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Email => "email",
+            Column::Mobile => "mobile",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Email => "Email",
+            Column::Mobile => "Mobile",
+        }
+    }
+
+    fn value(self, row: &Row) -> String {
+        match self {
+            Column::Name => row.name.to_lowercase(),
+            Column::Email => row.email.to_lowercase(),
+            Column::Mobile => row.mobile.clone(),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, String> {
+        Column::ALL.into_iter().find(|c| c.key() == key).ok_or_else(|| format!("unknown column {}", key))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sort {
+    pub column: Column,
+    pub ascending: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Sort { column: Column::Name, ascending: true }
+    }
+}
+
+impl Sort {
+    /// sort by `column`, the other way round when it is sorted by it already
+    pub fn toggle(self, column: Column) -> Sort {
+        Sort { column, ascending: self.column != column || !self.ascending }
+    }
+
+    pub fn apply(self, rows: &mut [Row]) {
+        rows.sort_by(|a, b| {
+            let order = self.column.value(a).cmp(&self.column.value(b)).then_with(|| a.id.cmp(&b.id));
+            if self.ascending { order } else { order.reverse() }
+        });
+    }
+}
+
+
+/// how saving the form went
+#[derive(Debug, PartialEq)]
+pub enum Saved {
+    Done,
+    /// nothing was sent, the form has mistakes
+    Invalid(Vec<FieldError>),
+    /// the server could not be reached or refused, see `Roster::error`
+    Failed,
+}
+
+#[derive(Default)]
+struct State {
+    rows: Vec<Row>,
+    sort: Sort,
+    loaded: bool,
+    error: Option<String>,
+}
+
+/// the students of the roster and what was done to them, everything but the DOM.
+///
+/// The state is never borrowed while a request is waiting, so the page can render it any time.
+pub struct Roster<T> {
+    transport: T,
+    state: RefCell<State>,
+}
+
+impl<T: Transport> Roster<T> {
+    pub fn new(transport: T) -> Self {
+        Roster { transport, state: RefCell::new(State::default()) }
+    }
+
+    /// the students sorted as chosen
+    pub fn rows(&self) -> Vec<Row> {
+        let state = self.state.borrow();
+        let mut rows = state.rows.clone();
+        state.sort.apply(&mut rows);
+        rows
+    }
+
+    pub fn row(&self, id: &str) -> Option<Row> {
+        self.state.borrow().rows.iter().find(|r| r.id == id).cloned()
+    }
+
+    pub fn sort(&self) -> Sort {
+        self.state.borrow().sort
+    }
+
+    pub fn sort_by(&self, column: Column) {
+        let mut state = self.state.borrow_mut();
+        state.sort = state.sort.toggle(column);
+    }
+
+    /// whether the students were loaded once
+    pub fn loaded(&self) -> bool {
+        self.state.borrow().loaded
+    }
+
+    /// what went wrong with the last request, `None` when it worked
+    pub fn error(&self) -> Option<String> {
+        self.state.borrow().error.clone()
+    }
+
+    /// load all students again
+    pub async fn load(&self) {
+        let loaded = self.request("GET", STUDENTS.to_string(), None).await.and_then(|body| parse::<Vec<StudentV2>>(&body));
+        let mut state = self.state.borrow_mut();
+        if let Ok(students) = self.finish(&mut state, loaded) {
+            state.rows = students.into_iter().map(Row::from).collect();
+            state.loaded = true;
+        }
+    }
+
+    /// create a student from `draft`, or change the student `id`
+    pub async fn save(&self, id: Option<&str>, draft: &Draft) -> Saved {
+        let errors = draft.validate();
+        if !errors.is_empty() {
+            return Saved::Invalid(errors);
+        }
+        let saved = match id {
+            None => self.request("POST", STUDENTS.to_string(), Some(draft.input(&[]))).await,
+            Some(id) => {
+                let others = self.row(id).map(|row| row.others).unwrap_or_default();
+                self.request("PUT", format!("{}/{}", STUDENTS, id), Some(draft.input(&others))).await
+            }
+        };
+        let saved = saved.and_then(|body| parse::<StudentV2>(&body)).map(Row::from);
+        let mut state = self.state.borrow_mut();
+        match self.finish(&mut state, saved) {
+            Ok(row) => {
+                match state.rows.iter_mut().find(|r| r.id == row.id) {
+                    Some(existing) => *existing = row,
+                    None => state.rows.push(row),
+                }
+                Saved::Done
+            }
+            Err(ApiError::Status(404)) => {
+                state.rows.retain(|r| Some(r.id.as_str()) != id);
+                Saved::Failed
+            }
+            Err(_) => Saved::Failed,
+        }
+    }
+
+    pub async fn delete(&self, id: &str) {
+        let deleted = self.request("DELETE", format!("{}/{}", STUDENTS, id), None).await;
+        let mut state = self.state.borrow_mut();
+        // a student which is gone already is as good as deleted
+        if matches!(self.finish(&mut state, deleted), Ok(_) | Err(ApiError::Status(404))) {
+            state.rows.retain(|r| r.id != id);
+        }
+    }
+
+    async fn request(&self, method: &str, path: String, body: Option<String>) -> Result<String, ApiError> {
+        let (status, body) = self.transport.send(method, &path, body).await.map_err(ApiError::Network)?;
+        match status {
+            200..=299 => Ok(body),
+            _ => Err(ApiError::Status(status)),
+        }
+    }
+
+    // remember the error of a request, or forget the last one when it worked
+    fn finish<R>(&self, state: &mut State, result: Result<R, ApiError>) -> Result<R, ApiError> {
+        state.error = result.as_ref().err().map(ToString::to_string);
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::VecDeque, pin::pin, task::{Context, Poll, Waker}};
+
+    /// answers from a list and remembers what was sent
+    #[derive(Default)]
+    struct Fake {
+        answers: RefCell<VecDeque<Result<(u16, String), String>>>,
+        sent: RefCell<Vec<(String, String, Option<String>)>>,
+    }
+
+    impl Fake {
+        fn answer(&self, status: u16, body: serde_json::Value) {
+            self.answers.borrow_mut().push_back(Ok((status, body.to_string())));
+        }
+    }
+
+    impl Transport for &Fake {
+        async fn send(&self, method: &str, path: &str, body: Option<String>) -> Result<(u16, String), String> {
+            self.sent.borrow_mut().push((method.to_string(), path.to_string(), body));
+            self.answers.borrow_mut().pop_front().expect("no answer left")
+        }
+    }
+
+    // the fake answers right away, so a single poll finishes every request
+    fn block_on<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the fake transport never waits"),
+        }
+    }
+
+    fn student(id: &str, given: &str, email: &str, mobile: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "name": { "given": given, "family": null, "display": given },
+            "contacts": [
+                { "kind": "email", "value": email, "primary": true },
+                { "kind": "mobile", "value": mobile, "primary": true },
+            ],
+            "created_at": null,
+            "updated_at": null
+        })
+    }
+
+    fn draft(given: &str, email: &str, mobile: &str) -> Draft {
+        Draft { given: given.into(), family: String::new(), email: email.into(), mobile: mobile.into() }
+    }
+
+    #[test]
+    fn validation() {
+        assert!(draft("Aman", "aman@example.com", "9876543210").validate().is_empty());
+        let errors = draft(" ", "aman.example.com", "98765-43210").validate();
+        let fields: Vec<Field> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, [Field::Given, Field::Email, Field::Mobile]);
+        assert_eq!(draft("Aman", "", "").validate().len(), 2);
+    }
+
+    #[test]
+    fn sorting() {
+        let fake = Fake::default();
+        fake.answer(200, json!([
+            student("1", "carla", "a@example.com", "3"),
+            student("2", "Aman", "c@example.com", "1"),
+            student("3", "Bela", "b@example.com", "2"),
+        ]));
+        let roster = Roster::new(&fake);
+        assert!(!roster.loaded());
+        block_on(roster.load());
+        assert!(roster.loaded());
+        let names = |roster: &Roster<&Fake>| roster.rows().into_iter().map(|r| r.name).collect::<Vec<_>>();
+        assert_eq!(names(&roster), ["Aman", "Bela", "carla"]);
+        roster.sort_by(Column::Name);
+        assert_eq!(names(&roster), ["carla", "Bela", "Aman"]);
+        roster.sort_by(Column::Email);
+        assert_eq!(roster.sort(), Sort { column: Column::Email, ascending: true });
+        assert_eq!(names(&roster), ["carla", "Bela", "Aman"]);
+        assert_eq!("mobile".parse::<Column>(), Ok(Column::Mobile));
+        assert!("age".parse::<Column>().is_err());
+    }
+
+    #[test]
+    fn create_edit_delete() {
+        let fake = Fake::default();
+        let mut stored = student("1", "Aman", "aman@example.com", "9876543210");
+        stored["contacts"].as_array_mut().unwrap().push(json!({ "kind": "phone", "value": "0201234567", "primary": false }));
+        fake.answer(200, json!([stored]));
+        let roster = Roster::new(&fake);
+        block_on(roster.load());
+
+        // nothing is sent for a form with mistakes
+        assert!(matches!(block_on(roster.save(None, &draft("", "x", "1"))), Saved::Invalid(_)));
+        assert_eq!(fake.sent.borrow().len(), 1);
+
+        fake.answer(201, student("2", "Bela", "bela@example.com", "123"));
+        assert_eq!(block_on(roster.save(None, &draft(" Bela ", "bela@example.com", "123"))), Saved::Done);
+        let (method, path, body) = fake.sent.borrow()[1].clone();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/v2/students"));
+        let body: serde_json::Value = serde_json::from_str(&body.unwrap()).unwrap();
+        assert_eq!(body["name"], json!({ "given": "Bela", "family": null }));
+        assert_eq!(roster.rows().len(), 2);
+
+        // the phone number the roster does not show is sent back unchanged
+        fake.answer(200, student("1", "Aman V", "aman@example.com", "9876543210"));
+        let edited = Draft { given: "Aman V".into(), ..Draft::from_row(&roster.row("1").unwrap()) };
+        assert_eq!(block_on(roster.save(Some("1"), &edited)), Saved::Done);
+        let (method, path, body) = fake.sent.borrow()[2].clone();
+        assert_eq!((method.as_str(), path.as_str()), ("PUT", "/v2/students/1"));
+        assert!(body.unwrap().contains("0201234567"));
+        assert_eq!(roster.row("1").unwrap().name, "Aman V");
+
+        fake.answer(200, json!(null));
+        block_on(roster.delete("2"));
+        assert_eq!(fake.sent.borrow()[3].0, "DELETE");
+        assert_eq!(roster.rows().len(), 1);
+    }
+
+    #[test]
+    fn errors_are_kept_until_the_next_request_works() {
+        let fake = Fake::default();
+        let roster = Roster::new(&fake);
+        fake.answers.borrow_mut().push_back(Err("connection refused".into()));
+        block_on(roster.load());
+        assert_eq!(roster.error().unwrap(), "could not reach the server: connection refused");
+        assert!(!roster.loaded());
+
+        fake.answer(200, json!([student("1", "Aman", "aman@example.com", "1")]));
+        block_on(roster.load());
+        assert_eq!(roster.error(), None);
+
+        fake.answer(409, json!(null));
+        assert_eq!(block_on(roster.save(Some("1"), &draft("Aman", "aman@example.com", "2"))), Saved::Failed);
+        assert!(roster.error().unwrap().contains("reload"));
+        fake.answer(200, json!({ "unexpected": true }));
+        assert_eq!(block_on(roster.save(None, &draft("Bela", "bela@example.com", "2"))), Saved::Failed);
+        assert!(roster.error().unwrap().starts_with("the answer of the server can not be read"));
+
+        // gone on the server, so gone in the roster too
+        fake.answer(404, json!(null));
+        assert_eq!(block_on(roster.save(Some("1"), &draft("Aman", "aman@example.com", "2"))), Saved::Failed);
+        assert!(roster.row("1").is_none());
+        assert_eq!(roster.error().unwrap(), "the student does not exist any more");
+    }
+}
@@ -0,0 +1,279 @@
+use std::{cell::RefCell, future::Future, rc::Rc};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Document, Element, Event, HtmlInputElement};
+use crate::{fetch::Fetch, roster::{Column, Draft, Field, FieldError, Roster, Saved}};
+
+
+/// the page around a `Roster`, it only turns the roster and the form into DOM nodes and back
+struct Page {
+    roster: Roster<Fetch>,
+    document: Document,
+    container: Element,
+    form: RefCell<Form>,
+}
+
+#[derive(Default)]
+struct Form {
+    // the id of the student being edited, `None` while a new one is entered
+    editing: Option<String>,
+    draft: Draft,
+    errors: Vec<FieldError>,
+    // a request is running, the buttons are disabled until it is done
+    busy: bool,
+}
+
+
+/// show the students of the api at `base_url` in `container`: a table sorted by a click on a
+/// column and a form to add students or edit one. An empty `base_url` is the origin of the page,
+/// another origin has to be allowed with CORS_ALLOWED_ORIGINS on the server.
+///
+/// await startRoster(document.getElementById("roster"), "");
+///
+/// Resolves once the students were loaded the first time, failed requests are shown in the page.
+#[wasm_bindgen(js_name = startRoster)]
+pub async fn start_roster(container: Element, base_url: String) -> Result<(), JsValue> {
+    let document = container.owner_document().ok_or_else(|| JsValue::from_str("the container is not part of a document"))?;
+    let page = Rc::new(Page { roster: Roster::new(Fetch::new(base_url)), document, container, form: RefCell::default() });
+    // one listener each for the whole roster, the DOM below is replaced on every render
+    listen(&page, "click", on_click)?;
+    listen(&page, "submit", on_submit)?;
+    page.render()?;
+    page.roster.load().await;
+    page.render()
+}
+
+fn listen(page: &Rc<Page>, event: &str, handler: fn(&Rc<Page>, Event)) -> Result<(), JsValue> {
+    let listening = page.clone();
+    let closure = Closure::<dyn FnMut(Event)>::new(move |event: Event| handler(&listening, event));
+    page.container.add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+    // the listener stays as long as the page
+    closure.forget();
+    Ok(())
+}
+
+fn on_click(page: &Rc<Page>, event: Event) {
+    let button = event
+        .target()
+        .and_then(|target| target.dyn_into::<Element>().ok())
+        .and_then(|target| target.closest("[data-action]").ok().flatten());
+    let Some(button) = button else { return };
+    let id = button.get_attribute("data-id").unwrap_or_default();
+    match button.get_attribute("data-action").as_deref() {
+        Some("sort") => match button.get_attribute("data-column").and_then(|column| column.parse::<Column>().ok()) {
+            Some(column) => page.roster.sort_by(column),
+            None => return,
+        },
+        Some("edit") => match page.roster.row(&id) {
+            Some(row) => *page.form.borrow_mut() = Form { editing: Some(id), draft: Draft::from_row(&row), ..Form::default() },
+            None => return,
+        },
+        Some("cancel") => *page.form.borrow_mut() = Form::default(),
+        Some("delete") => {
+            let name = page.roster.row(&id).map(|row| row.name).unwrap_or_default();
+            let confirmed = web_sys::window().and_then(|w| w.confirm_with_message(&format!("Delete {}?", name)).ok());
+            if confirmed == Some(true) {
+                let deleting = page.clone();
+                run(page, async move { deleting.roster.delete(&id).await });
+            }
+            return;
+        }
+        Some("reload") => {
+            let loading = page.clone();
+            run(page, async move { loading.roster.load().await });
+            return;
+        }
+        _ => return,
+    }
+    page.show();
+}
+
+fn on_submit(page: &Rc<Page>, event: Event) {
+    event.prevent_default();
+    let draft = page.read_form();
+    let editing = {
+        let mut form = page.form.borrow_mut();
+        form.draft = draft.clone();
+        form.editing.clone()
+    };
+    let saving = page.clone();
+    run(page, async move {
+        let saved = saving.roster.save(editing.as_deref(), &draft).await;
+        let mut form = saving.form.borrow_mut();
+        match saved {
+            Saved::Done => *form = Form::default(),
+            Saved::Invalid(errors) => form.errors = errors,
+            // the draft stays so nothing typed is lost, the reason is shown above the form
+            Saved::Failed => form.errors.clear(),
+        }
+    });
+}
+
+/// do `work` in the background with the buttons disabled, nothing starts while other work runs
+fn run(page: &Rc<Page>, work: impl Future<Output = ()> + 'static) {
+    if std::mem::replace(&mut page.form.borrow_mut().busy, true) {
+        return;
+    }
+    page.show();
+    let page = page.clone();
+    spawn_local(async move {
+        work.await;
+        page.form.borrow_mut().busy = false;
+        page.show();
+    });
+}
+
+
+/// append `child` to `parent` and give it back
+fn append(parent: &Element, child: Element) -> Result<Element, JsValue> {
+    parent.append_child(&child)?;
+    Ok(child)
+}
+
+
+impl Page {
+    fn show(&self) {
+        if let Err(e) = self.render() {
+            web_sys::console::error_1(&e);
+        }
+    }
+
+    fn read_form(&self) -> Draft {
+        let mut draft = Draft::default();
+        for field in Field::ALL {
+            let input = self.container.query_selector(&format!("input[name={}]", field.name())).ok().flatten();
+            if let Some(input) = input.and_then(|input| input.dyn_into::<HtmlInputElement>().ok()) {
+                draft.set(field, input.value());
+            }
+        }
+        draft
+    }
+
+    /// replace what the container shows. All values are set as text, never as markup.
+    fn render(&self) -> Result<(), JsValue> {
+        let form = self.form.borrow();
+        let root = self.element("div", "roster")?;
+        if let Some(error) = self.roster.error() {
+            let alert = self.text("p", "error", &error)?;
+            alert.set_attribute("role", "alert")?;
+            root.append_child(&alert)?;
+        }
+        append(&root, self.render_form(&form)?)?;
+        append(&root, self.render_table(&form)?)?;
+        self.container.set_text_content(None);
+        self.container.append_child(&root)?;
+        Ok(())
+    }
+
+    fn render_form(&self, form: &Form) -> Result<Element, JsValue> {
+        let element = self.element("form", "student-form")?;
+        // the roster checks the fields itself and shows the mistakes next to them
+        element.set_attribute("novalidate", "")?;
+        let title = if form.editing.is_some() { "Edit student" } else { "New student" };
+        append(&element, self.text("h2", "", title)?)?;
+        for field in Field::ALL {
+            let label = self.text("label", "", field.label())?;
+            let input = self.document.create_element("input")?;
+            input.set_attribute("name", field.name())?;
+            input.set_attribute("type", match field {
+                Field::Email => "email",
+                Field::Mobile => "tel",
+                _ => "text",
+            })?;
+            input.set_attribute("value", form.draft.get(field))?;
+            label.append_child(&input)?;
+            if let Some(error) = form.errors.iter().find(|e| e.field == field) {
+                input.set_attribute("aria-invalid", "true")?;
+                append(&label, self.text("span", "field-error", &error.message)?)?;
+            }
+            element.append_child(&label)?;
+        }
+        let submit = self.button(if form.editing.is_some() { "Save" } else { "Add" }, None, form.busy)?;
+        submit.set_attribute("type", "submit")?;
+        element.append_child(&submit)?;
+        if form.editing.is_some() {
+            append(&element, self.button("Cancel", Some(("cancel", "")), form.busy)?)?;
+        }
+        Ok(element)
+    }
+
+    fn render_table(&self, form: &Form) -> Result<Element, JsValue> {
+        let section = self.element("div", "students")?;
+        let rows = self.roster.rows();
+        if !self.roster.loaded() {
+            // an error is shown already when loading failed
+            if self.roster.error().is_none() {
+                append(&section, self.text("p", "loading", "Loading students...")?)?;
+            }
+        } else if rows.is_empty() {
+            append(&section, self.text("p", "empty", "No students yet.")?)?;
+        } else {
+            let table = self.element("table", "")?;
+            let head = self.element("tr", "")?;
+            let sort = self.roster.sort();
+            for column in Column::ALL {
+                let cell = self.element("th", "")?;
+                let mut label = column.label().to_string();
+                if sort.column == column {
+                    cell.set_attribute("aria-sort", if sort.ascending { "ascending" } else { "descending" })?;
+                    label.push_str(if sort.ascending { " ▲" } else { " ▼" });
+                }
+                let button = self.button(&label, Some(("sort", "")), false)?;
+                button.set_attribute("data-column", column.key())?;
+                cell.append_child(&button)?;
+                head.append_child(&cell)?;
+            }
+            append(&head, self.element("th", "")?)?;
+            let thead = self.element("thead", "")?;
+            thead.append_child(&head)?;
+            table.append_child(&thead)?;
+
+            let body = self.element("tbody", "")?;
+            for row in rows {
+                let line = self.element("tr", "")?;
+                for value in [&row.name, &row.email, &row.mobile] {
+                    append(&line, self.text("td", "", value)?)?;
+                }
+                let actions = self.element("td", "actions")?;
+                append(&actions, self.button("Edit", Some(("edit", &row.id)), form.busy)?)?;
+                append(&actions, self.button("Delete", Some(("delete", &row.id)), form.busy)?)?;
+                line.append_child(&actions)?;
+                body.append_child(&line)?;
+            }
+            table.append_child(&body)?;
+            section.append_child(&table)?;
+        }
+        append(&section, self.button("Reload", Some(("reload", "")), form.busy)?)?;
+        Ok(section)
+    }
+
+    fn element(&self, tag: &str, class: &str) -> Result<Element, JsValue> {
+        let element = self.document.create_element(tag)?;
+        if !class.is_empty() {
+            element.set_class_name(class);
+        }
+        Ok(element)
+    }
+
+    fn text(&self, tag: &str, class: &str, text: &str) -> Result<Element, JsValue> {
+        let element = self.element(tag, class)?;
+        element.set_text_content(Some(text));
+        Ok(element)
+    }
+
+    /// a button which runs the action `(action, id)` when clicked, see `on_click`
+    fn button(&self, label: &str, action: Option<(&str, &str)>, disabled: bool) -> Result<Element, JsValue> {
+        let button = self.text("button", "", label)?;
+        button.set_attribute("type", "button")?;
+        if let Some((action, id)) = action {
+            button.set_attribute("data-action", action)?;
+            if !id.is_empty() {
+                button.set_attribute("data-id", id)?;
+            }
+        }
+        if disabled {
+            button.set_attribute("disabled", "")?;
+        }
+        Ok(button)
+    }
+}
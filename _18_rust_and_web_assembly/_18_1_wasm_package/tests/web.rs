@@ -0,0 +1,79 @@
+// Rendering into a real DOM, run in a headless browser:
+//
+// wasm-pack test --headless --firefox
+// wasm-pack test --headless --chrome
+//
+// `cargo test` skips this file, CI runs both browsers, see .github/workflows/wasm.yml
+#![cfg(target_arch = "wasm32")]
+
+use _18_1_wasm_package::{decode_image, view::start_roster, Student};
+use wasm_bindgen_test::*;
+use web_sys::{window, Element};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+
+fn container() -> Element {
+    let document = window().unwrap().document().unwrap();
+    let container = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&container).unwrap();
+    container
+}
+
+
+#[wasm_bindgen_test]
+fn names_are_text_not_markup() {
+    let container = container();
+    let mut student = Student::new("<img src=x onerror=alert(1)>".into(), 22, true, vec![90, 85, 88]);
+    student.render(&container).unwrap();
+
+    assert!(container.query_selector("img").unwrap().is_none());
+    let text = container.text_content().unwrap();
+    assert!(text.contains("Name: <img src=x onerror=alert(1)>"), "{}", text);
+    assert!(text.contains("Grades: [90, 85, 88]"), "{}", text);
+}
+
+#[wasm_bindgen_test]
+fn renders_into_the_given_container_only() {
+    let first = container();
+    let second = container();
+    let mut student = Student::new("Alice".into(), 22, true, vec![90]);
+    student.render(&second).unwrap();
+
+    assert_eq!(first.child_element_count(), 0);
+    assert_eq!(second.query_selector_all(".student").unwrap().length(), 1);
+}
+
+#[wasm_bindgen_test]
+fn updates_in_place() {
+    let container = container();
+    let mut student = Student::new("Alice".into(), 22, true, vec![90]);
+    student.render(&container).unwrap();
+    student.render(&container).unwrap();
+    assert_eq!(container.query_selector_all(".student").unwrap().length(), 1);
+
+    student.set_name("Bela".into()).unwrap();
+    student.set_active(false).unwrap();
+    assert_eq!(container.query_selector_all(".student").unwrap().length(), 1);
+    let text = container.text_content().unwrap();
+    assert!(text.contains("Name: Bela") && text.contains("Active: false"), "{}", text);
+}
+
+#[wasm_bindgen_test]
+fn bad_images_are_errors_not_panics() {
+    assert!(decode_image(b"not an image").is_err());
+    let png = decode_image(&[137, 80, 78, 71, 13, 10, 26, 10]);
+    assert!(png.is_err());
+}
+
+#[wasm_bindgen_test]
+async fn roster_shows_network_errors_inline() {
+    let container = container();
+    // nothing listens on the discard port
+    start_roster(container.clone(), "http://127.0.0.1:9".into()).await.unwrap();
+    let alert = container.query_selector("[role=alert]").unwrap().expect("the error is not shown");
+    assert!(alert.text_content().unwrap().starts_with("could not reach the server"));
+    // the form stays to try again
+    assert!(container.query_selector("form input[name=given]").unwrap().is_some());
+    assert!(container.query_selector("button[data-action=reload]").unwrap().is_some());
+}
@@ -1,10 +1,14 @@
-import init, { Student } from "./pkg/_18_1_wasm_package.js";
+import init, { startRoster } from "./pkg/_18_1_wasm_package.js";
 
+// The roster talks to the student api of the axum server (_16_http_server/_16_1_axum).
+// Served by the server itself (STATIC_DIR and WASM_PKG_DIR) the api is on the same origin,
+// otherwise pass it like index.html?api=http://127.0.0.1:4500 and allow this page's origin
+// with CORS_ALLOWED_ORIGINS on the server.
 async function run() {
     await init();
 
-    let student = new Student("Alice", 22, true, [90, 85, 88]);
-    student.render(); // This updates the HTML page
+    const api = new URLSearchParams(window.location.search).get("api") ?? "";
+    await startRoster(document.getElementById("roster"), api);
 }
 
-run();
\ No newline at end of file
+run().catch(console.error);
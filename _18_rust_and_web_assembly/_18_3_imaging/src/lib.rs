@@ -0,0 +1,288 @@
+//! decoding, changing and encoding PNG and JPEG images.
+//!
+//! Shared by the wasm package (`_18_1_wasm_package`) and the student photos of the axum server,
+//! so a thumbnail made in the browser looks like one made on the server.
+
+use std::{fmt, io::Cursor, str::FromStr};
+use image::{imageops::FilterType, io::{Limits, Reader}, DynamicImage, ImageFormat, ImageOutputFormat};
+
+
+/// widest and highest image which is decoded
+pub const MAX_SIDE: u32 = 8192;
+
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// not a PNG or JPEG, or broken
+    Decode(String),
+    /// larger than `MAX_SIDE`
+    TooLarge { width: u32, height: u32 },
+    /// an argument out of range
+    Invalid(String),
+    Encode(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Decode(e) => write!(f, "the image can not be read: {}", e),
+            Error::TooLarge { width, height } => write!(f, "the image is {}x{}, at most {}x{} is allowed", width, height, MAX_SIDE, MAX_SIDE),
+            Error::Invalid(e) => write!(f, "{}", e),
+            Error::Encode(e) => write!(f, "the image can not be written: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+
+/// how pixels are mixed when resizing, from fastest to smoothest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name.to_lowercase().as_str() {
+            "nearest" => Ok(Filter::Nearest),
+            "triangle" | "bilinear" => Ok(Filter::Triangle),
+            "catmullrom" | "catmull-rom" | "bicubic" => Ok(Filter::CatmullRom),
+            "gaussian" => Ok(Filter::Gaussian),
+            "lanczos3" | "lanczos" => Ok(Filter::Lanczos3),
+            _ => Err(Error::Invalid(format!("unknown filter {}, use nearest, triangle, catmullrom, gaussian or lanczos3", name))),
+        }
+    }
+}
+
+impl From<Filter> for FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Png,
+    Jpeg,
+}
+
+impl Format {
+    pub fn mime(self) -> &'static str {
+        match self {
+            Format::Png => "image/png",
+            Format::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+
+/// the format and size of an image, read from its header only
+pub fn probe(bytes: &[u8]) -> Result<(Format, u32, u32), Error> {
+    let reader = Reader::new(Cursor::new(bytes)).with_guessed_format().map_err(|e| Error::Decode(e.to_string()))?;
+    let format = match reader.format() {
+        Some(ImageFormat::Png) => Format::Png,
+        Some(ImageFormat::Jpeg) => Format::Jpeg,
+        _ => return Err(Error::Decode("only PNG and JPEG images are supported".to_string())),
+    };
+    let (width, height) = reader.into_dimensions().map_err(|e| Error::Decode(e.to_string()))?;
+    Ok((format, width, height))
+}
+
+/// a PNG or JPEG image, up to `MAX_SIDE` pixels wide and high. Metadata like EXIF is not kept.
+pub fn decode(bytes: &[u8]) -> Result<DynamicImage, Error> {
+    let (format, width, height) = probe(bytes)?;
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(Error::TooLarge { width, height });
+    }
+    let mut reader = Reader::with_format(Cursor::new(bytes), match format {
+        Format::Png => ImageFormat::Png,
+        Format::Jpeg => ImageFormat::Jpeg,
+    });
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SIDE);
+    limits.max_image_height = Some(MAX_SIDE);
+    reader.limits(limits);
+    reader.decode().map_err(|e| Error::Decode(e.to_string()))
+}
+
+/// `image` at exactly `width`x`height`
+pub fn resize(image: &DynamicImage, width: u32, height: u32, filter: Filter) -> Result<DynamicImage, Error> {
+    size(width, height)?;
+    Ok(image.resize_exact(width, height, filter.into()))
+}
+
+/// `image` as large as fits into `width`x`height`, keeping its aspect ratio
+pub fn fit(image: &DynamicImage, width: u32, height: u32, filter: Filter) -> Result<DynamicImage, Error> {
+    size(width, height)?;
+    Ok(image.resize(width, height, filter.into()))
+}
+
+/// `image` scaled and cut to fill exactly `width`x`height`, like CSS `object-fit: cover`
+pub fn cover(image: &DynamicImage, width: u32, height: u32, filter: Filter) -> Result<DynamicImage, Error> {
+    size(width, height)?;
+    Ok(image.resize_to_fill(width, height, filter.into()))
+}
+
+pub fn crop(image: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> Result<DynamicImage, Error> {
+    size(width, height)?;
+    let inside = x.checked_add(width).is_some_and(|right| right <= image.width()) && y.checked_add(height).is_some_and(|bottom| bottom <= image.height());
+    if !inside {
+        return Err(Error::Invalid(format!("{}x{} at {},{} is outside of the {}x{} image", width, height, x, y, image.width(), image.height())));
+    }
+    Ok(image.crop_imm(x, y, width, height))
+}
+
+/// turn `image` clockwise by a multiple of 90 degrees, negative degrees turn it the other way
+pub fn rotate(image: &DynamicImage, degrees: i32) -> Result<DynamicImage, Error> {
+    match degrees.rem_euclid(360) {
+        0 => Ok(image.clone()),
+        90 => Ok(image.rotate90()),
+        180 => Ok(image.rotate180()),
+        270 => Ok(image.rotate270()),
+        _ => Err(Error::Invalid(format!("images can only be turned by multiples of 90 degrees, not {}", degrees))),
+    }
+}
+
+pub fn grayscale(image: &DynamicImage) -> DynamicImage {
+    image.grayscale()
+}
+
+/// a gaussian blur, `sigma` from 0.1 to 100 pixels
+pub fn blur(image: &DynamicImage, sigma: f32) -> Result<DynamicImage, Error> {
+    if !(0.1..=100.0).contains(&sigma) {
+        return Err(Error::Invalid(format!("blur must be between 0.1 and 100, not {}", sigma)));
+    }
+    Ok(image.blur(sigma))
+}
+
+/// add `value` from -255 to 255 to every channel
+pub fn brightness(image: &DynamicImage, value: i32) -> Result<DynamicImage, Error> {
+    if !(-255..=255).contains(&value) {
+        return Err(Error::Invalid(format!("brightness must be between -255 and 255, not {}", value)));
+    }
+    Ok(image.brighten(value))
+}
+
+/// more contrast for positive `percent`, less for negative ones, from -100 to 100
+pub fn contrast(image: &DynamicImage, percent: f32) -> Result<DynamicImage, Error> {
+    if !(-100.0..=100.0).contains(&percent) {
+        return Err(Error::Invalid(format!("contrast must be between -100 and 100, not {}", percent)));
+    }
+    Ok(image.adjust_contrast(percent))
+}
+
+/// `image` as PNG, or as JPEG with `quality` from 1 to 100. JPEG has no transparency, it is dropped.
+pub fn encode(image: &DynamicImage, format: Format, quality: u8) -> Result<Vec<u8>, Error> {
+    let mut bytes = Cursor::new(Vec::new());
+    let written = match format {
+        Format::Png => image.write_to(&mut bytes, ImageOutputFormat::Png),
+        Format::Jpeg => {
+            if !(1..=100).contains(&quality) {
+                return Err(Error::Invalid(format!("quality must be between 1 and 100, not {}", quality)));
+            }
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut bytes, ImageOutputFormat::Jpeg(quality))
+        }
+    };
+    written.map_err(|e| Error::Encode(e.to_string()))?;
+    Ok(bytes.into_inner())
+}
+
+fn size(width: u32, height: u32) -> Result<(), Error> {
+    if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+        return Err(Error::Invalid(format!("{}x{} is not a size from 1x1 to {}x{}", width, height, MAX_SIDE, MAX_SIDE)));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    // red on the left half, blue on the right half
+    fn sample(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, _| if x < width / 2 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) }))
+    }
+
+    #[test]
+    fn decode_and_encode() {
+        let png = encode(&sample(40, 20), Format::Png, 0).unwrap();
+        assert_eq!(probe(&png).unwrap(), (Format::Png, 40, 20));
+        let image = decode(&png).unwrap();
+        assert_eq!(image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+
+        let small = encode(&image, Format::Jpeg, 10).unwrap();
+        let large = encode(&image, Format::Jpeg, 100).unwrap();
+        assert_eq!(probe(&small).unwrap(), (Format::Jpeg, 40, 20));
+        assert!(small.len() < large.len());
+        assert!(matches!(encode(&image, Format::Jpeg, 0), Err(Error::Invalid(_))));
+
+        assert!(matches!(decode(b"GIF89a nope"), Err(Error::Decode(_))));
+        assert!(matches!(decode(&png[..30]), Err(Error::Decode(_))));
+        let huge = encode(&sample(MAX_SIDE + 1, 1), Format::Png, 0).unwrap();
+        assert_eq!(decode(&huge), Err(Error::TooLarge { width: MAX_SIDE + 1, height: 1 }));
+    }
+
+    #[test]
+    fn sizes() {
+        let image = sample(40, 20);
+        for filter in ["nearest", "triangle", "CatmullRom", "gaussian", "lanczos3"] {
+            let resized = resize(&image, 10, 30, filter.parse().unwrap()).unwrap();
+            assert_eq!(resized.dimensions(), (10, 30));
+        }
+        assert!("sharp".parse::<Filter>().is_err());
+        assert_eq!(fit(&image, 20, 20, Filter::Triangle).unwrap().dimensions(), (20, 10));
+        assert_eq!(cover(&image, 20, 20, Filter::Triangle).unwrap().dimensions(), (20, 20));
+        assert!(resize(&image, 0, 10, Filter::Nearest).is_err());
+
+        let right = crop(&image, 20, 0, 20, 20).unwrap();
+        assert_eq!((right.dimensions(), right.get_pixel(0, 0)), ((20, 20), Rgba([0, 0, 255, 255])));
+        assert!(crop(&image, 30, 0, 20, 20).is_err());
+        assert!(crop(&image, u32::MAX, 0, 2, 2).is_err());
+    }
+
+    #[test]
+    fn rotating() {
+        let image = sample(40, 20);
+        assert_eq!(rotate(&image, 90).unwrap().dimensions(), (20, 40));
+        // red ends up at the top when turned clockwise
+        assert_eq!(rotate(&image, 90).unwrap().get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(rotate(&image, -90).unwrap().get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(rotate(&image, 180).unwrap().get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(rotate(&image, 360).unwrap().dimensions(), (40, 20));
+        assert!(rotate(&image, 45).is_err());
+    }
+
+    #[test]
+    fn colors() {
+        let image = sample(40, 20);
+        let gray = grayscale(&image).to_rgba8();
+        let [r, g, b, _] = gray.get_pixel(0, 0).0;
+        assert!(r == g && g == b);
+
+        assert_eq!(brightness(&image, 255).unwrap().get_pixel(39, 0), Rgba([255, 255, 255, 255]));
+        assert_eq!(brightness(&image, -255).unwrap().get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        assert!(brightness(&image, 256).is_err());
+        assert!(contrast(&image, 50.0).is_ok() && contrast(&image, 101.0).is_err());
+
+        // the border between red and blue is mixed
+        let blurred = blur(&image, 2.0).unwrap();
+        let [r, _, b, _] = blurred.get_pixel(20, 10).0;
+        assert!(r > 0 && b > 0);
+        assert!(blur(&image, 0.0).is_err());
+    }
+}